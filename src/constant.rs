@@ -3,6 +3,9 @@ use std::env;
 use std::sync::{Arc, Mutex};
 
 use fernet::Fernet;
+use serde::{Deserialize, Serialize};
+
+use crate::squire::{settings, storage, tokens};
 
 pub static IMAGE_FORMATS: [&str; 7] = ["jpeg", "jpg", "png", "gif", "bmp", "svg", "webp"];
 pub static SECURE_INDEX: &str = "SECURE_INDEX";
@@ -49,23 +52,62 @@ pub fn build_info() -> Arc<MetaData> {
     Arc::new(metadata)
 }
 
+/// Struct to store the information of an individual session (device).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionEntry {
+    /// Username that the session belongs to.
+    pub username: String,
+    /// Random key that is also embedded in the encrypted `session_token` cookie.
+    pub key: String,
+    /// Epoch time when the session was created.
+    pub created: i64,
+    /// User agent of the device that created the session.
+    pub user_agent: String,
+    /// IP address of the device that created the session.
+    pub ip: String,
+}
+
+/// Struct to store a login challenge issued by the server.
+pub struct Challenge {
+    /// Epoch time when the challenge was issued.
+    pub issued: i64,
+    /// IP address of the client that requested the challenge.
+    pub peer: String,
+}
+
 /// Struct to store the session information.
 ///
 /// ## Fields
 ///
 /// * `tracker` - Used to log connection and streaming information without redundancy.
-/// * `mapping` - Used to store username and session token's payload as key value pairs.
+/// * `mapping` - Used to store session ID and the session's information as key value pairs.
+/// * `store` - Backend that persists the `mapping` beyond the lifetime of the server.
+/// * `challenges` - Used to store the login nonces issued by the server, along with the time and the client.
+/// * `tokens` - Used to store the API tokens created by the users.
 ///
 /// ## See Also:
 ///
 /// These fields are updated and used only for authenticated sessions.
 pub struct Session {
     pub tracker: Mutex<HashMap<String, String>>,
-    pub mapping: Mutex<HashMap<String, String>>,
+    pub mapping: Mutex<HashMap<String, SessionEntry>>,
+    pub store: Box<dyn storage::SessionStore>,
+    pub challenges: Mutex<HashMap<String, Challenge>>,
+    pub tokens: tokens::Tokens,
 }
 
+impl Session {
+    /// Writes the current session mapping to the configured store.
+    pub fn persist(&self) {
+        self.store.save(&self.mapping.lock().unwrap());
+    }
+}
 
-/// Instantiates the `Session` struct with empty `HashMap` for both `tracker` and `mapping` fields.
+/// Instantiates the `Session` struct with an empty `tracker` and the `mapping` restored from the session store.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
 ///
 /// ## See Also
 ///
@@ -74,20 +116,32 @@ pub struct Session {
 /// # Returns
 ///
 /// Returns the constructed `Arc` for the `Session` struct.
-pub fn session_info() -> Arc<Session> {
+pub fn session_info(config: &settings::Config) -> Arc<Session> {
+    let store = storage::session_store(config);
+    let mapping = store.load();
+    if !mapping.is_empty() {
+        log::info!("Restored {} session(s) from {:?} backend", mapping.len(), config.session_backend);
+    }
     Arc::new(Session {
         tracker: Mutex::new(HashMap::new()),
-        mapping: Mutex::new(HashMap::new()),
+        mapping: Mutex::new(mapping),
+        store,
+        challenges: Mutex::new(HashMap::new()),
+        tokens: tokens::tokens_info(config),
     })
 }
 
 /// Create a [Fernet](https://docs.rs/fernet/latest/fernet/) object to encrypt and decrypt session token.
 ///
-/// Generates a random key, that can be safely passed to `Fernet::new()`
+/// Uses the `secret_key` from the configuration, which defaults to a randomly generated key.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
 ///
 /// # Returns
 ///
-/// Returns the constructed `Arc` for the `Fernet` instance, with the generated key.
-pub fn fernet_object() -> Arc<Fernet> {
-    Arc::new(Fernet::new(&Fernet::generate_key()).unwrap())
+/// Returns the constructed `Arc` for the `Fernet` instance, with the configured key.
+pub fn fernet_object(config: &settings::Config) -> Arc<Fernet> {
+    Arc::new(Fernet::new(&config.secret_key).unwrap())
 }
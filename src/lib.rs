@@ -1,9 +1,6 @@
 #![allow(rustdoc::bare_urls)]
 #![doc = include_str!("../README.md")]
 
-#[macro_use]
-extern crate actix_web;
-
 use std::io;
 
 use actix_web::{App, HttpServer, middleware, web};
@@ -56,14 +53,30 @@ pub async fn start() -> io::Result<()> {
             "Secure session is turned on! This means that the server can ONLY be hosted via HTTPS or localhost"
         );
     }
+    if config.session_backend == squire::settings::SessionBackend::File && std::env::var("secret_key").is_err() {
+        log::warn!("Sessions are stored in {:?}, but 'secret_key' is not set.", config.storage);
+        log::warn!("A random key will be generated, so the stored sessions cannot be verified after a restart.");
+    }
     // Create a dedicated clone, since it will be used within closure
     let config_clone = config.clone();
     let host = format!("{}:{}", config.media_host, config.media_port);
     log::info!("{} [workers:{}] running on http://{} (Press CTRL+C to quit)",
         &metadata.pkg_name, &config.workers, &host);
     let jinja = templates::environment();
-    let fernet = constant::fernet_object();
-    let session = constant::session_info();
+    let fernet = constant::fernet_object(&config);
+    let session = constant::session_info(&config);
+    let lockout = squire::lockout::lockout_info(&config);
+    let shares = squire::share::shares_info(&config);
+    let transcoder = squire::transcode::transcoder_info(&config);
+    let prober = squire::probe::prober_info(&config);
+    let thumbnailer = squire::thumbnail::thumbnailer_info(&config);
+    let extractor = squire::extract::extractor_info(&config);
+    let progress = squire::progress::progress_info(&config);
+    let preferences = squire::preferences::preferences_info(&config);
+    let library = squire::index::library_info(&config);
+    // Dedicated clones to write the pending updates, once the server stops
+    let pending_writes = prober.clone();
+    let pending_progress = progress.clone();
     /*
         || syntax is creating a closure that serves as the argument to the HttpServer::new() method.
         The closure is defining the configuration for the Actix web server.
@@ -75,14 +88,27 @@ pub async fn start() -> io::Result<()> {
             .app_data(web::Data::new(jinja.clone()))
             .app_data(web::Data::new(fernet.clone()))
             .app_data(web::Data::new(session.clone()))
+            .app_data(web::Data::new(lockout.clone()))
+            .app_data(web::Data::new(shares.clone()))
+            .app_data(web::Data::new(transcoder.clone()))
+            .app_data(web::Data::new(prober.clone()))
+            .app_data(web::Data::new(thumbnailer.clone()))
+            .app_data(web::Data::new(extractor.clone()))
+            .app_data(web::Data::new(progress.clone()))
+            .app_data(web::Data::new(preferences.clone()))
+            .app_data(web::Data::new(library.clone()))
             .app_data(web::Data::new(metadata.clone()))
             .app_data(web::PayloadConfig::default().limit(config_clone.max_payload_size))
+            .app_data(web::QueryConfig::default().error_handler(squire::custom::query_error))
             .wrap(squire::middleware::get_cors(config_clone.websites.clone()))
             .wrap(middleware::Logger::default())  // Adds a default logger middleware to the application
             .service(routes::basics::health)  // Registers a service for handling requests
             .service(routes::basics::root)
+            .service(routes::auth::challenge)
             .service(routes::auth::login)
             .service(routes::auth::logout)
+            .service(routes::auth::lockouts)
+            .service(routes::auth::unban)
             .service(routes::auth::home)
             .service(routes::basics::profile)
             .service(routes::fileio::edit)
@@ -90,14 +116,36 @@ pub async fn start() -> io::Result<()> {
             .service(routes::media::track)
             .service(routes::media::stream)
             .service(routes::media::streaming_endpoint)
+            .service(routes::media::remux)
             .service(routes::upload::upload_files)
             .service(routes::upload::save_files)
+            .service(routes::share::create)
+            .service(routes::share::list)
+            .service(routes::share::revoke)
+            .service(routes::share::landing)
+            .service(routes::share::media)
+            .service(routes::api::library)
+            .service(routes::api::library_path)
+            .service(routes::api::history)
+            .service(routes::api::recent)
+            .service(routes::api::search)
+            .service(routes::search::search)
+            .service(routes::tokens::create)
+            .service(routes::tokens::list)
+            .service(routes::tokens::revoke)
+            .service(routes::hls::content)
+            .service(routes::dash::content)
+            .service(routes::thumbnail::thumbnail)
+            .service(routes::thumbnail::preview)
+            .service(routes::progress::update)
+            .service(routes::preferences::retrieve)
+            .service(routes::preferences::update)
     };
     let server = HttpServer::new(application)
         .workers(config.workers)
         .max_connections(config.max_connections);
     // Reference: https://actix.rs/docs/http2/
-    if config.cert_file.exists() && config.key_file.exists() {
+    let result = if config.cert_file.exists() && config.key_file.exists() {
         log::info!("Binding SSL certificate to serve over HTTPS");
         let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
         builder.set_private_key_file(&config.key_file, SslFiletype::PEM).unwrap();
@@ -109,5 +157,8 @@ pub async fn start() -> io::Result<()> {
         server.bind(host)?
             .run()
             .await
-    }
+    };
+    pending_writes.flush();
+    pending_progress.flush();
+    result
 }
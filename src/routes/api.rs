@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use actix_web::{get, HttpRequest, HttpResponse, web};
+use actix_web::http::header;
+use fernet::Fernet;
+use serde::{Deserialize, Serialize};
+
+use crate::{constant, routes, squire};
+
+/// Default number of entries in the feeds, when the limit is not specified.
+const DEFAULT_FEED_LIMIT: usize = 20;
+
+/// Maximum number of entries in the feeds.
+const MAX_FEED_LIMIT: usize = 200;
+
+/// Struct for representing the content of a directory in JSON format.
+#[derive(Serialize)]
+struct DirectoryResponse {
+    path: String,
+    content: squire::content::ContentPayload,
+}
+
+/// Struct for representing a media file in JSON format.
+#[derive(Serialize)]
+struct FileResponse {
+    metadata: squire::content::FileMetadata,
+    iter: squire::content::Iter,
+    media: String,
+    track: Option<String>,
+    tracks: Vec<squire::subtitles::Track>,
+    audio_tracks: Vec<squire::extract::AudioSource>,
+    hls: Option<String>,
+    dash: Option<String>,
+    probe: Option<squire::probe::MediaInfo>,
+}
+
+/// Struct for representing a feed of files in JSON format.
+#[derive(Serialize)]
+struct FeedResponse {
+    files: Vec<HashMap<String, String>>,
+}
+
+/// Represents the query parameters for the feeds.
+#[derive(Deserialize)]
+pub struct FeedQuery {
+    limit: Option<usize>,
+}
+
+/// Creates an `HttpResponse` for failed authentication, without redirecting to the `/error` page.
+///
+/// # Arguments
+///
+/// * `auth_response` - Authentication response of the failed request.
+///
+/// # Returns
+///
+/// * `401` - HttpResponse with the reason for the failed authentication.
+fn unauthorized(auth_response: squire::authenticator::AuthToken) -> HttpResponse {
+    HttpResponse::Unauthorized().json(routes::auth::DetailError {
+        detail: auth_response.detail
+    })
+}
+
+/// Handles requests for the `/api/v1/library` endpoint, listing all the content accessible to the user.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `query` - Query string with the sort order (`sort`, `order`) and the page (`page`, `limit`) of the files.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `prober` - Prober struct that holds the cached metadata of the media files.
+/// * `index` - Library index with the media files.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the files and directories as JSON.
+/// * `401` - HttpResponse with an error message for failed authentication.
+#[get("/api/v1/library")]
+pub async fn library(request: HttpRequest,
+                     query: web::Query<squire::content::ListingQuery>,
+                     fernet: web::Data<Arc<Fernet>>,
+                     session: web::Data<Arc<constant::Session>>,
+                     config: web::Data<Arc<squire::settings::Config>>,
+                     prober: web::Data<Arc<squire::probe::Prober>>,
+                     index: web::Data<Arc<squire::index::Library>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return unauthorized(auth_response);
+    }
+    let (_host, _last_accessed) = squire::custom::log_connection(&request, &session);
+    HttpResponse::Ok().json(DirectoryResponse {
+        path: String::new(),
+        content: squire::content::get_all_stream_content(&config, &auth_response, &prober, &index, &query),
+    })
+}
+
+/// Handles requests for the `/api/v1/library/{media_path:.*}` endpoint, describing a directory or a file.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `media_path` - The path parameter representing the media file or directory.
+/// * `query` - Query string with the sort order (`sort`, `order`) and the page (`page`, `limit`) of the files.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `transcoder` - Transcoder struct that keeps track of the media available for adaptive streaming.
+/// * `prober` - Prober struct that extracts and caches the metadata of the media files.
+/// * `index` - Library index with the media files.
+/// * `extractor` - Extractor struct for the embedded subtitles and the audio tracks.
+/// * `preferences` - Preferences struct with the preferred language for the subtitles of each user.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the directory content, or the file metadata with previous and next files.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message if the path is not accessible to the user.
+/// * `404` - HttpResponse with an error message if the path doesn't exist.
+#[get("/api/v1/library/{media_path:.*}")]
+#[allow(clippy::too_many_arguments)]
+pub async fn library_path(request: HttpRequest,
+                          media_path: web::Path<String>,
+                          query: web::Query<squire::content::ListingQuery>,
+                          fernet: web::Data<Arc<Fernet>>,
+                          session: web::Data<Arc<constant::Session>>,
+                          config: web::Data<Arc<squire::settings::Config>>,
+                          transcoder: web::Data<Arc<squire::transcode::Transcoder>>,
+                          prober: web::Data<Arc<squire::probe::Prober>>,
+                          index: web::Data<Arc<squire::index::Library>>,
+                          extractor: web::Data<Arc<squire::extract::Extractor>>,
+                          preferences: web::Data<Arc<squire::preferences::Preferences>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return unauthorized(auth_response);
+    }
+    let (_host, _last_accessed) = squire::custom::log_connection(&request, &session);
+    let filepath = media_path.trim_end_matches('/').to_string();
+    if !squire::authenticator::verify_access(&PathBuf::from(&filepath), &auth_response.username, &config) {
+        return HttpResponse::Forbidden().json(routes::auth::DetailError {
+            detail: format!("'{}' is not accessible to the user profile '{}'", filepath, auth_response.username)
+        });
+    }
+    let target = config.media_source.join(&filepath);
+    if target.is_dir() {
+        let child_dir = target.iter().next_back().unwrap().to_string_lossy().to_string();
+        let content = squire::content::get_dir_stream_content(
+            &target.to_string_lossy(), &child_dir, &config, &auth_response, &prober, &index, &query
+        );
+        return HttpResponse::Ok().json(DirectoryResponse { path: filepath, content });
+    }
+    let extension = target.extension().unwrap_or_default().to_string_lossy().to_string();
+    if target.is_file() && config.file_formats.contains(&extension) {
+        return match squire::content::get_file_metadata(&target, &filepath) {
+            Ok(metadata) => {
+                let preferred = preferences.subtitles(&auth_response.username)
+                    .languages(request.headers().get(header::ACCEPT_LANGUAGE)
+                        .and_then(|value| value.to_str().ok())
+                        .map(squire::subtitles::preferred_languages)
+                        .unwrap_or_default());
+                let (hls, dash, probe) = if constant::IMAGE_FORMATS.contains(&extension.to_lowercase().as_str()) {
+                    (None, None, None)
+                } else {
+                    let (hls, dash) = squire::transcode::sources(&transcoder, &target, &filepath);
+                    let (probe_target, probe_path, probe_prober) = (target.clone(), filepath.clone(), prober.clone());
+                    (hls, dash, web::block(move || probe_prober.probe(&probe_target, &probe_path)).await.ok().flatten())
+                };
+                let (embedded, audio_tracks) = match &probe {
+                    Some(info) => {
+                        extractor.schedule_subtitles(&target, &filepath, info);
+                        (extractor.tracks(&target, &filepath, info), squire::extract::audio_sources(&extractor, info, &filepath))
+                    }
+                    None => (Vec::new(), Vec::new())
+                };
+                let tracks = squire::subtitles::tracks(&target, &filepath, &preferred, &config, embedded);
+                let track = tracks.iter().find(|track| track.default).or(tracks.first())
+                    .map(|track| track.url.clone());
+                HttpResponse::Ok().json(FileResponse {
+                    iter: squire::content::get_iter(&target, &config, &auth_response, &index, &prober, &query),
+                    media: format!("/media?file={}", routes::media::url_encode(&filepath)),
+                    track,
+                    tracks,
+                    audio_tracks,
+                    hls,
+                    dash,
+                    probe,
+                    metadata,
+                })
+            }
+            Err(err) => {
+                log::error!("Failed to read metadata for {:?}: {}", target, err);
+                HttpResponse::InternalServerError().json(routes::auth::DetailError {
+                    detail: format!("Failed to read metadata for '{}'", filepath)
+                })
+            }
+        };
+    }
+    HttpResponse::NotFound().json(routes::auth::DetailError {
+        detail: format!("'{}' was not found", filepath)
+    })
+}
+
+/// Handles requests for the `/api/v1/history` endpoint, listing the watch history of the user.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `query` - Query string with the optional `limit` for the number of entries.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `progress` - Progress struct that stores the watch history for each user.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the watch history as JSON, most recent first.
+/// * `401` - HttpResponse with an error message for failed authentication.
+#[get("/api/v1/history")]
+pub async fn history(request: HttpRequest,
+                     query: web::Query<FeedQuery>,
+                     fernet: web::Data<Arc<Fernet>>,
+                     session: web::Data<Arc<constant::Session>>,
+                     config: web::Data<Arc<squire::settings::Config>>,
+                     progress: web::Data<Arc<squire::progress::Progress>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return unauthorized(auth_response);
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_FEED_LIMIT).min(MAX_FEED_LIMIT);
+    HttpResponse::Ok().json(FeedResponse {
+        files: squire::content::get_watch_history(&config, &auth_response, &progress, limit)
+    })
+}
+
+/// Handles requests for the `/api/v1/recent` endpoint, listing the recently added files accessible to the user.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `query` - Query string with the optional `limit` for the number of entries.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `index` - Library index with the media files.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the recently added files as JSON, newest first.
+/// * `401` - HttpResponse with an error message for failed authentication.
+#[get("/api/v1/recent")]
+pub async fn recent(request: HttpRequest,
+                    query: web::Query<FeedQuery>,
+                    fernet: web::Data<Arc<Fernet>>,
+                    session: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>,
+                    index: web::Data<Arc<squire::index::Library>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return unauthorized(auth_response);
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_FEED_LIMIT).min(MAX_FEED_LIMIT);
+    HttpResponse::Ok().json(FeedResponse {
+        files: squire::content::get_recently_added(&config, &auth_response, &index, limit)
+    })
+}
+
+/// Handles requests for the `/api/v1/search` endpoint, searching the library index.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `query` - Query string with the search text (`q`), extensions (`ext`), directory (`dir`), dates (`after`, `before`) and `limit`.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `index` - Library index with the media files.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the matching files as JSON, best match first.
+/// * `400` - HttpResponse with an error message for invalid filters.
+/// * `401` - HttpResponse with an error message for failed authentication.
+#[get("/api/v1/search")]
+pub async fn search(request: HttpRequest,
+                    query: web::Query<routes::search::SearchQuery>,
+                    fernet: web::Data<Arc<Fernet>>,
+                    session: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>,
+                    index: web::Data<Arc<squire::index::Library>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return unauthorized(auth_response);
+    }
+    let filter = match query.filter() {
+        Ok(filter) => filter,
+        Err(err) => return HttpResponse::BadRequest().json(routes::auth::DetailError { detail: err })
+    };
+    let limit = query.limit.unwrap_or(routes::search::DEFAULT_LIMIT).min(routes::search::MAX_LIMIT);
+    HttpResponse::Ok().json(FeedResponse {
+        files: squire::search::search(&index, &filter, &config, &auth_response, limit)
+    })
+}
@@ -1,15 +1,19 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
-use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web::{get, HttpRequest, HttpResponse, post, web};
 use actix_web::cookie::{Cookie, SameSite};
 use actix_web::cookie::time::{Duration, OffsetDateTime};
 use actix_web::http::StatusCode;
 use fernet::Fernet;
 use minijinja;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{constant, squire};
 
+/// Maximum number of entries in the "recently watched" and "recently added" sections of the home page.
+const FEED_LIMIT: usize = 10;
+
 /// Struct for representing a JSON Response with a redirect URL.
 #[derive(Serialize)]
 struct RedirectResponse {
@@ -22,6 +26,51 @@ pub struct DetailError {
     pub detail: String,
 }
 
+/// Struct for representing a JSON Response with the login challenge.
+#[derive(Serialize)]
+struct ChallengeResponse {
+    nonce: String,
+    salt: String,
+    iterations: u32,
+}
+
+/// Query parameters for the challenge endpoint.
+#[derive(Deserialize)]
+pub struct ChallengeQuery {
+    #[serde(default)]
+    username: String,
+}
+
+/// Handles the challenge endpoint, issuing a one-time nonce for the login request.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `query` - Query parameters with the username, to look up the salt for.
+/// * `config` - Configuration data for the application.
+/// * `session` - Session struct that holds the `challenges` issued by the server.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the nonce, that is valid for the duration of `login_window`, the salt and the iterations.
+/// * `429` - HttpResponse with an error message and `Retry-After` header when the client or the server is at its limit.
+#[get("/challenge")]
+pub async fn challenge(request: HttpRequest,
+                       query: web::Query<ChallengeQuery>,
+                       config: web::Data<Arc<squire::settings::Config>>,
+                       session: web::Data<Arc<constant::Session>>) -> HttpResponse {
+    let peer = squire::custom::client_ip(&request, &config);
+    match squire::authenticator::issue_challenge(&config, &session, &peer) {
+        Ok(nonce) => {
+            let (salt, iterations) = squire::authenticator::scram_params(&config, &query.username);
+            HttpResponse::Ok().json(ChallengeResponse { nonce, salt, iterations })
+        }
+        Err(err) => HttpResponse::TooManyRequests()
+            .insert_header(("Retry-After", config.login_window.to_string()))
+            .json(DetailError { detail: err.to_string() })
+    }
+}
+
 /// Handles the login endpoint, verifying credentials and creating session tokens.
 ///
 /// # Arguments
@@ -30,26 +79,51 @@ pub struct DetailError {
 /// * `config` - Configuration data for the application.
 /// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
 /// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `lockout` - Lockout struct that tracks the failed login attempts.
 ///
 /// # Returns
 ///
 /// * `200` - HttpResponse with a `session_token` and redirect URL to the `/home` entrypoint.
 /// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message if the IP address has been banned.
+/// * `429` - HttpResponse with an error message and `Retry-After` header during a temporary lockout.
 #[post("/login")]
 pub async fn login(request: HttpRequest,
                    config: web::Data<Arc<squire::settings::Config>>,
                    fernet: web::Data<Arc<Fernet>>,
-                   session: web::Data<Arc<constant::Session>>) -> HttpResponse {
+                   session: web::Data<Arc<constant::Session>>,
+                   lockout: web::Data<Arc<squire::lockout::Lockout>>) -> HttpResponse {
+    let ip = squire::custom::client_ip(&request, &config);
+    let username = squire::authenticator::login_username(&request);
+    match lockout.check(&ip, &username) {
+        Ok(_) => {}
+        Err(squire::lockout::Blocked::Banned) => {
+            log::warn!("Login request from banned IP address {}", ip);
+            return HttpResponse::Forbidden().json(DetailError {
+                detail: "Too many failed login attempts, access has been revoked".to_string()
+            });
+        }
+        Err(squire::lockout::Blocked::Locked(retry_after)) => {
+            log::warn!("Login request from {} for '{}' during lockout [{}s left]", ip, username, retry_after);
+            return HttpResponse::TooManyRequests()
+                .insert_header(("Retry-After", retry_after.to_string()))
+                .json(DetailError {
+                    detail: format!("Too many failed login attempts, please try again in {}s", retry_after)
+                });
+        }
+    }
     let verified = squire::authenticator::verify_login(&request, &config, &session);
     if let Err(err) = verified {
         let err_message = err.to_string();
         log::warn!("Error response::{}", err_message);
+        lockout.failed(&ip, &username, &config);
         return HttpResponse::Unauthorized().json(DetailError {
             detail: err_message
         });
     }
 
     let mapped = verified.unwrap();
+    lockout.succeeded(&ip, &username);
     let (_host, _last_accessed) = squire::custom::log_connection(&request, &session);
 
     let payload = serde_json::to_string(&mapped).unwrap();
@@ -78,6 +152,102 @@ pub async fn login(request: HttpRequest,
     response
 }
 
+/// Struct for representing the active lockouts in JSON format.
+#[derive(Serialize)]
+struct LockoutResponse {
+    ip_addresses: HashMap<String, squire::lockout::Attempts>,
+    usernames: HashMap<String, squire::lockout::Attempts>,
+    banned: Vec<String>,
+}
+
+/// Handles the lockouts endpoint, listing the failed login attempts, lockouts and banned IP addresses.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `lockout` - Lockout struct that tracks the failed login attempts.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the failed attempts per IP address and username, and the banned IP addresses.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message if the user is not an admin.
+#[get("/lockouts")]
+pub async fn lockouts(request: HttpRequest,
+                      fernet: web::Data<Arc<Fernet>>,
+                      session: web::Data<Arc<constant::Session>>,
+                      config: web::Data<Arc<squire::settings::Config>>,
+                      lockout: web::Data<Arc<squire::lockout::Lockout>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return failed_auth(auth_response, &config);
+    }
+    if squire::authenticator::role(&auth_response, &config) < squire::settings::Role::Admin {
+        return HttpResponse::Forbidden().json(DetailError {
+            detail: "Lockouts are only visible to an admin".to_string()
+        });
+    }
+    HttpResponse::Ok().json(LockoutResponse {
+        ip_addresses: lockout.by_ip.lock().unwrap().clone(),
+        usernames: lockout.by_user.lock().unwrap().clone(),
+        banned: lockout.banned.lock().unwrap().clone(),
+    })
+}
+
+/// Struct for representing the JSON payload to unban an IP address.
+#[derive(Deserialize)]
+pub struct UnbanPayload {
+    ip: String,
+}
+
+/// Handles the `POST /lockouts/unban` endpoint, lifting the ban and the lockout on an IP address.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `payload` - JSON payload with the `ip` address to be unbanned.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `lockout` - Lockout struct that tracks the failed login attempts.
+///
+/// # Returns
+///
+/// * `200` - Blank HttpResponse to indicate that the IP address was unbanned.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message if the user is not an admin.
+/// * `404` - HttpResponse with an error message if the IP address is not banned.
+#[post("/lockouts/unban")]
+pub async fn unban(request: HttpRequest,
+                   payload: web::Json<UnbanPayload>,
+                   fernet: web::Data<Arc<Fernet>>,
+                   session: web::Data<Arc<constant::Session>>,
+                   config: web::Data<Arc<squire::settings::Config>>,
+                   lockout: web::Data<Arc<squire::lockout::Lockout>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return failed_auth(auth_response, &config);
+    }
+    if squire::authenticator::role(&auth_response, &config) < squire::settings::Role::Admin {
+        return HttpResponse::Forbidden().json(DetailError {
+            detail: "Only an admin can unban an IP address".to_string()
+        });
+    }
+    let ip = payload.ip.trim();
+    // Failed attempts are reset as well, so the IP address starts afresh
+    lockout.by_ip.lock().unwrap().remove(ip);
+    if lockout.unban(ip) {
+        log::warn!("'{}' unbanned the IP address {}", auth_response.username, ip);
+        return HttpResponse::Ok().finish();
+    }
+    HttpResponse::NotFound().json(DetailError {
+        detail: format!("IP address '{}' is not banned", ip)
+    })
+}
+
 /// Handles the logout endpoint, logging out the user and rendering the appropriate HTML page.
 ///
 /// # Arguments
@@ -113,6 +283,8 @@ pub async fn logout(request: HttpRequest,
     }
 
     if auth_response.ok {
+        // Only the current device is logged out, sessions on other devices remain valid
+        squire::authenticator::revoke_session(&session, &auth_response.session_id);
         let mut tracker = session.tracker.lock().unwrap();
         if tracker.get(&host).is_some() {
             tracker.remove(&host);
@@ -145,23 +317,34 @@ pub async fn logout(request: HttpRequest,
 /// # Arguments
 ///
 /// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `query` - Query string with the sort order and the page of the files.
 /// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
 /// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
 /// * `metadata` - Struct containing metadata of the application.
 /// * `config` - Configuration data for the application.
 /// * `template` - Configuration container for the loaded templates.
+/// * `prober` - Prober struct that holds the cached metadata of the media files.
+/// * `thumbnailer` - Thumbnailer struct to indicate if the thumbnails are available for the grid view.
+/// * `progress` - Progress struct that stores the watch history for each user.
+/// * `library` - Library index with the media files.
 ///
 /// # Returns
 ///
 /// * `200` - Returns an `HTTPResponse` with the home/listing page if session token is valid.
 /// * `401` - HttpResponse with an error message for failed authentication.
 #[get("/home")]
+#[allow(clippy::too_many_arguments)]
 pub async fn home(request: HttpRequest,
+                  query: web::Query<squire::content::ListingQuery>,
                   fernet: web::Data<Arc<Fernet>>,
                   session: web::Data<Arc<constant::Session>>,
                   metadata: web::Data<Arc<constant::MetaData>>,
                   config: web::Data<Arc<squire::settings::Config>>,
-                  template: web::Data<Arc<minijinja::Environment<'static>>>) -> HttpResponse {
+                  template: web::Data<Arc<minijinja::Environment<'static>>>,
+                  prober: web::Data<Arc<squire::probe::Prober>>,
+                  thumbnailer: web::Data<Arc<squire::thumbnail::Thumbnailer>>,
+                  progress: web::Data<Arc<squire::progress::Progress>>,
+                  library: web::Data<Arc<squire::index::Library>>) -> HttpResponse {
     let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
     if !auth_response.ok {
         return failed_auth(auth_response, &config);
@@ -169,7 +352,9 @@ pub async fn home(request: HttpRequest,
     let (_host, _last_accessed) = squire::custom::log_connection(&request, &session);
     log::debug!("{}", auth_response.detail);
 
-    let listing_page = squire::content::get_all_stream_content(&config, &auth_response);
+    let listing_page = squire::content::get_all_stream_content(&config, &auth_response, &prober, &library, &query);
+    let history = squire::content::get_watch_history(&config, &auth_response, &progress, FEED_LIMIT);
+    let recently_added = squire::content::get_recently_added(&config, &auth_response, &library, FEED_LIMIT);
     let listing = template.get_template("listing").unwrap();
 
     HttpResponse::build(StatusCode::OK)
@@ -179,9 +364,16 @@ pub async fn home(request: HttpRequest,
                 version => metadata.pkg_version,
                 files => listing_page.files,
                 user => auth_response.username,
+                role => squire::authenticator::role(&auth_response, &config).as_str(),
                 secure_index => constant::SECURE_INDEX,
                 directories => listing_page.directories,
-                secured_directories => listing_page.secured_directories
+                secured_directories => listing_page.secured_directories,
+                thumbnails => thumbnailer.enabled,
+                pagination => listing_page.pagination,
+                sorting => &*query,
+                sort_query => query.sort_query(),
+                history => history,
+                recently_added => recently_added
             )).unwrap()
         )
 }
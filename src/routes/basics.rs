@@ -1,13 +1,16 @@
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web::{get, HttpRequest, HttpResponse, web};
 use actix_web::http::StatusCode;
 use fernet::Fernet;
 
 use crate::{constant, routes, squire};
 
+/// Maximum number of entries in the "continue watching" section of the profile page.
+const CONTINUE_WATCHING: usize = 10;
+
 /// Handles the health endpoint, returning a JSON response indicating the server is healthy.
 ///
 /// # Returns
@@ -55,17 +58,22 @@ pub async fn root(request: HttpRequest,
 /// * `metadata` - Struct containing metadata of the application.
 /// * `config` - Configuration data for the application.
 /// * `template` - Configuration container for the loaded templates.
+/// * `progress` - Progress struct that stores the playback positions for each user.
+/// * `preferences` - Preferences struct that stores the subtitle preferences for each user.
 ///
 /// # Returns
 ///
 /// Returns an `HttpResponse` with the profile page as its body.
 #[get("/profile")]
+#[allow(clippy::too_many_arguments)]
 pub async fn profile(request: HttpRequest,
                      fernet: web::Data<Arc<Fernet>>,
                      session: web::Data<Arc<constant::Session>>,
                      metadata: web::Data<Arc<constant::MetaData>>,
                      config: web::Data<Arc<squire::settings::Config>>,
-                     template: web::Data<Arc<minijinja::Environment<'static>>>) -> HttpResponse {
+                     template: web::Data<Arc<minijinja::Environment<'static>>>,
+                     progress: web::Data<Arc<squire::progress::Progress>>,
+                     preferences: web::Data<Arc<squire::preferences::Preferences>>) -> HttpResponse {
     let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
     if !auth_response.ok {
         return routes::auth::failed_auth(auth_response, &config);
@@ -76,7 +84,7 @@ pub async fn profile(request: HttpRequest,
     if !last_accessed.is_empty() {
         let filepath = Path::new(&last_accessed);
         let extn = filepath.extension().unwrap().to_str().unwrap();
-        let name = filepath.iter().last().unwrap().to_string_lossy().to_string();
+        let name = filepath.iter().next_back().unwrap().to_string_lossy().to_string();
         let path = format!("/stream/{}", &last_accessed);
         let font = if last_accessed.contains(constant::SECURE_INDEX) {
             "fa-solid fa-lock".to_string()
@@ -87,12 +95,31 @@ pub async fn profile(request: HttpRequest,
             ("name", name), ("font", font), ("path", path)
         ]);
     }
+    // Files that were removed or are no longer accessible to the user are skipped
+    let watching: Vec<HashMap<&str, String>> = progress.continue_watching(&auth_response.username, CONTINUE_WATCHING)
+        .into_iter()
+        .filter(|(path, _)| {
+            config.media_source.join(path).is_file()
+                && squire::authenticator::verify_access(&PathBuf::from(path), &auth_response.username, &config)
+        })
+        .map(|(path, watched)| HashMap::from([
+            ("name", Path::new(&path).file_name().unwrap_or_default().to_string_lossy().to_string()),
+            ("path", format!("/stream/{}", &path)),
+            ("position", watched.position_str()),
+            ("completion", format!("{:.0}", watched.completion())),
+        ]))
+        .collect();
     HttpResponse::build(StatusCode::OK)
         .content_type("text/html; charset=utf-8")
         .body(index.render(minijinja::context!(
             version => &metadata.pkg_version,
             user => &auth_response.username,
+            role => squire::authenticator::role(&auth_response, &config).as_str(),
             time_left => &auth_response.time_left,
             file => access_map,
+            watching => watching,
+            sessions => squire::authenticator::list_sessions(&session, &config, &auth_response),
+            subtitles => preferences.subtitles(&auth_response.username),
+            font_sizes => squire::preferences::FONT_SIZES,
         )).unwrap())
 }
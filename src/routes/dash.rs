@@ -0,0 +1,148 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use actix_web::{get, HttpRequest, HttpResponse, web};
+use fernet::Fernet;
+
+use crate::{constant, routes, squire};
+
+/// Maximum number of seconds to wait for `ffmpeg` to write the first manifest.
+const MANIFEST_TIMEOUT: u64 = 30;
+
+/// Maximum number of seconds to wait for `ffmpeg` to generate a segment that was requested ahead of time.
+const SEGMENT_TIMEOUT: u64 = 15;
+
+/// Number of seconds after which the player should retry, when the maximum number of jobs are running.
+const BUSY_RETRY: u64 = 10;
+
+/// Checks if the filename matches the segments generated by `ffmpeg` for the DASH manifest.
+///
+/// # Arguments
+///
+/// * `filename` - Requested filename.
+///
+/// # Returns
+///
+/// Returns a boolean flag to indicate if the filename is a valid segment name.
+fn is_segment(filename: &str) -> bool {
+    (filename.starts_with("init-") || filename.starts_with("chunk-"))
+        && filename.ends_with(".m4s")
+        && filename.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+        && !filename.contains("..")
+}
+
+/// Handles requests for the `/dash/{key}/{filename}` endpoint, serving the DASH manifest and segments.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `path` - The path parameters with the cache key of the media file and the requested filename.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `transcoder` - Transcoder struct that keeps track of the `ffmpeg` processes.
+///
+/// ## See Also
+///
+/// - The cache key is registered when the landing page or the API response is rendered for the media file.
+/// - The manifest is `dynamic` while transcoding, and turns `static` once all the segments are ready.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the manifest or a segment.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message if the media is not accessible to the user.
+/// * `404` - HttpResponse with an error message if the media or segment doesn't exist.
+/// * `503` - HttpResponse with an error message if transcoding is disabled, busy or didn't start in time.
+#[get("/dash/{key}/{filename}")]
+pub async fn content(request: HttpRequest,
+                     path: web::Path<(String, String)>,
+                     fernet: web::Data<Arc<Fernet>>,
+                     session: web::Data<Arc<constant::Session>>,
+                     config: web::Data<Arc<squire::settings::Config>>,
+                     transcoder: web::Data<Arc<squire::transcode::Transcoder>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return routes::auth::failed_auth(auth_response, &config);
+    }
+    if !transcoder.dash {
+        return HttpResponse::ServiceUnavailable().json(routes::auth::DetailError {
+            detail: "MPEG-DASH streaming is not enabled".to_string()
+        });
+    }
+    let (key, filename) = path.into_inner();
+    let relative_path = match transcoder.lookup(&key) {
+        Some(relative_path) => relative_path,
+        None => return HttpResponse::NotFound().json(routes::auth::DetailError {
+            detail: "Stream was not found, please reload the page".to_string()
+        })
+    };
+    if !squire::authenticator::verify_access(&PathBuf::from(&relative_path), &auth_response.username, &config) {
+        return HttpResponse::Forbidden().json(routes::auth::DetailError {
+            detail: format!("'{}' is not accessible to the user profile '{}'", relative_path, auth_response.username)
+        });
+    }
+    let output = config.cache_dir.join("dash").join(&key);
+    if filename == "manifest.mpd" {
+        let (job_transcoder, job_config, job_key, job_output) =
+            (transcoder.clone(), config.clone(), key.clone(), output.clone());
+        let ensured = web::block(move || job_transcoder.ensure_dash(&job_config, &job_key, &job_output)).await
+            .unwrap_or_else(|err| Err(squire::transcode::JobError::Failed(err.to_string())));
+        let manifest = match ensured {
+            Ok(manifest) => manifest,
+            Err(squire::transcode::JobError::Busy) => {
+                return HttpResponse::ServiceUnavailable()
+                    .insert_header(("Retry-After", BUSY_RETRY.to_string()))
+                    .json(routes::auth::DetailError {
+                        detail: "Server is busy transcoding other videos, please try again later".to_string()
+                    });
+            }
+            Err(err) => {
+                log::error!("{}", err);
+                return HttpResponse::ServiceUnavailable().json(routes::auth::DetailError {
+                    detail: format!("Failed to transcode '{}'", relative_path)
+                });
+            }
+        };
+        transcoder.touch(&format!("{}/dash", key));
+        let polled = manifest.clone();
+        if !squire::transcode::wait_for(MANIFEST_TIMEOUT, move || polled.exists()).await {
+            return HttpResponse::ServiceUnavailable().json(routes::auth::DetailError {
+                detail: format!("Transcoding '{}' is taking longer than expected", relative_path)
+            });
+        }
+        let read_manifest = manifest.clone();
+        let content = web::block(move || std::fs::read_to_string(read_manifest)).await
+            .unwrap_or_else(|err| Err(std::io::Error::other(err.to_string())));
+        return match content {
+            Ok(content) => HttpResponse::Ok()
+                .content_type("application/dash+xml")
+                .insert_header(("Cache-Control", "no-cache"))
+                .body(content),
+            Err(err) => {
+                log::error!("Failed to read {:?}: {}", manifest, err);
+                HttpResponse::NotFound().json(routes::auth::DetailError {
+                    detail: format!("Manifest for '{}' was not found", relative_path)
+                })
+            }
+        };
+    }
+    // Segment names are generated by ffmpeg, anything else is rejected to avoid reading arbitrary files
+    if !is_segment(&filename) {
+        return HttpResponse::NotFound().json(routes::auth::DetailError {
+            detail: format!("'{}' was not found", filename)
+        });
+    }
+    let segment = output.join(&filename);
+    transcoder.touch(&format!("{}/dash", key));
+    if transcoder.is_running(&format!("{}/dash", key)) {
+        let polled = segment.clone();
+        squire::transcode::wait_for(SEGMENT_TIMEOUT, move || polled.exists()).await;
+    }
+    match actix_files::NamedFile::open_async(&segment).await {
+        Ok(file) => file.set_content_type("video/iso.segment".parse().unwrap()).into_response(&request),
+        Err(_) => HttpResponse::NotFound().json(routes::auth::DetailError {
+            detail: format!("'{}' was not found", filename)
+        })
+    }
+}
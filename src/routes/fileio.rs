@@ -3,7 +3,7 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web::{HttpRequest, HttpResponse, post, web};
 use actix_web::http::StatusCode;
 use fernet::Fernet;
 use serde::Deserialize;
@@ -63,27 +63,35 @@ fn extract_media_path(payload: &web::Json<Payload>, media_source: &Path) -> Resu
 /// * `metadata` - Struct containing metadata of the application.
 /// * `config` - Configuration data for the application.
 /// * `template` - Configuration container for the loaded templates.
+/// * `library` - Library index that is updated with the deleted/renamed files.
 ///
 /// # Returns
 ///
 /// * `200` - Blank HttpResponse to indicate that the request was successful.
 /// * `400` - HttpResponse with an error message for invalid action or incorrect payload.
 /// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message if the user is not an admin.
 /// * `500` - HttpResponse with an error message for failed delete/rename.
 #[post("/edit")]
+#[allow(clippy::too_many_arguments)]
 pub async fn edit(request: HttpRequest,
                   payload: web::Json<Payload>,
                   fernet: web::Data<Arc<Fernet>>,
                   session: web::Data<Arc<constant::Session>>,
                   metadata: web::Data<Arc<constant::MetaData>>,
                   config: web::Data<Arc<squire::settings::Config>>,
-                  template: web::Data<Arc<minijinja::Environment<'static>>>) -> HttpResponse {
+                  template: web::Data<Arc<minijinja::Environment<'static>>>,
+                  library: web::Data<Arc<squire::index::Library>>) -> HttpResponse {
     let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
     if !auth_response.ok {
         return routes::auth::failed_auth(auth_response, &config);
     }
     let (_host, _last_accessed) = squire::custom::log_connection(&request, &session);
     log::debug!("{}", auth_response.detail);
+    if squire::authenticator::role(&auth_response, &config) < squire::settings::Role::Admin {
+        log::warn!("'{}' is not permitted to edit content", &auth_response.username);
+        return HttpResponse::Forbidden().body("You are not permitted to delete or rename content!");
+    }
     let extracted = extract_media_path(&payload, &config.media_source);
     // todo: styling of the pop up is very basic
     let media_path: PathBuf = match extracted {
@@ -94,7 +102,9 @@ pub async fn edit(request: HttpRequest,
             return HttpResponse::BadRequest().body(msg);
         }
     };
-    if !squire::authenticator::verify_secure_index(&PathBuf::from(&media_path), &auth_response.username) {
+    let accessible = media_path.strip_prefix(&config.media_source)
+        .is_ok_and(|relative| squire::authenticator::verify_access(relative, &auth_response.username, &config));
+    if !accessible {
         return squire::custom::error(
             "RESTRICTED SECTION",
             template.get_template("error").unwrap(),
@@ -107,11 +117,11 @@ pub async fn edit(request: HttpRequest,
         let action = edit_action.to_str().unwrap();
         log::info!("{} requested to {} {:?}", &auth_response.username, action, &media_path);
         return if action == "delete" {
-            return delete(media_path);
+            return delete(media_path, &library);
         } else if action == "rename" {
             let new_name_str = payload.new_name.as_deref();
             if let Some(new_name) = new_name_str {
-                return rename(media_path, new_name.trim());
+                return rename(media_path, new_name.trim(), &library);
             } else {
                 HttpResponse::BadRequest().body("New name is missing!")
             }
@@ -154,7 +164,7 @@ fn is_valid_name(old_filepath: &PathBuf, new_name: &str) -> Result<bool, String>
         return Err(format!("New name cannot start or end with '.' or '_'\n\n'{}'", new_name))
     }
     let old_extension = old_filepath.extension().unwrap().to_str().unwrap();
-    let new_extension = new_name.split('.').last().unwrap_or_default();
+    let new_extension = new_name.split('.').next_back().unwrap_or_default();
     if old_extension != new_extension {
         return Err(format!("File extension cannot be changed\n\n'{new_extension}' => '{old_extension}'"))
     }
@@ -170,13 +180,14 @@ fn is_valid_name(old_filepath: &PathBuf, new_name: &str) -> Result<bool, String>
 ///
 /// - `old_filepath` - PathBuf object to the file that has to be renamed.
 /// - `new_name` - New name for the file.
+/// - `library` - Library index that is updated with the renamed file.
 ///
 /// # Returns
 ///
 /// * `200` - Blank HttpResponse to indicate that the request was successful.
 /// * `400` - HttpResponse with an error message for invalid action or incorrect payload.
 /// * `500` - HttpResponse with an error message for failed rename.
-fn rename(media_path: PathBuf, new_name: &str) -> HttpResponse {
+fn rename(media_path: PathBuf, new_name: &str, library: &squire::index::Library) -> HttpResponse {
     if new_name.is_empty() {
         let reason = "New name not received in payload";
         log::warn!("{}", reason);
@@ -189,22 +200,23 @@ fn rename(media_path: PathBuf, new_name: &str) -> HttpResponse {
     let validity = is_valid_name(
         &media_path, new_name
     );
-    return match validity {
+    match validity {
         Ok(_) => {
-            let new_path = media_path.parent().unwrap().join(new_name).to_string_lossy().to_string();
-            let old_path = media_path.to_string_lossy().to_string();
-            if let Err(error) = fs::rename(old_path, new_path) {
+            let new_path = media_path.parent().unwrap().join(new_name);
+            if let Err(error) = fs::rename(&media_path, &new_path) {
                 let reason = format!("Error renaming file: {}", error);
                 log::error!("{}", reason);
                 HttpResponse::InternalServerError().body(reason)
             } else {
+                library.sync(&media_path);
+                library.sync(&new_path);
                 HttpResponse::Ok().finish()
             }
         },
         Err(msg) => {
             HttpResponse::BadRequest().body(msg)
         }
-    };
+    }
 }
 
 /// Deletes the file.
@@ -212,27 +224,30 @@ fn rename(media_path: PathBuf, new_name: &str) -> HttpResponse {
 /// # Arguments
 ///
 /// - `media_path` - PathBuf object to the file that has to be deleted.
+/// - `library` - Library index that is updated with the deleted file.
 ///
 /// # Returns
 ///
 /// * `200` - Blank HttpResponse to indicate that the request was successful.
 /// * `400` - HttpResponse with an error message for invalid action or incorrect payload.
 /// * `500` - HttpResponse with an error message for failed delete.
-fn delete(media_path: PathBuf) -> HttpResponse {
+fn delete(media_path: PathBuf, library: &squire::index::Library) -> HttpResponse {
     if media_path.is_file() {
-        if let Err(error) = fs::remove_file(media_path) {
+        if let Err(error) = fs::remove_file(&media_path) {
             let reason = format!("Error deleting file: {}", error);
             log::error!("{}", reason);
             HttpResponse::InternalServerError().body(reason)
         } else {
+            library.sync(&media_path);
             HttpResponse::Ok().finish()
         }
     } else if media_path.is_dir() {
-        if let Err(error) = fs::remove_dir_all(media_path) {
+        if let Err(error) = fs::remove_dir_all(&media_path) {
             let reason = format!("Error deleting directory: {}", error);
             log::error!("{}", reason);
             HttpResponse::InternalServerError().body(reason)
         } else {
+            library.sync(&media_path);
             HttpResponse::Ok().finish()
         }
     } else {
@@ -0,0 +1,156 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use actix_web::{get, HttpRequest, HttpResponse, web};
+use fernet::Fernet;
+
+use crate::{constant, routes, squire};
+
+/// Maximum number of seconds to wait for `ffmpeg` to generate the first segment of a rendition.
+const PLAYLIST_TIMEOUT: u64 = 30;
+
+/// Maximum number of seconds to wait for `ffmpeg` to generate a segment that was requested ahead of time.
+const SEGMENT_TIMEOUT: u64 = 15;
+
+/// Number of seconds after which the player should retry, when the maximum number of jobs are running.
+const BUSY_RETRY: u64 = 10;
+
+/// Content type for the HLS playlists.
+const PLAYLIST_TYPE: &str = "application/vnd.apple.mpegurl";
+
+/// Handles requests for the `/hls/{key}/{filename}` endpoint, serving the HLS playlists and segments.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `path` - The path parameters with the cache key of the media file and the requested filename.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `transcoder` - Transcoder struct that keeps track of the `ffmpeg` processes.
+/// * `prober` - Prober struct that extracts and caches the metadata of the media files.
+///
+/// ## See Also
+///
+/// - The cache key is registered when the landing page is rendered for the media file.
+/// - The master playlist lists only the renditions that are not taller than the source.
+/// - A rendition is transcoded only when its playlist is requested by the player.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the master playlist, a rendition playlist or a segment.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message if the media is not accessible to the user.
+/// * `404` - HttpResponse with an error message if the media, rendition or segment doesn't exist.
+/// * `503` - HttpResponse with an error message if transcoding is disabled, busy or didn't start in time.
+#[get("/hls/{key}/{filename}")]
+pub async fn content(request: HttpRequest,
+                     path: web::Path<(String, String)>,
+                     fernet: web::Data<Arc<Fernet>>,
+                     session: web::Data<Arc<constant::Session>>,
+                     config: web::Data<Arc<squire::settings::Config>>,
+                     transcoder: web::Data<Arc<squire::transcode::Transcoder>>,
+                     prober: web::Data<Arc<squire::probe::Prober>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return routes::auth::failed_auth(auth_response, &config);
+    }
+    if !transcoder.hls {
+        return HttpResponse::ServiceUnavailable().json(routes::auth::DetailError {
+            detail: "HLS streaming is not enabled".to_string()
+        });
+    }
+    let (key, filename) = path.into_inner();
+    let relative_path = match transcoder.lookup(&key) {
+        Some(relative_path) => relative_path,
+        None => return HttpResponse::NotFound().json(routes::auth::DetailError {
+            detail: "Stream was not found, please reload the page".to_string()
+        })
+    };
+    if !squire::authenticator::verify_access(&PathBuf::from(&relative_path), &auth_response.username, &config) {
+        return HttpResponse::Forbidden().json(routes::auth::DetailError {
+            detail: format!("'{}' is not accessible to the user profile '{}'", relative_path, auth_response.username)
+        });
+    }
+    if filename == "master.m3u8" {
+        let filepath = config.media_source.join(&relative_path);
+        let height = squire::transcode::source_height(&prober, filepath, relative_path).await;
+        return HttpResponse::Ok()
+            .content_type(PLAYLIST_TYPE)
+            .body(squire::transcode::master_playlist(height));
+    }
+    let output = config.cache_dir.join("hls").join(&key);
+    if let Some(name) = filename.strip_suffix(".m3u8") {
+        let rendition = match squire::transcode::rendition(name) {
+            Some(rendition) => rendition,
+            None => return HttpResponse::NotFound().json(routes::auth::DetailError {
+                detail: format!("Rendition '{}' was not found", name)
+            })
+        };
+        let (job_transcoder, job_config, job_key, job_output) =
+            (transcoder.clone(), config.clone(), key.clone(), output.clone());
+        let ensured = web::block(move || job_transcoder.ensure_hls(&job_config, &job_key, rendition, &job_output)).await
+            .unwrap_or_else(|err| Err(squire::transcode::JobError::Failed(err.to_string())));
+        let playlist = match ensured {
+            Ok(playlist) => playlist,
+            Err(squire::transcode::JobError::Busy) => {
+                return HttpResponse::ServiceUnavailable()
+                    .insert_header(("Retry-After", BUSY_RETRY.to_string()))
+                    .json(routes::auth::DetailError {
+                        detail: "Server is busy transcoding other videos, please try again later".to_string()
+                    });
+            }
+            Err(err) => {
+                log::error!("{}", err);
+                return HttpResponse::ServiceUnavailable().json(routes::auth::DetailError {
+                    detail: format!("Failed to transcode '{}'", relative_path)
+                });
+            }
+        };
+        transcoder.touch(&format!("{}/{}", key, rendition.name));
+        let polled = playlist.clone();
+        if !squire::transcode::wait_for(PLAYLIST_TIMEOUT, move || squire::transcode::is_playable(&polled)).await {
+            return HttpResponse::ServiceUnavailable().json(routes::auth::DetailError {
+                detail: format!("Transcoding '{}' is taking longer than expected", relative_path)
+            });
+        }
+        let read_playlist = playlist.clone();
+        let content = web::block(move || std::fs::read_to_string(read_playlist)).await
+            .unwrap_or_else(|err| Err(std::io::Error::other(err.to_string())));
+        return match content {
+            Ok(content) => HttpResponse::Ok()
+                .content_type(PLAYLIST_TYPE)
+                .insert_header(("Cache-Control", "no-cache"))
+                .body(content),
+            Err(err) => {
+                log::error!("Failed to read {:?}: {}", playlist, err);
+                HttpResponse::NotFound().json(routes::auth::DetailError {
+                    detail: format!("Playlist '{}' was not found", filename)
+                })
+            }
+        };
+    }
+    // Segment names are generated by ffmpeg, anything else is rejected to avoid reading arbitrary files
+    let rendition_name = filename.split('_').next().unwrap_or_default();
+    if !filename.ends_with(".ts") || filename.contains('/') || squire::transcode::rendition(rendition_name).is_none() {
+        return HttpResponse::NotFound().json(routes::auth::DetailError {
+            detail: format!("'{}' was not found", filename)
+        });
+    }
+    let segment = output.join(&filename);
+    let job = format!("{}/{}", key, rendition_name);
+    transcoder.touch(&job);
+    // A segment is complete only once it is listed in the playlist
+    let playlist = output.join(format!("{}.m3u8", rendition_name));
+    let segment_name = filename.clone();
+    let is_ready = move || std::fs::read_to_string(&playlist).is_ok_and(|content| content.contains(&segment_name));
+    if transcoder.is_running(&job) {
+        squire::transcode::wait_for(SEGMENT_TIMEOUT, is_ready).await;
+    }
+    match actix_files::NamedFile::open_async(&segment).await {
+        Ok(file) => file.set_content_type("video/mp2t".parse().unwrap()).into_response(&request),
+        Err(_) => HttpResponse::NotFound().json(routes::auth::DetailError {
+            detail: format!("'{}' was not found", filename)
+        })
+    }
+}
@@ -2,8 +2,8 @@ use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use actix_web::{HttpRequest, HttpResponse, web};
-use actix_web::http::StatusCode;
+use actix_web::{get, HttpRequest, HttpResponse, web};
+use actix_web::http::{header, StatusCode};
 use fernet::Fernet;
 use minijinja;
 use serde::Deserialize;
@@ -15,14 +15,27 @@ use crate::{constant, routes, squire};
 /// Represents the payload structure for deserializing data from the request query parameters.
 #[derive(Deserialize)]
 pub struct Payload {
-    file: String,
+    pub file: String,
 }
 
-/// Represents the paths and filenames for subtitles, including both SRT and VTT formats.
-struct Subtitles {
-    srt: PathBuf,
-    vtt: PathBuf,
-    vtt_file: String,
+/// Represents the query parameters for a subtitle track.
+#[derive(Deserialize)]
+pub struct TrackQuery {
+    /// Path of the subtitle file, or the media file for the embedded subtitles, relative to `media_source`.
+    pub file: String,
+    /// Index of the subtitle stream within the media file, for the embedded subtitles.
+    pub stream: Option<u32>,
+    /// Offset in milliseconds to shift the subtitles by, positive values delay the subtitles.
+    pub offset: Option<i64>,
+}
+
+/// Represents the query parameters for a media file with the chosen audio track.
+#[derive(Deserialize)]
+pub struct RemuxQuery {
+    /// Path of the media file, relative to `media_source`.
+    pub file: String,
+    /// Index of the audio stream within the media file.
+    pub audio: u32,
 }
 
 /// URL encodes the provided path string.
@@ -40,35 +53,13 @@ struct Subtitles {
 /// # Returns
 ///
 /// Returns a URL encoded string.
-fn url_encode(path: &String) -> String {
+pub fn url_encode(path: &String) -> String {
     form_urlencoded::byte_serialize(path.as_bytes())
         .collect::<Vec<_>>()
         .join("")
 }
 
-/// Constructs a `Subtitles` struct based on the provided `target` path and `target_str`.
-///
-/// # Arguments
-///
-/// * `true_path` - True path of the requested file.
-/// * `relative_path` - The string representation of the relative filepath.
-///
-/// # Returns
-///
-/// Returns a `Subtitles` struct containing paths and filenames for both SRT and VTT subtitle files.
-fn subtitles(true_path: PathBuf, relative_path: &String) -> Subtitles {
-    // Set srt and vtt extensions to true path to check if they exist
-    let srt = true_path.with_extension("srt");
-    let vtt = true_path.with_extension("vtt");
-
-    // Set vtt extension to the relative path, so it could be used as a parameter in HTML
-    let vtt_filepath = PathBuf::new().join(relative_path).with_extension("vtt");
-    let vtt_file = vtt_filepath.to_string_lossy().to_string();
-
-    Subtitles { srt, vtt, vtt_file }
-}
-
-/// Handles requests for the '/track/{track_path:.*}' endpoint, serving track files.
+/// Handles requests for the '/track' endpoint, serving the subtitle files in WebVTT format.
 ///
 /// # Arguments
 ///
@@ -79,23 +70,26 @@ fn subtitles(true_path: PathBuf, relative_path: &String) -> Subtitles {
 /// * `metadata` - Struct containing metadata of the application.
 /// * `config` - Configuration data for the application.
 /// * `template` - Configuration container for the loaded templates.
+/// * `extractor` - Extractor struct that caches the subtitle streams extracted from the media files.
 ///
 /// # Returns
 ///
-/// Returns an `HttpResponse` containing the track file content or an error response.
+/// Returns an `HttpResponse` containing the track file content (converted from the `subtitle_cache` when required, and shifted by the `offset`) or an error response.
 #[get("/track")]
+#[allow(clippy::too_many_arguments)]
 pub async fn track(request: HttpRequest,
-                   info: web::Query<Payload>,
+                   info: web::Query<TrackQuery>,
                    fernet: web::Data<Arc<Fernet>>,
                    session: web::Data<Arc<constant::Session>>,
                    metadata: web::Data<Arc<constant::MetaData>>,
                    config: web::Data<Arc<squire::settings::Config>>,
-                   template: web::Data<Arc<minijinja::Environment<'static>>>) -> HttpResponse {
+                   template: web::Data<Arc<minijinja::Environment<'static>>>,
+                   extractor: web::Data<Arc<squire::extract::Extractor>>) -> HttpResponse {
     let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
     if !auth_response.ok {
         return routes::auth::failed_auth(auth_response, &config);
     }
-    if !squire::authenticator::verify_secure_index(&PathBuf::from(&info.file), &auth_response.username) {
+    if !squire::authenticator::verify_access(&PathBuf::from(&info.file), &auth_response.username, &config) {
         return squire::custom::error(
             "RESTRICTED SECTION",
             template.get_template("error").unwrap(),
@@ -104,22 +98,45 @@ pub async fn track(request: HttpRequest,
             StatusCode::FORBIDDEN
         );
     }
+    let offset = info.offset.unwrap_or_default();
+    if offset.abs() > squire::subtitles::MAX_OFFSET {
+        return squire::custom::error(
+            "INVALID OFFSET",
+            template.get_template("error").unwrap(),
+            &metadata.pkg_version,
+            format!("Offset must be within {} milliseconds", squire::subtitles::MAX_OFFSET),
+            StatusCode::BAD_REQUEST
+        );
+    }
     let (_host, _last_accessed) = squire::custom::log_connection(&request, &session);
     log::debug!("{}", auth_response.detail);
     log::debug!("Track requested: {}", &info.file);
     let filepath = Path::new(&config.media_source).join(&info.file);
     log::debug!("Track file lookup: {}", &filepath.to_string_lossy());
-    match std::fs::read_to_string(&filepath) {
+    let content = match info.stream {
+        Some(index) => extractor.subtitle(&filepath, &info.file, index)
+            .ok_or(format!("Subtitle stream {} was not extracted from '{}'", index, info.file))
+            .and_then(|subtitle| squire::subtitles::read(&subtitle, &info.file, &config)),
+        None => squire::subtitles::read(&filepath, &info.file, &config)
+    };
+    match content {
+        // Shifted track is generated on the fly, since the offset is adjusted by the user while watching
+        Ok(content) if offset != 0 => HttpResponse::Ok()
+            .content_type("text/vtt; charset=utf-8")
+            .body(squire::subtitles::to_vtt(&squire::subtitles::shift(squire::subtitles::parse_vtt(&content), offset))),
         Ok(content) => HttpResponse::Ok()
-            .content_type("text/plain")
+            .content_type("text/vtt; charset=utf-8")
             .body(content),
-        Err(_) => squire::custom::error(
-            "CONTENT UNAVAILABLE",
-            template.get_template("error").unwrap(),
-            &metadata.pkg_version,
-            format!("'{}' was not found", &info.file),
-            StatusCode::NOT_FOUND
-        )
+        Err(err) => {
+            log::warn!("{}", err);
+            squire::custom::error(
+                "CONTENT UNAVAILABLE",
+                template.get_template("error").unwrap(),
+                &metadata.pkg_version,
+                format!("'{}' was not found", &info.file),
+                StatusCode::NOT_FOUND
+            )
+        }
     }
 }
 
@@ -128,10 +145,10 @@ pub async fn track(request: HttpRequest,
 /// # Arguments
 ///
 /// * `landing` - `Template` retrieved from the configuration container.
-/// * `serializable` - `HashMap` that can be serialized into a single block of String to be rendered.
+/// * `serializable` - Context that can be serialized into a single block of String to be rendered.
 fn render_content(landing: minijinja::Template,
-                  serializable: HashMap<&str, &String>) -> HttpResponse {
-    return match landing.render(serializable) {
+                  serializable: minijinja::Value) -> HttpResponse {
+    match landing.render(serializable) {
         Ok(response_body) => {
             HttpResponse::build(StatusCode::OK)
                 .content_type("text/html; charset=utf-8").body(response_body)
@@ -140,7 +157,7 @@ fn render_content(landing: minijinja::Template,
             log::error!("{}", err);
             HttpResponse::FailedDependency().json("Failed to render content.")
         }
-    };
+    }
 }
 
 /// Handles requests for the `/stream/{media_path:.*}` endpoint, serving media files and directories.
@@ -149,23 +166,40 @@ fn render_content(landing: minijinja::Template,
 ///
 /// * `request` - A reference to the Actix web `HttpRequest` object.
 /// * `media_path` - The path parameter representing the media file or directory.
+/// * `query` - Query string with the sort order and the page of the files.
 /// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
 /// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
 /// * `metadata` - Struct containing metadata of the application.
 /// * `config` - Configuration data for the application.
 /// * `template` - Configuration container for the loaded templates.
+/// * `transcoder` - Transcoder struct that keeps track of the media available for adaptive streaming.
+/// * `prober` - Prober struct that extracts and caches the metadata of the media files.
+/// * `thumbnailer` - Thumbnailer struct for the grid view in listings and the seek-previews for videos.
+/// * `progress` - Progress struct that stores the playback positions and the watch history for each user.
+/// * `library` - Library index with the media files.
+/// * `extractor` - Extractor struct for the embedded subtitles and the audio tracks.
+/// * `preferences` - Preferences struct that stores the subtitle preferences for each user.
 ///
 /// # Returns
 ///
 /// Returns an `HttpResponse` containing the media content or directory listing, or an error response.
 #[get("/stream/{media_path:.*}")]
+#[allow(clippy::too_many_arguments)]
 pub async fn stream(request: HttpRequest,
                     media_path: web::Path<String>,
+                    query: web::Query<squire::content::ListingQuery>,
                     fernet: web::Data<Arc<Fernet>>,
                     session: web::Data<Arc<constant::Session>>,
                     metadata: web::Data<Arc<constant::MetaData>>,
                     config: web::Data<Arc<squire::settings::Config>>,
-                    template: web::Data<Arc<minijinja::Environment<'static>>>) -> HttpResponse {
+                    template: web::Data<Arc<minijinja::Environment<'static>>>,
+                    transcoder: web::Data<Arc<squire::transcode::Transcoder>>,
+                    prober: web::Data<Arc<squire::probe::Prober>>,
+                    thumbnailer: web::Data<Arc<squire::thumbnail::Thumbnailer>>,
+                    progress: web::Data<Arc<squire::progress::Progress>>,
+                    library: web::Data<Arc<squire::index::Library>>,
+                    extractor: web::Data<Arc<squire::extract::Extractor>>,
+                    preferences: web::Data<Arc<squire::preferences::Preferences>>) -> HttpResponse {
     let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
     if !auth_response.ok {
         return routes::auth::failed_auth(auth_response, &config);
@@ -173,7 +207,7 @@ pub async fn stream(request: HttpRequest,
     let (_host, _last_accessed) = squire::custom::log_connection(&request, &session);
     log::debug!("{}", auth_response.detail);
     let filepath = media_path.to_string();
-    if !squire::authenticator::verify_secure_index(&PathBuf::from(&filepath), &auth_response.username) {
+    if !squire::authenticator::verify_access(&PathBuf::from(&filepath), &auth_response.username, &config) {
         return squire::custom::error(
             "RESTRICTED SECTION",
             template.get_template("error").unwrap(),
@@ -200,51 +234,84 @@ pub async fn stream(request: HttpRequest,
     let __filename = __target.file_name().unwrap().to_string_lossy().to_string();
     if __target.is_file() {
         let landing = template.get_template("landing").unwrap();
-        let rust_iter = squire::content::get_iter(&__target, &config.file_formats);
+        let rust_iter = squire::content::get_iter(&__target, &config, &auth_response, &library, &prober, &query);
         let render_path = format!("/media?file={}", url_encode(&filepath));
         let prev = rust_iter.previous.unwrap_or_default();
         let next = rust_iter.next.unwrap_or_default();
+        let sort_query = query.sort_query();
         let secure_index = constant::SECURE_INDEX.to_string();
+        let role = squire::authenticator::role(&auth_response, &config).as_str().to_string();
         let mut context_builder = vec![
             ("version", &metadata.pkg_version),
             ("media_title", &__filename),
             ("path", &render_path),
             ("previous", &prev),
             ("next", &next),
+            ("sort_query", &sort_query),
             ("user", &auth_response.username),
+            ("role", &role),
             ("secure_index", &secure_index),
         ].into_iter().collect::<HashMap<_, _>>();
         if constant::IMAGE_FORMATS
             .contains(&render_path.split('.')
-                .last()
+                .next_back()
                 .unwrap()  // file extension WILL be present at this point
                 .to_lowercase().as_str()) {
             context_builder.insert("render_image", &render_path);
-            return render_content(landing, context_builder);
+            return render_content(landing, minijinja::Value::from_serialize(&context_builder));
         }
-        let subtitle = subtitles(__target, &filepath);
-        let mut sfx_file = String::new();
-        if subtitle.vtt.exists() {
-            sfx_file = format!("/track?file={}", url_encode(&subtitle.vtt_file));
-        } else if subtitle.srt.exists() {
-            log::info!("Converting {:?} to {:?} for subtitles",
-                subtitle.srt.file_name().unwrap(),
-                subtitle.vtt.file_name().unwrap());
-            match squire::subtitles::srt_to_vtt(&subtitle.srt) {
-                Ok(_) => {
-                    log::debug!("Successfully converted srt to vtt file");
-                    sfx_file = format!("/track?file={}", url_encode(&subtitle.vtt_file));
-                }
-                Err(err) => log::error!("Failed to convert srt to vtt: {}", err),
-            }
+        progress.record(&auth_response.username, &filepath);
+        let (probe_target, probe_path, probe_prober) = (__target.clone(), filepath.clone(), prober.clone());
+        let probed = web::block(move || probe_prober.probe(&probe_target, &probe_path)).await.ok().flatten();
+        let media_info = probed.as_ref()
+            .map(|info| info.summary())
+            .unwrap_or_default();
+        if !media_info.is_empty() {
+            context_builder.insert("media_info", &media_info);
         }
-        if !sfx_file.is_empty() {
-            context_builder.insert("track", &sfx_file);
+        let (resume, resume_str) = progress.resume(&auth_response.username, &filepath)
+            .map(|watched| (watched.position.to_string(), watched.position_str()))
+            .unwrap_or_default();
+        if !resume.is_empty() {
+            context_builder.insert("resume", &resume);
+            context_builder.insert("resume_str", &resume_str);
+        }
+        let preview_track = format!("/preview?file={}", url_encode(&filepath));
+        if thumbnailer.preview(&__target, &filepath).is_some() {
+            context_builder.insert("previews", &preview_track);
+        } else {
+            thumbnailer.schedule_preview(&prober, &__target, &filepath);
         }
-        return render_content(landing, context_builder);
+        let (hls_source, dash_source) = squire::transcode::sources(&transcoder, &__target, &filepath);
+        if let Some(hls_source) = &hls_source {
+            context_builder.insert("hls", hls_source);
+        }
+        if let Some(dash_source) = &dash_source {
+            context_builder.insert("dash", dash_source);
+        }
+        let subtitle_preferences = preferences.subtitles(&auth_response.username);
+        let preferred = subtitle_preferences.languages(request.headers().get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .map(squire::subtitles::preferred_languages)
+            .unwrap_or_default());
+        let (embedded, audio_tracks) = match &probed {
+            Some(info) => {
+                extractor.schedule_subtitles(&__target, &filepath, info);
+                (extractor.tracks(&__target, &filepath, info), squire::extract::audio_sources(&extractor, info, &filepath))
+            }
+            None => (Vec::new(), Vec::new())
+        };
+        let tracks = squire::subtitles::tracks(&__target, &filepath, &preferred, &config, embedded);
+        return render_content(landing, minijinja::context! {
+            tracks => tracks,
+            audio_tracks => audio_tracks,
+            subtitles => subtitle_preferences,
+            max_offset => squire::subtitles::MAX_OFFSET,
+            ..minijinja::Value::from_serialize(&context_builder)
+        });
     } else if __target.is_dir() {
-        let child_dir = __target.iter().last().unwrap().to_string_lossy().to_string();
-        let listing_page = squire::content::get_dir_stream_content(&__target_str, &child_dir, &config.file_formats);
+        let child_dir = __target.iter().next_back().unwrap().to_string_lossy().to_string();
+        let listing_page = squire::content::get_dir_stream_content(&__target_str, &child_dir, &config, &auth_response, &prober, &library, &query);
         let listing = template.get_template("listing").unwrap();
         let custom_title = if child_dir.ends_with(constant::SECURE_INDEX) {
             format!(
@@ -261,10 +328,15 @@ pub async fn stream(request: HttpRequest,
                 custom_title => custom_title,
                 files => listing_page.files,
                 user => auth_response.username,
+                role => squire::authenticator::role(&auth_response, &config).as_str(),
                 secure_index => constant::SECURE_INDEX,
                 directories => listing_page.directories,
                 secured_directories => listing_page.secured_directories,
-                secure_path => &secure_flag
+                secure_path => &secure_flag,
+                thumbnails => thumbnailer.enabled,
+                pagination => listing_page.pagination,
+                sorting => &*query,
+                sort_query => query.sort_query()
             )).unwrap());
     }
     log::error!("Something went horribly wrong");
@@ -303,7 +375,7 @@ pub async fn streaming_endpoint(request: HttpRequest,
         return routes::auth::failed_auth(auth_response, &config);
     }
     let media_path = config.media_source.join(&info.file);
-    if !squire::authenticator::verify_secure_index(&media_path, &auth_response.username) {
+    if !squire::authenticator::verify_access(Path::new(&info.file), &auth_response.username, &config) {
         return squire::custom::error(
             "RESTRICTED SECTION",
             template.get_template("error").unwrap(),
@@ -333,3 +405,82 @@ pub async fn streaming_endpoint(request: HttpRequest,
         StatusCode::NOT_FOUND
     )
 }
+
+/// Handles requests for the `/remux` endpoint, serving the media file with the chosen audio track.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `info` - Query string with the media file and the index of the audio stream.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `prober` - Prober struct that extracts and caches the metadata of the media files.
+/// * `extractor` - Extractor struct that remuxes the media files with the chosen audio track.
+///
+/// ## See Also
+///
+/// - The media file is remuxed in the background on the first request, without re-encoding the streams.
+/// - Players are expected to retry until the remuxed variant is ready, as indicated by the `Retry-After` header.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the remuxed variant of the media file (`206` for the range requests).
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message if the media is not accessible to the user.
+/// * `404` - HttpResponse with an error message if the media file or the audio track doesn't exist.
+/// * `500` - HttpResponse with an error message if remuxing failed for the current version of the media file.
+/// * `503` - HttpResponse with an error message if remuxing is unavailable or still in progress.
+#[get("/remux")]
+pub async fn remux(request: HttpRequest,
+                   info: web::Query<RemuxQuery>,
+                   fernet: web::Data<Arc<Fernet>>,
+                   session: web::Data<Arc<constant::Session>>,
+                   config: web::Data<Arc<squire::settings::Config>>,
+                   prober: web::Data<Arc<squire::probe::Prober>>,
+                   extractor: web::Data<Arc<squire::extract::Extractor>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return routes::auth::failed_auth(auth_response, &config);
+    }
+    if !squire::authenticator::verify_access(&PathBuf::from(&info.file), &auth_response.username, &config) {
+        return HttpResponse::Forbidden().json(routes::auth::DetailError {
+            detail: format!("'{}' is not accessible to the user profile '{}'", info.file, auth_response.username)
+        });
+    }
+    let filepath = config.media_source.join(&info.file);
+    let (probe_target, probe_path, probe_prober) = (filepath.clone(), info.file.clone(), prober.clone());
+    let audio_track = web::block(move || probe_prober.probe(&probe_target, &probe_path)).await.ok().flatten()
+        .and_then(|probed| probed.audio_tracks.into_iter().find(|audio| audio.index == info.audio));
+    if !filepath.is_file() || audio_track.is_none() {
+        return HttpResponse::NotFound().json(routes::auth::DetailError {
+            detail: format!("Audio track {} of '{}' was not found", info.audio, info.file)
+        });
+    }
+    if let Some(remuxed) = extractor.remux(&filepath, &info.file, info.audio) {
+        return match actix_files::NamedFile::open_async(&remuxed).await {
+            Ok(file) => file.into_response(&request),
+            Err(err) => {
+                log::error!("Failed to open {:?}: {}", remuxed, err);
+                HttpResponse::NotFound().json(routes::auth::DetailError {
+                    detail: format!("Audio track {} of '{}' was not found", info.audio, info.file)
+                })
+            }
+        };
+    }
+    if !extractor.enabled {
+        return HttpResponse::ServiceUnavailable().json(routes::auth::DetailError {
+            detail: "Switching the audio tracks is not available".to_string()
+        });
+    }
+    if !extractor.schedule_remux(&filepath, &info.file, info.audio) {
+        return HttpResponse::InternalServerError().json(routes::auth::DetailError {
+            detail: format!("Audio track {} of '{}' could not be prepared", info.audio, info.file)
+        });
+    }
+    HttpResponse::ServiceUnavailable()
+        .insert_header((header::RETRY_AFTER, "5"))
+        .json(routes::auth::DetailError {
+            detail: format!("Preparing '{}' with the audio track {}, please retry shortly", info.file, info.audio)
+        })
+}
@@ -8,3 +8,21 @@ pub mod auth;
 pub mod upload;
 /// Module for `/edit` entrypoint that handles delete/rename actions.
 pub mod fileio;
+/// Module for `/share`, `/shares` and `/shared` entrypoints that handle the share links.
+pub mod share;
+/// Module for the `/api/v1` entrypoints that expose the library as JSON.
+pub mod api;
+/// Module for `/tokens` entrypoints that handle the API tokens.
+pub mod tokens;
+/// Module for `/hls` entrypoint that serves the HLS playlists and segments.
+pub mod hls;
+/// Module for `/dash` entrypoint that serves the MPEG-DASH manifest and segments.
+pub mod dash;
+/// Module for `/thumbnail` and `/preview` entrypoints that serve the thumbnails and seek-previews of the media files.
+pub mod thumbnail;
+/// Module for `/progress` entrypoint that stores the playback positions reported by the player.
+pub mod progress;
+/// Module for `/preferences` entrypoints that retrieve and store the subtitle preferences.
+pub mod preferences;
+/// Module for `/search` entrypoint that renders the search results.
+pub mod search;
@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use actix_web::{get, HttpRequest, HttpResponse, post, web};
+use fernet::Fernet;
+
+use crate::{constant, routes, squire};
+
+/// Handles requests for the `/preferences` endpoint, to retrieve the subtitle preferences of the user.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `preferences` - Preferences struct that stores the subtitle preferences for each user.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the subtitle preferences as JSON.
+/// * `401` - HttpResponse with an error message for failed authentication.
+#[get("/preferences")]
+pub async fn retrieve(request: HttpRequest,
+                      fernet: web::Data<Arc<Fernet>>,
+                      session: web::Data<Arc<constant::Session>>,
+                      config: web::Data<Arc<squire::settings::Config>>,
+                      preferences: web::Data<Arc<squire::preferences::Preferences>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return routes::auth::failed_auth(auth_response, &config);
+    }
+    HttpResponse::Ok().json(preferences.subtitles(&auth_response.username))
+}
+
+/// Handles requests for the `/preferences` endpoint, to store the subtitle preferences of the user.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `payload` - JSON payload with the `offset` in milliseconds, `font_size` as a percentage and the `language`
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `preferences` - Preferences struct that stores the subtitle preferences for each user.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the stored subtitle preferences as JSON.
+/// * `400` - HttpResponse with an error message for invalid offset, font size or language.
+/// * `401` - HttpResponse with an error message for failed authentication.
+#[post("/preferences")]
+pub async fn update(request: HttpRequest,
+                    payload: web::Json<squire::preferences::SubtitlePreferences>,
+                    fernet: web::Data<Arc<Fernet>>,
+                    session: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>,
+                    preferences: web::Data<Arc<squire::preferences::Preferences>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return routes::auth::failed_auth(auth_response, &config);
+    }
+    match payload.into_inner().validate() {
+        Ok(validated) => {
+            preferences.update(&auth_response.username, validated.clone());
+            HttpResponse::Ok().json(validated)
+        }
+        Err(err) => HttpResponse::BadRequest().json(routes::auth::DetailError { detail: err })
+    }
+}
@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use actix_web::{HttpRequest, HttpResponse, post, web};
+use fernet::Fernet;
+use serde::Deserialize;
+
+use crate::{constant, routes, squire};
+
+/// Represents the payload structure to report the playback position.
+#[derive(Deserialize)]
+pub struct ProgressPayload {
+    file: String,
+    position: f64,
+    duration: f64,
+}
+
+/// Handles requests for the `/progress` endpoint, to store the playback position reported by the player.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `payload` - JSON payload with the `file`, `position` and `duration` in seconds.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `progress` - Progress struct that stores the playback positions for each user.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse when the position is stored.
+/// * `400` - HttpResponse with an error message for invalid position or duration.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message if the media is not accessible to the user.
+/// * `404` - HttpResponse with an error message if the media file doesn't exist.
+#[post("/progress")]
+pub async fn update(request: HttpRequest,
+                    payload: web::Json<ProgressPayload>,
+                    fernet: web::Data<Arc<Fernet>>,
+                    session: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>,
+                    progress: web::Data<Arc<squire::progress::Progress>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return routes::auth::failed_auth(auth_response, &config);
+    }
+    let valid = |value: f64| value.is_finite() && value >= 0.0;
+    if !valid(payload.position) || !valid(payload.duration) {
+        return HttpResponse::BadRequest().json(routes::auth::DetailError {
+            detail: "Position and duration must be positive numbers".to_string()
+        });
+    }
+    if !squire::authenticator::verify_access(&PathBuf::from(&payload.file), &auth_response.username, &config) {
+        return HttpResponse::Forbidden().json(routes::auth::DetailError {
+            detail: format!("'{}' is not accessible to the user profile '{}'", payload.file, auth_response.username)
+        });
+    }
+    if !config.media_source.join(&payload.file).is_file() {
+        return HttpResponse::NotFound().json(routes::auth::DetailError {
+            detail: format!("'{}' was not found", payload.file)
+        });
+    }
+    progress.update(&auth_response.username, &payload.file, payload.position, payload.duration);
+    HttpResponse::Ok().finish()
+}
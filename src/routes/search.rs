@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use actix_web::{get, HttpRequest, HttpResponse, web};
+use actix_web::http::StatusCode;
+use chrono::NaiveDate;
+use fernet::Fernet;
+use minijinja;
+use serde::Deserialize;
+
+use crate::{constant, routes, squire};
+
+/// Default number of search results, when the limit is not specified.
+pub const DEFAULT_LIMIT: usize = 50;
+
+/// Maximum number of search results.
+pub const MAX_LIMIT: usize = 500;
+
+/// Represents the query parameters for the search.
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    /// Free text query.
+    q: Option<String>,
+    /// Comma separated list of file extensions.
+    ext: Option<String>,
+    /// Directory to search within, relative to `media_source`.
+    dir: Option<String>,
+    /// Only include the files modified on or after this date (`YYYY-MM-DD`).
+    after: Option<String>,
+    /// Only include the files modified on or before this date (`YYYY-MM-DD`).
+    before: Option<String>,
+    /// Maximum number of results.
+    pub limit: Option<usize>,
+}
+
+impl SearchQuery {
+    /// Converts the query parameters into a `SearchFilter`.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the `SearchFilter`, or an error message for the invalid dates.
+    pub fn filter(&self) -> Result<squire::search::SearchFilter, String> {
+        let parse_date = |value: &Option<String>| -> Result<Option<NaiveDate>, String> {
+            match value.as_deref().map(str::trim).filter(|value| !value.is_empty()) {
+                Some(value) => NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                    .map(Some)
+                    .map_err(|_| "Dates must be in the format YYYY-MM-DD".to_string()),
+                None => Ok(None)
+            }
+        };
+        Ok(squire::search::SearchFilter {
+            query: self.q.clone().unwrap_or_default(),
+            extensions: self.ext.as_deref().unwrap_or_default()
+                .split(',')
+                .map(|extension| extension.trim().trim_start_matches('.').to_lowercase())
+                .filter(|extension| !extension.is_empty())
+                .collect(),
+            directory: self.dir.clone().filter(|directory| !directory.trim().is_empty()),
+            after: parse_date(&self.after)?,
+            before: parse_date(&self.before)?,
+        })
+    }
+}
+
+/// Handles requests for the `/search` endpoint, rendering the search results in the listing page.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `query` - Query string with the search text and the filters.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `metadata` - Struct containing metadata of the application.
+/// * `config` - Configuration data for the application.
+/// * `template` - Configuration container for the loaded templates.
+/// * `library` - Library index with the media files.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the listing page of the search results.
+/// * `400` - HttpResponse with an error page for invalid filters.
+/// * `401` - HttpResponse with an error message for failed authentication.
+#[get("/search")]
+#[allow(clippy::too_many_arguments)]
+pub async fn search(request: HttpRequest,
+                    query: web::Query<SearchQuery>,
+                    fernet: web::Data<Arc<Fernet>>,
+                    session: web::Data<Arc<constant::Session>>,
+                    metadata: web::Data<Arc<constant::MetaData>>,
+                    config: web::Data<Arc<squire::settings::Config>>,
+                    template: web::Data<Arc<minijinja::Environment<'static>>>,
+                    library: web::Data<Arc<squire::index::Library>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return routes::auth::failed_auth(auth_response, &config);
+    }
+    let (_host, _last_accessed) = squire::custom::log_connection(&request, &session);
+    let filter = match query.filter() {
+        Ok(filter) => filter,
+        Err(err) => return squire::custom::error(
+            "INVALID SEARCH",
+            template.get_template("error").unwrap(),
+            &metadata.pkg_version,
+            err,
+            StatusCode::BAD_REQUEST
+        )
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    let files = squire::search::search(&library, &filter, &config, &auth_response, limit);
+    let listing = template.get_template("listing").unwrap();
+    HttpResponse::build(StatusCode::OK)
+        .content_type("text/html; charset=utf-8")
+        .body(listing.render(minijinja::context!(
+            version => metadata.pkg_version,
+            custom_title => "Search Results",
+            files => files,
+            user => auth_response.username,
+            role => squire::authenticator::role(&auth_response, &config).as_str(),
+            secure_index => constant::SECURE_INDEX,
+            search => filter.query
+        )).unwrap())
+}
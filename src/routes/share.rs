@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use actix_web::{get, HttpRequest, HttpResponse, post, web};
+use actix_web::http::StatusCode;
+use fernet::Fernet;
+use minijinja;
+use serde::{Deserialize, Serialize};
+
+use crate::{constant, routes, squire};
+
+/// Default duration (1 day) for a share link, when the expiry is not specified.
+const DEFAULT_EXPIRY: i64 = 86_400;
+
+/// Maximum duration (30 days) for a share link.
+const MAX_EXPIRY: i64 = 2_592_000;
+
+/// Represents the payload structure to create a share link.
+#[derive(Deserialize)]
+pub struct SharePayload {
+    file: String,
+    expiry: Option<i64>,
+    max_views: Option<u32>,
+}
+
+/// Represents the payload structure to revoke a share link.
+#[derive(Deserialize)]
+pub struct RevokePayload {
+    id: String,
+}
+
+/// Represents the query parameters of a share link.
+#[derive(Deserialize)]
+pub struct ShareQuery {
+    expiry: i64,
+    signature: String,
+    view: Option<String>,
+}
+
+/// Struct for representing a share link in JSON format.
+#[derive(Serialize)]
+struct ShareResponse {
+    id: String,
+    path: String,
+    url: String,
+    created: i64,
+    expiry: i64,
+    max_views: Option<u32>,
+    views: u32,
+}
+
+/// Converts the `ShareLink` into a `ShareResponse` with the signed URL.
+///
+/// # Arguments
+///
+/// * `link` - Share link stored in the server.
+/// * `config` - Configuration data for the application.
+///
+/// # Returns
+///
+/// Returns the `ShareResponse` that can be serialized into JSON.
+fn share_response(link: squire::share::ShareLink, config: &squire::settings::Config) -> ShareResponse {
+    ShareResponse {
+        url: squire::share::url(&config.secret_key, &link),
+        id: link.id,
+        path: link.path,
+        created: link.created,
+        expiry: link.expiry,
+        max_views: link.max_views,
+        views: link.views,
+    }
+}
+
+/// Handles requests for the `/share` endpoint, to create a signed and expiring link for a media file.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `payload` - JSON payload with the `file`, `expiry` in seconds and `max_views` received from the UI.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `shares` - Shares struct that holds the share links.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the share link.
+/// * `400` - HttpResponse with an error message for invalid expiry or a path that is not a file.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message if the file is not accessible to the user.
+#[post("/share")]
+pub async fn create(request: HttpRequest,
+                    payload: web::Json<SharePayload>,
+                    fernet: web::Data<Arc<Fernet>>,
+                    session: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>,
+                    shares: web::Data<Arc<squire::share::Shares>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return routes::auth::failed_auth(auth_response, &config);
+    }
+    let expiry = payload.expiry.unwrap_or(DEFAULT_EXPIRY);
+    if expiry <= 0 || expiry > MAX_EXPIRY {
+        return HttpResponse::BadRequest().json(routes::auth::DetailError {
+            detail: format!("Expiry must be between 1 and {} seconds", MAX_EXPIRY)
+        });
+    }
+    if payload.max_views == Some(0) {
+        return HttpResponse::BadRequest().json(routes::auth::DetailError {
+            detail: "Maximum views must be greater than zero".to_string()
+        });
+    }
+    let filepath = payload.file.trim_start_matches('/');
+    if !squire::authenticator::verify_access(&PathBuf::from(filepath), &auth_response.username, &config) {
+        return HttpResponse::Forbidden().json(routes::auth::DetailError {
+            detail: format!("'{}' is not accessible to the user profile '{}'", filepath, auth_response.username)
+        });
+    }
+    if !config.media_source.join(filepath).is_file() {
+        return HttpResponse::BadRequest().json(routes::auth::DetailError {
+            detail: format!("'{}' is not a file", filepath)
+        });
+    }
+    let link = shares.mint(filepath, &auth_response.username, expiry, payload.max_views);
+    log::info!("'{}' shared '{}' for {}s", auth_response.username, filepath, expiry);
+    HttpResponse::Ok().json(share_response(link, &config))
+}
+
+/// Handles requests for the `/shares` endpoint, to list the active share links created by the user.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `shares` - Shares struct that holds the share links.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the list of share links.
+/// * `401` - HttpResponse with an error message for failed authentication.
+#[get("/shares")]
+pub async fn list(request: HttpRequest,
+                  fernet: web::Data<Arc<Fernet>>,
+                  session: web::Data<Arc<constant::Session>>,
+                  config: web::Data<Arc<squire::settings::Config>>,
+                  shares: web::Data<Arc<squire::share::Shares>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return routes::auth::failed_auth(auth_response, &config);
+    }
+    let links: Vec<ShareResponse> = shares.list(&auth_response.username)
+        .into_iter()
+        .map(|link| share_response(link, &config))
+        .collect();
+    HttpResponse::Ok().json(links)
+}
+
+/// Handles requests for the `/share/revoke` endpoint, to revoke a share link created by the user.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `payload` - JSON payload with the `id` of the share link.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `shares` - Shares struct that holds the share links.
+///
+/// # Returns
+///
+/// * `200` - Blank HttpResponse to indicate that the share link was revoked.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `404` - HttpResponse with an error message if the share link doesn't exist for the user.
+#[post("/share/revoke")]
+pub async fn revoke(request: HttpRequest,
+                    payload: web::Json<RevokePayload>,
+                    fernet: web::Data<Arc<Fernet>>,
+                    session: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>,
+                    shares: web::Data<Arc<squire::share::Shares>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return routes::auth::failed_auth(auth_response, &config);
+    }
+    if shares.revoke(&payload.id, &auth_response.username) {
+        log::info!("'{}' revoked the share link '{}'", auth_response.username, payload.id);
+        return HttpResponse::Ok().finish();
+    }
+    HttpResponse::NotFound().json(routes::auth::DetailError {
+        detail: format!("Share link '{}' was not found", payload.id)
+    })
+}
+
+/// Validates the share link and the issuer's access to the shared file.
+///
+/// # Arguments
+///
+/// * `id` - ID of the share link.
+/// * `query` - Query parameters with the expiry and signature.
+/// * `config` - Configuration data for the application.
+/// * `shares` - Shares struct that holds the share links.
+/// * `view` - Kind of the request, either the landing page or the media.
+///
+/// ## See Also
+///
+/// - The issuer's access is verified on every request, so a link stops working if the issuer loses access.
+///
+/// # Returns
+///
+/// Returns a `Result` with the `ShareLink`, or the status code and an error message.
+fn validate(id: &str,
+            query: &ShareQuery,
+            config: &squire::settings::Config,
+            shares: &squire::share::Shares,
+            view: squire::share::View) -> Result<squire::share::ShareLink, (StatusCode, String)> {
+    let link = shares.validate(id, query.expiry, &query.signature, &config.secret_key, view)
+        .map_err(|err| (StatusCode::FORBIDDEN, err.to_string()))?;
+    if !config.authorization.contains_key(&link.issuer)
+        || !squire::authenticator::verify_access(&PathBuf::from(&link.path), &link.issuer, config) {
+        log::warn!("Share link '{}' is no longer accessible to its issuer '{}'", link.id, link.issuer);
+        return Err((StatusCode::FORBIDDEN, "This link is no longer valid".to_string()));
+    }
+    if !config.media_source.join(&link.path).is_file() {
+        return Err((StatusCode::NOT_FOUND, format!("'{}' was not found", link.path)));
+    }
+    Ok(link)
+}
+
+/// Handles requests for the `/shared/{id}` endpoint, rendering a read-only landing page without a session.
+///
+/// # Arguments
+///
+/// * `id` - ID of the share link.
+/// * `query` - Query parameters with the expiry and signature.
+/// * `metadata` - Struct containing metadata of the application.
+/// * `config` - Configuration data for the application.
+/// * `shares` - Shares struct that holds the share links.
+/// * `template` - Configuration container for the loaded templates.
+///
+/// # Returns
+///
+/// Returns an `HttpResponse` with the landing page or an error page.
+#[get("/shared/{id}")]
+pub async fn landing(id: web::Path<String>,
+                     query: web::Query<ShareQuery>,
+                     metadata: web::Data<Arc<constant::MetaData>>,
+                     config: web::Data<Arc<squire::settings::Config>>,
+                     shares: web::Data<Arc<squire::share::Shares>>,
+                     template: web::Data<Arc<minijinja::Environment<'static>>>) -> HttpResponse {
+    // View is counted only after the issuer's access and the file have been verified
+    let validated = validate(&id, &query, &config, &shares, squire::share::View::Landing)
+        .and_then(|link| shares.count_view(&link.id)
+            .map(|token| (link, token))
+            .map_err(|err| (StatusCode::FORBIDDEN, err.to_string())));
+    let (link, token) = match validated {
+        Ok(validated) => validated,
+        Err((status_code, description)) => {
+            return squire::custom::error(
+                "LINK UNAVAILABLE",
+                template.get_template("error").unwrap(),
+                &metadata.pkg_version,
+                description,
+                status_code
+            );
+        }
+    };
+    let filename = PathBuf::from(&link.path).file_name().unwrap().to_string_lossy().to_string();
+    let mut render_path = format!("/shared/{}/media?expiry={}&signature={}", link.id, query.expiry, query.signature);
+    if let Some(token) = token {
+        // Media requests are bound to this view, so they cannot outlive the view limit
+        render_path.push_str(&format!("&view={}", token));
+    }
+    let shared = "true".to_string();
+    let mut context_builder = vec![
+        ("version", &metadata.pkg_version),
+        ("media_title", &filename),
+        ("path", &render_path),
+        ("shared", &shared),
+    ].into_iter().collect::<HashMap<_, _>>();
+    let extension = filename.split('.').next_back().unwrap_or_default().to_lowercase();
+    if constant::IMAGE_FORMATS.contains(&extension.as_str()) {
+        context_builder.insert("render_image", &render_path);
+    }
+    let landing = template.get_template("landing").unwrap();
+    match landing.render(context_builder) {
+        Ok(response_body) => HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body(response_body),
+        Err(err) => {
+            log::error!("{}", err);
+            HttpResponse::FailedDependency().json("Failed to render content.")
+        }
+    }
+}
+
+/// Handles requests for the `/shared/{id}/media` endpoint, serving the shared file without a session.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `id` - ID of the share link.
+/// * `query` - Query parameters with the expiry and signature.
+/// * `config` - Configuration data for the application.
+/// * `shares` - Shares struct that holds the share links.
+///
+/// # Returns
+///
+/// Returns an `HttpResponse` with the media content or an error message.
+#[get("/shared/{id}/media")]
+pub async fn media(request: HttpRequest,
+                   id: web::Path<String>,
+                   query: web::Query<ShareQuery>,
+                   config: web::Data<Arc<squire::settings::Config>>,
+                   shares: web::Data<Arc<squire::share::Shares>>) -> HttpResponse {
+    let view = squire::share::View::Media(query.view.as_deref());
+    let link = match validate(&id, &query, &config, &shares, view) {
+        Ok(validated) => validated,
+        Err((status_code, description)) => {
+            return HttpResponse::build(status_code).json(routes::auth::DetailError { detail: description });
+        }
+    };
+    match actix_files::NamedFile::open_async(config.media_source.join(&link.path)).await {
+        Ok(file) => file.into_response(&request),
+        Err(err) => {
+            log::error!("Failed to open '{}' for share link '{}': {}", link.path, link.id, err);
+            HttpResponse::NotFound().json(routes::auth::DetailError {
+                detail: format!("'{}' was not found", link.path)
+            })
+        }
+    }
+}
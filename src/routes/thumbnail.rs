@@ -0,0 +1,183 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use actix_web::{get, HttpRequest, HttpResponse, web};
+use fernet::Fernet;
+use serde::Deserialize;
+
+use crate::{constant, routes, squire};
+
+/// Handles requests for the `/thumbnail` endpoint, serving the thumbnails of the media files.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `info` - Query string from the request with the path of the media file, relative to `media_source`.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `thumbnailer` - Thumbnailer struct that generates and caches the thumbnails.
+/// * `prober` - Prober struct that extracts and caches the metadata of the media files.
+///
+/// ## See Also
+///
+/// - Thumbnails are generated on the first request, and served from the cache until the media file is modified.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the thumbnail as a JPEG image.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message if the media is not accessible to the user.
+/// * `404` - HttpResponse with an error message if the media file doesn't exist.
+/// * `503` - HttpResponse with an error message if `ffmpeg` is unavailable or failed to generate the thumbnail.
+#[get("/thumbnail")]
+pub async fn thumbnail(request: HttpRequest,
+                       info: web::Query<routes::media::Payload>,
+                       fernet: web::Data<Arc<Fernet>>,
+                       session: web::Data<Arc<constant::Session>>,
+                       config: web::Data<Arc<squire::settings::Config>>,
+                       thumbnailer: web::Data<Arc<squire::thumbnail::Thumbnailer>>,
+                       prober: web::Data<Arc<squire::probe::Prober>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return routes::auth::failed_auth(auth_response, &config);
+    }
+    if !thumbnailer.enabled {
+        return HttpResponse::ServiceUnavailable().json(routes::auth::DetailError {
+            detail: "Thumbnails are not available".to_string()
+        });
+    }
+    let relative_path = info.into_inner().file;
+    if !squire::authenticator::verify_access(&PathBuf::from(&relative_path), &auth_response.username, &config) {
+        return HttpResponse::Forbidden().json(routes::auth::DetailError {
+            detail: format!("'{}' is not accessible to the user profile '{}'", relative_path, auth_response.username)
+        });
+    }
+    let filepath = config.media_source.join(&relative_path);
+    let extension = filepath.extension().unwrap_or_default().to_string_lossy().to_string();
+    if !filepath.is_file() || !config.file_formats.contains(&extension) {
+        return HttpResponse::NotFound().json(routes::auth::DetailError {
+            detail: format!("'{}' was not found", relative_path)
+        });
+    }
+    let media = relative_path.clone();
+    let generated = web::block(move || {
+        thumbnailer.generate(&filepath, &media, &prober)
+    }).await;
+    let thumbnail = match generated {
+        Ok(Ok(thumbnail)) => thumbnail,
+        Ok(Err(err)) => {
+            log::warn!("{}", err);
+            return HttpResponse::ServiceUnavailable().json(routes::auth::DetailError {
+                detail: "Failed to generate the thumbnail".to_string()
+            });
+        }
+        Err(err) => {
+            log::error!("{}", err);
+            return HttpResponse::ServiceUnavailable().json(routes::auth::DetailError {
+                detail: "Failed to generate the thumbnail".to_string()
+            });
+        }
+    };
+    let is_svg = thumbnail.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("svg"));
+    let content_type = if is_svg { "image/svg+xml" } else { "image/jpeg" };
+    match actix_files::NamedFile::open_async(&thumbnail).await {
+        Ok(file) => {
+            let mut response = file.set_content_type(content_type.parse().unwrap()).into_response(&request);
+            response.headers_mut().insert(
+                actix_web::http::header::CACHE_CONTROL,
+                actix_web::http::header::HeaderValue::from_static("private, max-age=86400"),
+            );
+            if is_svg {
+                // SVGs are served as-is, so the scripts within them are blocked when opened directly
+                response.headers_mut().insert(
+                    actix_web::http::header::CONTENT_SECURITY_POLICY,
+                    actix_web::http::header::HeaderValue::from_static("default-src 'none'; style-src 'unsafe-inline'; sandbox"),
+                );
+            }
+            response
+        }
+        Err(err) => {
+            log::error!("Failed to read {:?}: {}", thumbnail, err);
+            HttpResponse::NotFound().json(routes::auth::DetailError {
+                detail: format!("Thumbnail for '{}' was not found", relative_path)
+            })
+        }
+    }
+}
+
+/// Represents the query parameters for the seek-preview endpoint.
+#[derive(Deserialize)]
+pub struct PreviewPayload {
+    /// Path of the media file, relative to `media_source`.
+    file: String,
+    /// Number of the sprite sheet, the WebVTT track is served when unset.
+    sheet: Option<u32>,
+}
+
+/// Handles requests for the `/preview` endpoint, serving the seek-preview track and sprite sheets.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `info` - Query string from the request with the path of the media file, and optionally the sprite sheet number.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `thumbnailer` - Thumbnailer struct that generates and caches the sprite sheets.
+///
+/// ## See Also
+///
+/// - Sprite sheets are generated in the background, when the landing page is rendered for the video.
+/// - Cues in the WebVTT track point back to this endpoint, with the sheet number and the `#xywh` media fragment.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the WebVTT track or a sprite sheet.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message if the media is not accessible to the user.
+/// * `404` - HttpResponse with an error message if the preview is not generated (yet).
+#[get("/preview")]
+pub async fn preview(request: HttpRequest,
+                     info: web::Query<PreviewPayload>,
+                     fernet: web::Data<Arc<Fernet>>,
+                     session: web::Data<Arc<constant::Session>>,
+                     config: web::Data<Arc<squire::settings::Config>>,
+                     thumbnailer: web::Data<Arc<squire::thumbnail::Thumbnailer>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return routes::auth::failed_auth(auth_response, &config);
+    }
+    if !squire::authenticator::verify_access(&PathBuf::from(&info.file), &auth_response.username, &config) {
+        return HttpResponse::Forbidden().json(routes::auth::DetailError {
+            detail: format!("'{}' is not accessible to the user profile '{}'", info.file, auth_response.username)
+        });
+    }
+    let filepath = config.media_source.join(&info.file);
+    let (preview, content_type) = match info.sheet {
+        Some(sheet) => (thumbnailer.sprite_sheet(&filepath, &info.file, sheet), "image/jpeg"),
+        None => (thumbnailer.preview(&filepath, &info.file), "text/vtt")
+    };
+    let preview = match preview {
+        Some(preview) => preview,
+        None => return HttpResponse::NotFound().json(routes::auth::DetailError {
+            detail: format!("Preview for '{}' was not found", info.file)
+        })
+    };
+    match actix_files::NamedFile::open_async(&preview).await {
+        Ok(file) => {
+            let mut response = file.set_content_type(content_type.parse().unwrap()).into_response(&request);
+            response.headers_mut().insert(
+                actix_web::http::header::CACHE_CONTROL,
+                actix_web::http::header::HeaderValue::from_static("private, max-age=86400"),
+            );
+            response
+        }
+        Err(err) => {
+            log::error!("Failed to read {:?}: {}", preview, err);
+            HttpResponse::NotFound().json(routes::auth::DetailError {
+                detail: format!("Preview for '{}' was not found", info.file)
+            })
+        }
+    }
+}
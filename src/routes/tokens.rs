@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use actix_web::{get, HttpRequest, HttpResponse, post, web};
+use fernet::Fernet;
+use serde::{Deserialize, Serialize};
+
+use crate::{constant, routes, squire};
+
+/// Represents the payload structure to create an API token.
+#[derive(Deserialize)]
+pub struct TokenPayload {
+    name: String,
+    scope: Option<squire::settings::Role>,
+    expiry: Option<i64>,
+}
+
+/// Represents the payload structure to revoke an API token.
+#[derive(Deserialize)]
+pub struct RevokePayload {
+    id: String,
+}
+
+/// Struct for representing a newly created API token in JSON format.
+#[derive(Serialize)]
+struct TokenResponse {
+    token: String,
+    #[serde(flatten)]
+    info: squire::tokens::ApiToken,
+}
+
+/// Handles requests for the `POST /tokens` endpoint, to create an API token for the user.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `payload` - JSON payload with the `name`, `scope` and `expiry` in seconds.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+///
+/// ## See Also
+///
+/// - API tokens can only be created with a `session_token` cookie, so a token cannot create more tokens.
+/// - The token is returned only once, since only its digest is stored in the server.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the API token.
+/// * `400` - HttpResponse with an error message for an invalid name or expiry.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message if the scope exceeds the user's role.
+#[post("/tokens")]
+pub async fn create(request: HttpRequest,
+                    payload: web::Json<TokenPayload>,
+                    fernet: web::Data<Arc<Fernet>>,
+                    session: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return routes::auth::failed_auth(auth_response, &config);
+    }
+    if auth_response.scope.is_some() {
+        return HttpResponse::Forbidden().json(routes::auth::DetailError {
+            detail: "API tokens cannot be created with an API token".to_string()
+        });
+    }
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return HttpResponse::BadRequest().json(routes::auth::DetailError {
+            detail: "Name of the API token cannot be empty".to_string()
+        });
+    }
+    if payload.expiry.is_some_and(|expiry| expiry <= 0) {
+        return HttpResponse::BadRequest().json(routes::auth::DetailError {
+            detail: "Expiry must be a positive number of seconds".to_string()
+        });
+    }
+    let role = config.role(&auth_response.username);
+    let scope = payload.scope.unwrap_or(squire::settings::Role::Viewer);
+    if scope > role {
+        return HttpResponse::Forbidden().json(routes::auth::DetailError {
+            detail: format!("Scope '{}' exceeds the role '{}'", scope.as_str(), role.as_str())
+        });
+    }
+    let (token, info) = session.tokens.create(&auth_response.username, name, scope, payload.expiry);
+    log::info!("'{}' created the API token '{}' with the scope '{}'", auth_response.username, name, scope.as_str());
+    HttpResponse::Ok().json(TokenResponse { token, info })
+}
+
+/// Handles requests for the `GET /tokens` endpoint, to list the API tokens created by the user.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the list of API tokens, without their secrets.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message if the request was made with an API token.
+#[get("/tokens")]
+pub async fn list(request: HttpRequest,
+                  fernet: web::Data<Arc<Fernet>>,
+                  session: web::Data<Arc<constant::Session>>,
+                  config: web::Data<Arc<squire::settings::Config>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return routes::auth::failed_auth(auth_response, &config);
+    }
+    if auth_response.scope.is_some() {
+        return HttpResponse::Forbidden().json(routes::auth::DetailError {
+            detail: "API tokens cannot be listed with an API token".to_string()
+        });
+    }
+    HttpResponse::Ok().json(session.tokens.list(&auth_response.username))
+}
+
+/// Handles requests for the `POST /tokens/revoke` endpoint, to revoke an API token created by the user.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `payload` - JSON payload with the `id` of the API token.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+///
+/// # Returns
+///
+/// * `200` - Blank HttpResponse to indicate that the API token was revoked.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message if the request was made with an API token.
+/// * `404` - HttpResponse with an error message if the API token doesn't exist for the user.
+#[post("/tokens/revoke")]
+pub async fn revoke(request: HttpRequest,
+                    payload: web::Json<RevokePayload>,
+                    fernet: web::Data<Arc<Fernet>>,
+                    session: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
+    if !auth_response.ok {
+        return routes::auth::failed_auth(auth_response, &config);
+    }
+    if auth_response.scope.is_some() {
+        return HttpResponse::Forbidden().json(routes::auth::DetailError {
+            detail: "API tokens cannot be revoked with an API token".to_string()
+        });
+    }
+    if session.tokens.revoke(&payload.id, &auth_response.username) {
+        log::info!("'{}' revoked the API token '{}'", auth_response.username, payload.id);
+        return HttpResponse::Ok().finish();
+    }
+    HttpResponse::NotFound().json(routes::auth::DetailError {
+        detail: format!("API token '{}' was not found", payload.id)
+    })
+}
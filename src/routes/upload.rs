@@ -3,7 +3,7 @@ use std::io::Write;
 use std::sync::Arc;
 
 use actix_multipart::Multipart;
-use actix_web::{http, HttpRequest, HttpResponse, web};
+use actix_web::{get, http, HttpRequest, HttpResponse, post, web};
 use fernet::Fernet;
 use futures_util::StreamExt as _;
 
@@ -18,6 +18,7 @@ use crate::{constant, routes, squire};
 /// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
 /// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
 /// * `config` - Configuration data for the application.
+/// * `library` - Library index that is updated with the uploaded files.
 ///
 /// ## See Also
 ///
@@ -33,6 +34,7 @@ use crate::{constant, routes, squire};
 /// # Returns
 ///
 /// * `200` - Plain HTTPResponse indicating that the file was uploaded.
+/// * `403` - HTTPResponse with JSON object indicating that the user is not permitted to upload.
 /// * `422` - HTTPResponse with JSON object indicating that the payload was incomplete.
 /// * `400` - HTTPResponse with JSON object indicating that the payload was invalid.
 #[post("/upload")]
@@ -40,11 +42,17 @@ pub async fn save_files(request: HttpRequest,
                         mut payload: Multipart,
                         fernet: web::Data<Arc<Fernet>>,
                         session: web::Data<Arc<constant::Session>>,
-                        config: web::Data<Arc<squire::settings::Config>>) -> HttpResponse {
+                        config: web::Data<Arc<squire::settings::Config>>,
+                        library: web::Data<Arc<squire::index::Library>>) -> HttpResponse {
     let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
     if !auth_response.ok {
         return routes::auth::failed_auth(auth_response, &config);
     }
+    if squire::authenticator::role(&auth_response, &config) < squire::settings::Role::Uploader {
+        let error = format!("'{}' is not permitted to upload files", &auth_response.username);
+        log::warn!("{}", &error);
+        return HttpResponse::Forbidden().json(error);
+    }
     let mut upload_path = config.media_source.clone();  // cannot be borrowed as mutable
     let mut secure_str = "";
     if let Some(secure_flag) = request.headers().get("secure-flag") {
@@ -71,7 +79,8 @@ pub async fn save_files(request: HttpRequest,
                         return HttpResponse::BadRequest().json(error);
                     }
                 };
-                let mut destination = File::create(&upload_path.join(filename)).unwrap();
+                let destination_path = upload_path.join(filename);
+                let mut destination = File::create(&destination_path).unwrap();
                 log::info!("Downloading '{}' {}- uploaded by '{}'", &filename, secure_str, &auth_response.username);
                 while let Some(fragment) = field.next().await {
                     match fragment {
@@ -86,6 +95,7 @@ pub async fn save_files(request: HttpRequest,
                         }
                     }
                 }
+                library.sync(&destination_path);
             }
             Err(err) => {
                 let error = format!("Error processing field: {}", err);
@@ -122,6 +132,15 @@ pub async fn upload_files(request: HttpRequest,
     if !auth_response.ok {
         return routes::auth::failed_auth(auth_response, &config);
     }
+    if squire::authenticator::role(&auth_response, &config) < squire::settings::Role::Uploader {
+        return squire::custom::error(
+            "RESTRICTED SECTION",
+            template.get_template("error").unwrap(),
+            &metadata.pkg_version,
+            format!("Uploading files is not permitted for the user profile '{}'", auth_response.username),
+            http::StatusCode::FORBIDDEN
+        );
+    }
     let landing = template.get_template("upload").unwrap();
     HttpResponse::build(http::StatusCode::OK)
         .content_type("text/html; charset=utf-8")
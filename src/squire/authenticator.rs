@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Component, Path};
 use std::sync::Arc;
 
 use actix_web::{HttpRequest, web};
@@ -12,11 +12,15 @@ use crate::squire;
 
 /// Represents user credentials extracted from an authorization header.
 ///
-/// Contains the username, signature, and timestamp obtained by decoding and parsing the authorization header.
+/// Contains the username, signature, timestamp and nonce obtained by decoding and parsing the authorization header.
+///
+/// The signature is the client proof, that binds the password to the timestamp and nonce of the login request,
+/// which is verified against the server key stored in the configuration.
 struct Credentials {
     username: String,
     signature: String,
     timestamp: String,
+    nonce: String,
 }
 
 /// Represents the result of authentication, indicating whether it was successful or not.
@@ -26,7 +30,9 @@ pub struct AuthToken {
     pub ok: bool,
     pub detail: String,
     pub username: String,
-    pub time_left: i64
+    pub session_id: String,
+    pub time_left: i64,
+    pub scope: Option<squire::settings::Role>
 }
 
 
@@ -38,7 +44,7 @@ pub struct AuthToken {
 ///
 /// # See Also
 /// - Decodes the base64 encoded header
-/// - Splits it into 3 parts with first one being the username followed by the signature and timestamp
+/// - Splits it into 4 parts with first one being the username followed by the signature, timestamp and nonce
 /// - Converts the username from hex into a string.
 ///
 /// # Returns
@@ -48,24 +54,152 @@ fn extract_credentials(authorization: &HeaderValue) -> Result<Credentials, &'sta
     let header = authorization.to_str().unwrap().to_string();
     // base64 encoded in JavaScript using inbuilt btoa function
     let b64_decode_response = squire::secure::base64_decode(&header);
-    return match b64_decode_response {
+    match b64_decode_response {
         Ok(decoded_auth) => {
             if decoded_auth.is_empty() {
                 log::warn!("Authorization header was received without a value");
                 return Err("No credentials received");
             }
             let vector: Vec<&str> = decoded_auth.split(',').collect();
+            if vector.len() != 4 {
+                log::warn!("Authorization header was received with {} parts", vector.len());
+                return Err("Invalid credentials format");
+            }
             Ok(Credentials {
                 // Decode hex username into string to retrieve password from config file
-                username: squire::secure::hex_decode(vector.first().unwrap()),
-                signature: vector.get(1).unwrap().to_string(),
-                timestamp: vector.get(2).unwrap().to_string(),
+                username: squire::secure::hex_decode(vector[0]),
+                signature: vector[1].to_string(),
+                timestamp: vector[2].to_string(),
+                nonce: vector[3].to_string(),
             })
         }
         Err(err) => {
             Err(err)
         }
+    }
+}
+
+/// Extracts the username from the authorization header of a login request, without verifying it.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+///
+/// # Returns
+///
+/// Returns the username, or an empty string if it couldn't be extracted.
+pub fn login_username(request: &HttpRequest) -> String {
+    request.headers().get("authorization")
+        .and_then(|authorization| extract_credentials(authorization).ok())
+        .map(|credentials| credentials.username)
+        .unwrap_or_default()
+}
+
+/// Maximum number of outstanding challenges, so the unauthenticated endpoint cannot exhaust the memory.
+const MAX_CHALLENGES: usize = 10_000;
+
+/// Maximum number of outstanding challenges per client, within the login window.
+const MAX_CHALLENGES_PER_PEER: usize = 10;
+
+/// Issues a one-time nonce, that has to be signed in the authorization header of the login request.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+/// * `session` - Session struct that holds the `challenges` issued by the server.
+/// * `peer` - IP address of the client requesting the challenge.
+///
+/// ## See Also
+///
+/// - Challenges that were never used are removed once the login window elapses.
+/// - Each client can hold only a few outstanding challenges, which also rate limits the endpoint.
+///
+/// # Returns
+///
+/// Returns a `Result` with the generated nonce, or an error message if the limits have been reached.
+pub fn issue_challenge(config: &squire::settings::Config,
+                       session: &constant::Session,
+                       peer: &str) -> Result<String, &'static str> {
+    let current_time = Utc::now().timestamp();
+    let mut challenges = session.challenges.lock().unwrap();
+    challenges.retain(|_, challenge| current_time - challenge.issued <= config.login_window);
+    if challenges.values().filter(|challenge| challenge.peer == peer).count() >= MAX_CHALLENGES_PER_PEER {
+        log::warn!("{} has requested too many login challenges", peer);
+        return Err("Too many login requests, please try again later");
+    }
+    if challenges.len() >= MAX_CHALLENGES {
+        log::error!("Maximum number of outstanding login challenges [{}] has been reached", MAX_CHALLENGES);
+        return Err("Server is busy, please try again later");
+    }
+    let nonce = squire::secure::keygen();
+    challenges.insert(nonce.to_string(), constant::Challenge {
+        issued: current_time,
+        peer: peer.to_string(),
+    });
+    Ok(nonce)
+}
+
+/// Gets the salt and the number of iterations, that the login page needs to derive the salted password.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+/// * `username` - Username received in the challenge request.
+///
+/// ## See Also
+///
+/// - Unknown usernames get a decoy salt, so the response does not reveal whether the username exists.
+///
+/// # Returns
+///
+/// Returns a tuple of the hex encoded salt and the number of PBKDF2 iterations.
+pub fn scram_params(config: &squire::settings::Config, username: &str) -> (String, u32) {
+    match config.authorization.get(username).and_then(|stored| squire::secure::parse_hash(stored)) {
+        Some(server_key) => (server_key.salt, server_key.iterations),
+        None => (squire::secure::decoy_salt(&config.secret_key, username), squire::secure::SCRAM_ITERATIONS)
+    }
+}
+
+/// Verifies that the login request is fresh and that it has not been used before.
+///
+/// # Arguments
+///
+/// * `credentials` - Credentials extracted from the authorization header.
+/// * `config` - Configuration data for the application.
+/// * `session` - Session struct that holds the `challenges` issued by the server.
+///
+/// ## See Also
+///
+/// - The timestamp should be within the login window, since it is also used to compute the session expiry.
+/// - The nonce is removed as soon as it is received, so a captured authorization header cannot be replayed.
+/// - Both the timestamp and the nonce are part of the message signed by the client proof, so they cannot be swapped.
+///
+/// # Returns
+///
+/// Returns a `Result` with an error message if the request is stale or replayed.
+fn verify_freshness(credentials: &Credentials,
+                    config: &squire::settings::Config,
+                    session: &constant::Session) -> Result<(), &'static str> {
+    let current_time = Utc::now().timestamp();
+    let timestamp = match credentials.timestamp.parse::<i64>() {
+        Ok(timestamp) => timestamp,
+        Err(_) => return Err("Invalid login timestamp"),
     };
+    if (current_time - timestamp).abs() > config.login_window {
+        log::warn!("{} sent a login request with a stale timestamp [{}]", credentials.username, timestamp);
+        return Err("Login request has expired, please try again");
+    }
+    match session.challenges.lock().unwrap().remove(&credentials.nonce) {
+        Some(challenge) if current_time - challenge.issued <= config.login_window => Ok(()),
+        Some(_) => {
+            log::warn!("{} sent a login request with an expired challenge", credentials.username);
+            Err("Login challenge has expired, please try again")
+        }
+        None => {
+            log::warn!("{} sent a login request with an unknown or reused challenge", credentials.username);
+            Err("Login challenge is invalid or has already been used")
+        }
+    }
 }
 
 /// Verifies user login based on extracted credentials and configuration settings.
@@ -90,19 +224,37 @@ pub fn verify_login(
         let extracted_credentials = extract_credentials(authorization);
         match extracted_credentials {
             Ok(credentials) => {
+                if let Err(err) = verify_freshness(&credentials, config, session) {
+                    return Err(err.to_string());
+                }
                 // Check if the username is present in HashMap as key
-                if let Some(password) = config.authorization.get(&credentials.username) {
-                    let message = format!("{}{}{}",
-                                          squire::secure::hex_encode(&credentials.username),
-                                          squire::secure::hex_encode(password),
-                                          credentials.timestamp);
-                    // Create a new signature with hex encoded username and password stored in config file as plain text
-                    let expected_signature = squire::secure::calculate_hash(message);
-                    if expected_signature == credentials.signature {
+                if let Some(stored) = config.authorization.get(&credentials.username) {
+                    // Signature is a proof over the timestamp and nonce, so neither the password nor a reusable digest is sent
+                    let auth_message = format!("{},{},{}", squire::secure::hex_encode(&credentials.username),
+                                               credentials.timestamp, credentials.nonce);
+                    if squire::secure::verify_password(&credentials.username, stored,
+                                                       &credentials.signature, &auth_message) {
                         let key = squire::secure::keygen();
-                        session.mapping.lock().unwrap().insert(credentials.username.to_string(), key.to_string());
+                        let session_id = squire::secure::keygen();
+                        let entry = constant::SessionEntry {
+                            username: credentials.username.to_string(),
+                            key: key.to_string(),
+                            created: Utc::now().timestamp(),
+                            user_agent: request.headers().get("user-agent")
+                                .and_then(|agent| agent.to_str().ok())
+                                .unwrap_or_default().to_string(),
+                            ip: squire::custom::client_ip(request, config),
+                        };
+                        let mut mapping = session.mapping.lock().unwrap();
+                        // Remove the expired sessions, since each login creates a new entry
+                        let expiry = Utc::now().timestamp() - config.session_duration;
+                        mapping.retain(|_, stored| stored.created > expiry);
+                        mapping.insert(session_id.to_string(), entry);
+                        drop(mapping);
+                        session.persist();
                         let mut mapped = HashMap::new();
                         mapped.insert("username", credentials.username.to_string());
+                        mapped.insert("session_id", session_id.to_string());
                         mapped.insert("key", key.to_string());
                         mapped.insert("timestamp", credentials.timestamp.to_string());
                         return Ok(mapped);
@@ -126,6 +278,50 @@ pub fn verify_login(
     Err(err_response.to_string())
 }
 
+/// Verifies an API token received as a bearer token in the `Authorization` header.
+///
+/// # Arguments
+///
+/// * `token` - API token extracted from the `Authorization` header.
+/// * `config` - Configuration data for the application.
+/// * `session` - Session struct that holds the `tokens` created by the users.
+///
+/// # Returns
+///
+/// Returns an instance of the `AuthToken` struct indicating the result of the token verification.
+fn verify_bearer(token: &str,
+                 config: &squire::settings::Config,
+                 session: &constant::Session) -> AuthToken {
+    match session.tokens.verify(token) {
+        // Tokens of the users that were removed from the configuration are not honored
+        Ok(api_token) if config.authorization.contains_key(&api_token.username) => {
+            let time_left = api_token.expiry
+                .map(|expiry| expiry - Utc::now().timestamp())
+                .unwrap_or(i64::MAX);
+            AuthToken {
+                ok: true,
+                detail: format!("API token '{}' is valid", api_token.name),
+                username: api_token.username,
+                session_id: String::new(),
+                time_left,
+                scope: Some(api_token.scope)
+            }
+        }
+        result => {
+            let detail = result.err().unwrap_or("Invalid API token");
+            log::warn!("{}", detail);
+            AuthToken {
+                ok: false,
+                detail: detail.to_string(),
+                username: "NA".to_string(),
+                session_id: String::new(),
+                time_left: 0,
+                scope: None
+            }
+        }
+    }
+}
+
 /// Verifies a session token extracted from an HTTP request against stored session mappings and configuration.
 ///
 /// # Arguments
@@ -135,6 +331,10 @@ pub fn verify_login(
 /// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
 /// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
 ///
+/// ## See Also
+///
+/// - API tokens received as `Authorization: Bearer <token>` take precedence over the `session_token` cookie.
+///
 /// # Returns
 ///
 /// Returns an instance of the `AuthToken` struct indicating the result of the token verification.
@@ -144,38 +344,53 @@ pub fn verify_token(
     fernet: &Fernet,
     session: &constant::Session,
 ) -> AuthToken {
+    if let Some(token) = request.headers().get("authorization")
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| header.strip_prefix("Bearer ")) {
+        return verify_bearer(token.trim(), config, session);
+    }
     if session.mapping.lock().unwrap().is_empty() {
         log::warn!("No stored sessions, no point in validating further");
         return AuthToken {
             ok: false,
             detail: "Server doesn't recognize your session".to_string(),
             username: "NA".to_string(),
-            time_left: 0
+            session_id: String::new(),
+            time_left: 0,
+            scope: None
         };
     }
     if let Some(cookie) = request.cookie("session_token") {
         if let Ok(decrypted) = fernet.decrypt(cookie.value()) {
             let payload: HashMap<String, String> = serde_json::from_str(&String::from_utf8_lossy(&decrypted)).unwrap();
             let username = payload.get("username").unwrap().to_string();
+            let session_id = payload.get("session_id").cloned().unwrap_or_default();
             let cookie_key = payload.get("key").unwrap().to_string();
             let timestamp = payload.get("timestamp").unwrap().parse::<i64>().unwrap();
-            let stored_key = session.mapping.lock().unwrap().get(&username).unwrap().to_string();
+            let stored = session.mapping.lock().unwrap().get(&session_id).cloned();
             let current_time = Utc::now().timestamp();
             // Max time and expiry for session token is set in the Cookie, but this is a fallback mechanism
-            if stored_key != *cookie_key {
-                return AuthToken {
-                    ok: false,
-                    detail: "Invalid session token".to_string(),
-                    username,
-                    time_left: 0
-                };
+            match stored {
+                Some(entry) if entry.username == username && entry.key == cookie_key => {}
+                _ => {
+                    return AuthToken {
+                        ok: false,
+                        detail: "Invalid session token".to_string(),
+                        username,
+                        session_id: String::new(),
+                        time_left: 0,
+                        scope: None
+                    };
+                }
             }
             if current_time - timestamp > config.session_duration {
                 return AuthToken {
                     ok: false,
                     detail: "Session Expired".to_string(),
                     username,
-                    time_left: 0
+                    session_id,
+                    time_left: 0,
+                    scope: None
                 };
             }
             let time_left = timestamp + config.session_duration - current_time;
@@ -183,14 +398,18 @@ pub fn verify_token(
                 ok: true,
                 detail: format!("Session valid for {}s", time_left),
                 username,
-                time_left
+                session_id,
+                time_left,
+                scope: None
             }
         } else {
             AuthToken {
                 ok: false,
                 detail: "Invalid session token".to_string(),
                 username: "NA".to_string(),
-                time_left: 0
+                session_id: String::new(),
+                time_left: 0,
+                scope: None
             }
         }
     } else {
@@ -198,11 +417,87 @@ pub fn verify_token(
             ok: false,
             detail: "Session information not found".to_string(),
             username: "NA".to_string(),
-            time_left: 0
+            session_id: String::new(),
+            time_left: 0,
+            scope: None
         }
     }
 }
 
+/// Returns the effective role of the request, which is the user's role capped by the API token's scope.
+///
+/// # Arguments
+///
+/// * `auth_response` - Authentication response of the current request.
+/// * `config` - Configuration data for the application.
+///
+/// # Returns
+///
+/// Returns the `Role` that the request is allowed to act as.
+pub fn role(auth_response: &AuthToken, config: &squire::settings::Config) -> squire::settings::Role {
+    let role = config.role(&auth_response.username);
+    match auth_response.scope {
+        Some(scope) => role.min(scope),
+        None => role
+    }
+}
+
+/// Revokes the session that was used to make the request, leaving the other devices signed in.
+///
+/// # Arguments
+///
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `session_id` - Session ID of the current device.
+pub fn revoke_session(session: &constant::Session, session_id: &str) {
+    if session.mapping.lock().unwrap().remove(session_id).is_some() {
+        session.persist();
+    } else {
+        log::warn!("Session ID was not found in the stored sessions");
+    }
+}
+
+/// Lists the active sessions of a user.
+///
+/// # Arguments
+///
+/// * `session` - Session struct that holds the `session_mapping` and `session_tracker` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `auth_response` - Authentication response of the current session.
+///
+/// # Returns
+///
+/// Returns a vector of `HashMap` with the device information for each active session, marking the current one.
+pub fn list_sessions(session: &constant::Session,
+                     config: &squire::settings::Config,
+                     auth_response: &AuthToken) -> Vec<HashMap<&'static str, String>> {
+    let expiry = Utc::now().timestamp() - config.session_duration;
+    let mapping = session.mapping.lock().unwrap();
+    let mut sessions: Vec<&constant::SessionEntry> = Vec::new();
+    let mut current = None;
+    for (session_id, entry) in mapping.iter() {
+        if entry.username != auth_response.username || entry.created <= expiry {
+            continue;
+        }
+        if *session_id == auth_response.session_id {
+            current = Some(entry);
+        } else {
+            sessions.push(entry);
+        }
+    }
+    sessions.sort_by_key(|entry| -entry.created);
+    current.into_iter().chain(sessions).enumerate().map(|(idx, entry)| {
+        let created = chrono::DateTime::from_timestamp(entry.created, 0)
+            .map(|datetime| datetime.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+            .unwrap_or_default();
+        HashMap::from([
+            ("created", created),
+            ("ip", entry.ip.to_string()),
+            ("user_agent", entry.user_agent.to_string()),
+            ("current", (idx == 0 && current.is_some()).to_string()),
+        ])
+    }).collect()
+}
+
 /// Verifies the secure index of the directory/file that's being accessed.
 ///
 /// # Arguments
@@ -237,3 +532,76 @@ pub fn verify_secure_index(path: &Path, username: &String) -> bool {
     }
     true
 }
+
+/// Checks if the path is within the secure index of another user, without logging it as an access attempt.
+///
+/// # Arguments
+/// * `path` - A reference to the `Path` object, relative to `media_source`.
+/// * `username` - Username of the session.
+///
+/// ## See Also
+/// - Used to filter the listings and search results, where skipping the content of other users is expected.
+///
+/// # Returns
+///
+/// Returns a boolean value to indicate if the path belongs to the secure index of another user.
+pub fn within_foreign_index(path: &Path, username: &str) -> bool {
+    let own_index = format!("{}_{}", username, constant::SECURE_INDEX);
+    path.iter().any(|component| {
+        let component = component.to_string_lossy();
+        component.ends_with(constant::SECURE_INDEX) && component != own_index
+    })
+}
+
+/// Verifies the access control list of the directory/file that's being accessed.
+///
+/// # Arguments
+/// * `path` - A reference to the `Path` object that's being accessed, either absolute or relative to `media_source`.
+/// * `username` - Username of the session.
+/// * `config` - Configuration data for the application.
+///
+/// ## See Also
+/// - The most specific (longest) path in the `acl` that contains the requested path is applied.
+/// - Paths that are not covered by any entry in the `acl` are accessible to everyone.
+/// - Entries starting with `@` refer to a group of users.
+///
+/// # Returns
+///
+/// Returns a boolean value to indicate if the access can be granted.
+pub fn verify_acl(path: &Path, username: &str, config: &squire::settings::Config) -> bool {
+    let relative = path.strip_prefix(&config.media_source).unwrap_or(path);
+    let matched = config.acl.iter()
+        .filter(|(restricted, _)| relative.starts_with(restricted.trim_matches('/')))
+        .max_by_key(|(restricted, _)| Path::new(restricted.trim_matches('/')).iter().count());
+    if let Some((restricted, allowed)) = matched {
+        let granted = allowed.iter().any(|entry| match entry.strip_prefix('@') {
+            Some(group) => config.groups.get(group)
+                .is_some_and(|members| members.iter().any(|member| member == username)),
+            None => entry == username,
+        });
+        if !granted {
+            log::warn!("'{}' tried to access {:?} that is restricted by the ACL for '{}'", username, path, restricted);
+        }
+        return granted;
+    }
+    true
+}
+
+/// Verifies both the secure index and the access control list of the directory/file that's being accessed.
+///
+/// # Arguments
+/// * `path` - A reference to the `Path` object that's being accessed, relative to `media_source`.
+/// * `username` - Username of the session.
+/// * `config` - Configuration data for the application.
+///
+/// # Returns
+///
+/// Returns a boolean value to indicate if the access can be granted.
+pub fn verify_access(path: &Path, username: &String, config: &squire::settings::Config) -> bool {
+    // Absolute paths and parent directory references could be used to step out of a restricted path
+    if !path.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir)) {
+        log::warn!("'{}' tried to access {:?} that is not relative to the media source", username, path);
+        return false;
+    }
+    verify_secure_index(path, username) && verify_acl(path, username, config)
+}
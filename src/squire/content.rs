@@ -1,14 +1,19 @@
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use walkdir::WalkDir;
+use url::form_urlencoded;
 
 use crate::constant;
 use crate::squire::authenticator;
+use crate::squire::index;
+use crate::squire::probe;
+use crate::squire::progress;
 use crate::squire::settings;
+use crate::squire::subtitles;
 
 /// Represents the payload structure for content, including files and directories.
 ///
@@ -16,7 +21,7 @@ use crate::squire::settings;
 /// when necessary.
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct ContentPayload {
-    /// List of files with their names, paths and font icons.
+    /// List of files with their names, paths, font icons and thumbnails.
     #[serde(default = "default_structure")]
     pub files: Vec<HashMap<String, String>>,
     /// List of directories with their names, paths and font icons.
@@ -25,6 +30,101 @@ pub struct ContentPayload {
     /// List of user specific directories with their names, paths and font icons.
     #[serde(default = "default_structure")]
     pub secured_directories: Vec<HashMap<String, String>>,
+    /// Page of the files that is listed.
+    #[serde(default)]
+    pub pagination: Pagination,
+}
+
+/// Default number of files in a page of the listing, when the limit is not specified.
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Maximum number of files in a page of the listing.
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// Represents the attributes by which the files in a listing can be sorted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    /// Natural sort order of the filenames.
+    #[default]
+    Name,
+    /// Last modified time of the files.
+    Mtime,
+    /// Size of the files.
+    Size,
+    /// Duration of the media, files that are not probed yet are considered to have no duration.
+    Duration,
+}
+
+impl SortBy {
+    /// Returns the sort attribute as a lowercase string, to be used in the query parameters.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortBy::Name => "name",
+            SortBy::Mtime => "mtime",
+            SortBy::Size => "size",
+            SortBy::Duration => "duration",
+        }
+    }
+}
+
+/// Represents the order in which the files in a listing are sorted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// Ascending order.
+    #[default]
+    Asc,
+    /// Descending order.
+    Desc,
+}
+
+impl SortOrder {
+    /// Returns the sort order as a lowercase string, to be used in the query parameters.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+}
+
+/// Represents the query parameters to sort and paginate the listings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListingQuery {
+    /// Attribute by which the files are sorted.
+    #[serde(default)]
+    pub sort: SortBy,
+    /// Order in which the files are sorted.
+    #[serde(default)]
+    pub order: SortOrder,
+    /// Page number starting from 1.
+    pub page: Option<usize>,
+    /// Number of files in a page.
+    pub limit: Option<usize>,
+}
+
+impl ListingQuery {
+    /// Returns the query string to carry the sort order into the links, empty for the default order.
+    pub fn sort_query(&self) -> String {
+        if self.sort == SortBy::default() && self.order == SortOrder::default() {
+            return String::new();
+        }
+        format!("?sort={}&order={}", self.sort.as_str(), self.order.as_str())
+    }
+}
+
+/// Represents a page of the files in a listing.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Pagination {
+    /// Current page number starting from 1.
+    pub page: usize,
+    /// Total number of pages.
+    pub pages: usize,
+    /// Number of files in a page.
+    pub limit: usize,
+    /// Total number of files.
+    pub total: usize,
 }
 
 /// Returns the default structure for content, represented as an empty vector of HashMaps.
@@ -48,12 +148,12 @@ pub fn default_structure() -> Vec<HashMap<String, String>> {
 ///
 /// A vector of `Result<i32, String>` where each element is either an integer representing a numeric part
 /// or a string representing a non-numeric part converted to lowercase.
-fn natural_sort_key(regex: &Regex, filename: &str) -> Vec<Result<i32, String>> {
+pub fn natural_sort_key(regex: &Regex, filename: &str) -> Vec<Result<i32, String>> {
     // reusing regex is way faster than creating a new object everytime (~8s
     regex.find_iter(filename)
         .map(|part| {
             // chaining methods is kinda faster (~79% faster in terms of ms)
-            part.as_str().parse::<i32>().map_err(|e| e.to_string())
+            part.as_str().parse::<i32>().map_err(|_| part.as_str().to_lowercase())
             // if let Ok(num) = part.as_str().parse::<i32>() {
             //     Ok(num)
             // } else {
@@ -84,6 +184,137 @@ pub fn get_file_font(extn: &str) -> String {
     font.to_string()
 }
 
+/// Adds the duration and resolution of a video to its entry in the listing, if the metadata is cached.
+///
+/// # Arguments
+///
+/// * `entry_map` - Entry of the file in the listing.
+/// * `prober` - Prober struct that holds the cached metadata.
+/// * `filepath` - True path of the media file.
+/// * `relative_path` - Path of the media file, relative to `media_source`.
+/// * `pending` - Files that are not probed yet, to be probed in the background.
+fn add_media_info(entry_map: &mut HashMap<String, String>,
+                  prober: &probe::Prober,
+                  filepath: &Path,
+                  relative_path: &str,
+                  pending: &mut Vec<(PathBuf, String)>) {
+    let extension = filepath.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+    if !prober.enabled || constant::IMAGE_FORMATS.contains(&extension.as_str()) {
+        return;
+    }
+    match prober.cached(filepath, relative_path) {
+        Some(info) => {
+            entry_map.insert("duration".to_string(), info.duration_str());
+            entry_map.insert("resolution".to_string(), info.resolution());
+        }
+        None => pending.push((filepath.to_path_buf(), relative_path.to_string()))
+    }
+}
+
+/// Sorts the indexed files by the attribute and the order requested.
+///
+/// # Arguments
+///
+/// * `entries` - Indexed files to be sorted.
+/// * `query` - Query parameters with the sort attribute and order.
+/// * `config` - Configuration data for the application.
+/// * `prober` - Prober struct that holds the cached metadata, to sort by the duration.
+fn sort_entries(entries: &mut [index::IndexEntry],
+                query: &ListingQuery,
+                config: &settings::Config,
+                prober: &probe::Prober) {
+    sort_by(entries, query, |entry| prober.cached(&config.media_source.join(&entry.path), &entry.path)
+        .map(|info| (info.duration * 1000.0) as i64)
+        .unwrap_or_default());
+}
+
+/// Sorts the indexed files by the attribute and the order requested, with the duration looked up by the caller.
+///
+/// # Arguments
+///
+/// * `entries` - Indexed files to be sorted.
+/// * `query` - Query parameters with the sort attribute and order.
+/// * `duration` - Closure that returns the duration of a file in milliseconds, `0` when it is unknown.
+///
+/// ## See Also
+///
+/// - Files with the same value are kept in the natural sort order of the filenames.
+fn sort_by(entries: &mut [index::IndexEntry],
+           query: &ListingQuery,
+           duration: impl Fn(&index::IndexEntry) -> i64) {
+    let re = Regex::new(r"(\D+|\d+)").unwrap();
+    entries.sort_by_cached_key(|entry| natural_sort_key(&re, &entry.name));
+    let key = |entry: &index::IndexEntry| -> i64 {
+        match query.sort {
+            SortBy::Name => 0,
+            SortBy::Mtime => entry.modified,
+            SortBy::Size => entry.size as i64,
+            SortBy::Duration => duration(entry)
+        }
+    };
+    match (query.sort, query.order) {
+        (SortBy::Name, SortOrder::Asc) => {}
+        (SortBy::Name, SortOrder::Desc) => entries.reverse(),
+        (_, SortOrder::Asc) => entries.sort_by_cached_key(key),
+        (_, SortOrder::Desc) => entries.sort_by_cached_key(|entry| std::cmp::Reverse(key(entry)))
+    }
+}
+
+/// Sorts the indexed files and slices the page requested.
+///
+/// # Arguments
+///
+/// * `entries` - Indexed files to be listed.
+/// * `query` - Query parameters with the sort order, page and limit.
+/// * `config` - Configuration data for the application.
+/// * `prober` - Prober struct that holds the cached metadata, to sort by the duration.
+///
+/// # Returns
+///
+/// Returns a tuple of the indexed files in the page and the `Pagination`.
+fn paginate(mut entries: Vec<index::IndexEntry>,
+            query: &ListingQuery,
+            config: &settings::Config,
+            prober: &probe::Prober) -> (Vec<index::IndexEntry>, Pagination) {
+    sort_entries(&mut entries, query, config, prober);
+    page(entries, query)
+}
+
+/// Slices the page requested from the sorted files.
+///
+/// # Arguments
+///
+/// * `entries` - Sorted files to be listed.
+/// * `query` - Query parameters with the page and limit.
+///
+/// ## See Also
+///
+/// - The limit is clamped between `1` and `MAX_PAGE_SIZE`.
+/// - Pages beyond the last one return the last page.
+///
+/// # Returns
+///
+/// Returns a tuple of the files in the page and the `Pagination`.
+fn page(entries: Vec<index::IndexEntry>, query: &ListingQuery) -> (Vec<index::IndexEntry>, Pagination) {
+    let total = entries.len();
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let pages = total.div_ceil(limit).max(1);
+    let page = query.page.unwrap_or(1).clamp(1, pages);
+    let entries = entries.into_iter().skip((page - 1) * limit).take(limit).collect();
+    (entries, Pagination { page, pages, limit, total })
+}
+
+/// Adds the URL of the thumbnail to the entry of the file in the listing.
+///
+/// # Arguments
+///
+/// * `entry_map` - Entry of the file in the listing.
+/// * `relative_path` - Path of the media file, relative to `media_source`.
+fn add_thumbnail(entry_map: &mut HashMap<String, String>, relative_path: &str) {
+    let encoded = form_urlencoded::byte_serialize(relative_path.as_bytes()).collect::<String>();
+    entry_map.insert("thumbnail".to_string(), format!("/thumbnail?file={}", encoded));
+}
+
 /// Generate font awesome icon's value for a given folder depth.
 ///
 /// Creates custom icons for `folder-tree`, defaults to `folder` icon.
@@ -127,60 +358,57 @@ fn get_folder_font(structure: &Path,
 /// # Arguments
 ///
 /// * `config` - Configuration data for the application.
+/// * `auth_response` - Authentication response of the current session.
+/// * `prober` - Prober struct that holds the cached metadata of the media files.
+/// * `library` - Library index with the media files.
+/// * `query` - Query parameters to sort and paginate the files.
 ///
 /// # Returns
 ///
-/// A `ContentPayload` struct representing the content of all streams.
-pub fn get_all_stream_content(config: &settings::Config, auth_response: &authenticator::AuthToken) -> ContentPayload {
+/// A `ContentPayload` struct representing the content of all streams, that are accessible to the user.
+pub fn get_all_stream_content(config: &settings::Config,
+                              auth_response: &authenticator::AuthToken,
+                              prober: &Arc<probe::Prober>,
+                              library: &index::Library,
+                              query: &ListingQuery) -> ContentPayload {
     let mut payload = ContentPayload::default();
+    let mut files = Vec::new();
 
-    for entry in WalkDir::new(&config.media_source).into_iter().filter_map(|e| e.ok()) {
-        if entry.path().ends_with("__") {
+    for entry in library.files() {
+        let path = Path::new(&entry.path);
+        if !authenticator::verify_acl(path, &auth_response.username, config) {
             continue;
         }
-
-        if let Some(file_name) = entry.file_name().to_str() {
-            if file_name.starts_with('_') || file_name.starts_with('.') {
-                continue;
-            }
-
-            if let Some(extension) = PathBuf::from(file_name).extension().and_then(|ext| ext.to_str()) {
-                if config.file_formats.iter().any(|format| extension == format) {
-                    let path = entry.path().strip_prefix(&config.media_source)
-                        .unwrap_or_else(|_| Path::new(""));
-                    let components: &Vec<_> = &path.components().collect();
-                    if components.len() == 1 {
-                        let mut entry_map = HashMap::new();
-                        entry_map.insert("path".to_string(), format!("stream/{}", &file_name));
-                        entry_map.insert("name".to_string(), file_name.to_string());
-                        entry_map.insert("font".to_string(), get_file_font(extension));
-                        payload.files.push(entry_map);
-                    } else {
-                        /*
-                        path.components(): returns an iterator over the components of the path
-                        .rev(): reverses the order of the iterator
-                        .skip(1): skips the first (originally last) component of the reversed path
-                         */
-                        let skimmed = path.components().rev().skip(1)
-                            .collect::<Vec<_>>().iter().rev()
-                            .collect::<PathBuf>();
-                        let entry_map = get_folder_font(&skimmed, auth_response);
-                        if entry_map.get("secured").unwrap_or(&"".to_string()) == "true" {
-                            if payload.secured_directories.contains(&entry_map) || entry_map.is_empty() { continue; }
-                            payload.secured_directories.push(entry_map);
-                        } else {
-                            if payload.directories.contains(&entry_map) || entry_map.is_empty() { continue; }
-                            payload.directories.push(entry_map);
-                        }
-                    }
-                }
+        if entry.directory.is_empty() {
+            files.push(entry);
+        } else {
+            let entry_map = get_folder_font(Path::new(&entry.directory), auth_response);
+            if entry_map.get("secured").unwrap_or(&"".to_string()) == "true" {
+                if payload.secured_directories.contains(&entry_map) || entry_map.is_empty() { continue; }
+                payload.secured_directories.push(entry_map);
+            } else {
+                if payload.directories.contains(&entry_map) || entry_map.is_empty() { continue; }
+                payload.directories.push(entry_map);
             }
         }
     }
 
+    let (files, pagination) = paginate(files, query, config, prober);
+    let mut pending = Vec::new();
+    for entry in files {
+        let mut entry_map = HashMap::new();
+        entry_map.insert("path".to_string(), format!("stream/{}", &entry.name));
+        entry_map.insert("name".to_string(), entry.name.clone());
+        entry_map.insert("font".to_string(), get_file_font(&entry.extension));
+        add_media_info(&mut entry_map, prober, &config.media_source.join(&entry.path), &entry.name, &mut pending);
+        add_thumbnail(&mut entry_map, &entry.name);
+        payload.files.push(entry_map);
+    }
+    payload.pagination = pagination;
+
     let re = Regex::new(r"(\D+|\d+)").unwrap();
-    payload.files.sort_by(|a, b| natural_sort_key(&re, &a["name"]).cmp(&natural_sort_key(&re, &b["name"])));
     payload.directories.sort_by(|a, b| natural_sort_key(&re, &a["name"]).cmp(&natural_sort_key(&re, &b["name"])));
+    prober.schedule(pending);
 
     payload
 }
@@ -191,34 +419,143 @@ pub fn get_all_stream_content(config: &settings::Config, auth_response: &authent
 ///
 /// * `parent` - Path to the parent directory.
 /// * `child` - Path to the child directory.
-/// * `file_formats` - File formats (set as env vars) that are allowed for streaming.
+/// * `config` - Configuration data for the application.
+/// * `auth_response` - Authentication response of the current session.
+/// * `prober` - Prober struct that holds the cached metadata of the media files.
+/// * `library` - Library index with the media files.
+/// * `query` - Query parameters to sort and paginate the files.
 ///
 /// # Returns
 ///
 /// A `ContentPayload` struct representing the content of the specified directory.
 pub fn get_dir_stream_content(parent: &str,
                               child: &str,
-                              file_formats: &[String]) -> ContentPayload {
+                              config: &settings::Config,
+                              auth_response: &authenticator::AuthToken,
+                              prober: &Arc<probe::Prober>,
+                              library: &index::Library,
+                              query: &ListingQuery) -> ContentPayload {
+    let directory = Path::new(parent).strip_prefix(&config.media_source)
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let entries = library.directory(&directory)
+        .into_iter()
+        .filter(|entry| authenticator::verify_acl(&config.media_source.join(&entry.path), &auth_response.username, config))
+        .collect();
+    let (entries, pagination) = paginate(entries, query, config, prober);
     let mut files = Vec::new();
-    for entry in fs::read_dir(parent).unwrap().flatten() {
-        let file_name = entry.file_name().into_string().unwrap();
-        if file_name.starts_with('_') || file_name.starts_with('.') {
-            continue;
-        }
-        let file_path = Path::new(child).join(&file_name);
-        let file_extn = &file_path.extension().unwrap_or_default().to_string_lossy().to_string();
-        if file_formats.contains(file_extn) {
-            let map = HashMap::from([
-                ("name".to_string(), file_name),
-                ("path".to_string(), file_path.to_string_lossy().to_string()),
-                ("font".to_string(), get_file_font(file_extn))
-            ]);
-            files.push(map);
-        }
+    let mut pending = Vec::new();
+    for entry in entries {
+        let true_path = config.media_source.join(&entry.path);
+        let mut map = HashMap::from([
+            ("name".to_string(), entry.name.clone()),
+            ("path".to_string(), Path::new(child).join(&entry.name).to_string_lossy().to_string()),
+            ("font".to_string(), get_file_font(&entry.extension))
+        ]);
+        add_media_info(&mut map, prober, &true_path, &entry.path, &mut pending);
+        add_thumbnail(&mut map, &entry.path);
+        files.push(map);
     }
-    let re = Regex::new(r"(\D+|\d+)").unwrap();
-    files.sort_by_key(|a| natural_sort_key(&re, a.get("name").unwrap()));
-    ContentPayload { files, ..Default::default() }
+    prober.schedule(pending);
+    ContentPayload { files, pagination, ..Default::default() }
+}
+
+/// Formats the epoch time as a UTC timestamp for the feeds.
+fn format_time(epoch: i64) -> String {
+    chrono::DateTime::from_timestamp(epoch, 0)
+        .map(|datetime| datetime.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default()
+}
+
+/// Builds the entry of a file for the feeds and the search results.
+///
+/// # Arguments
+///
+/// * `relative_path` - Path of the media file, relative to `media_source`.
+/// * `extension` - File extension.
+/// * `modified` - Last modified time of the file as epoch.
+///
+/// # Returns
+///
+/// Returns a `HashMap` with the name, path, font icon, modified time and thumbnail of the file.
+pub fn file_entry(relative_path: &str, extension: &str, modified: i64) -> HashMap<String, String> {
+    let mut entry_map = HashMap::from([
+        ("name".to_string(), Path::new(relative_path).file_name().unwrap_or_default().to_string_lossy().to_string()),
+        ("path".to_string(), format!("stream/{}", relative_path)),
+        ("font".to_string(), get_file_font(extension)),
+        ("modified".to_string(), format_time(modified)),
+    ]);
+    add_thumbnail(&mut entry_map, relative_path);
+    entry_map
+}
+
+/// Retrieves the most recently added (or modified) files under `media_source`, that are accessible to the user.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+/// * `auth_response` - Authentication response of the current session.
+/// * `library` - Library index with the media files.
+/// * `limit` - Maximum number of files to return.
+///
+/// # Returns
+///
+/// A vector of the files with their names, paths, font icons, thumbnails and modified time, newest first.
+pub fn get_recently_added(config: &settings::Config,
+                          auth_response: &authenticator::AuthToken,
+                          library: &index::Library,
+                          limit: usize) -> Vec<HashMap<String, String>> {
+    let mut recent: Vec<index::IndexEntry> = library.files()
+        .into_iter()
+        .filter(|entry| {
+            let path = Path::new(&entry.path);
+            !authenticator::within_foreign_index(path, &auth_response.username)
+                && authenticator::verify_acl(path, &auth_response.username, config)
+        })
+        .collect();
+    recent.sort_by_key(|entry| std::cmp::Reverse(entry.modified));
+    recent.into_iter()
+        .take(limit)
+        .map(|entry| file_entry(&entry.path, &entry.extension, entry.modified))
+        .collect()
+}
+
+/// Retrieves the watch history of the user, skipping the files that were removed or are no longer accessible.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+/// * `auth_response` - Authentication response of the current session.
+/// * `progress` - Progress struct that stores the watch history of each user.
+/// * `limit` - Maximum number of entries to return.
+///
+/// # Returns
+///
+/// A vector of the entries with their names, paths, font icons, watched time and completion, most recent first.
+pub fn get_watch_history(config: &settings::Config,
+                         auth_response: &authenticator::AuthToken,
+                         progress: &progress::Progress,
+                         limit: usize) -> Vec<HashMap<String, String>> {
+    progress.history(&auth_response.username)
+        .into_iter()
+        .filter(|entry| {
+            config.media_source.join(&entry.path).is_file()
+                && authenticator::verify_access(Path::new(&entry.path), &auth_response.username, config)
+        })
+        .take(limit)
+        .map(|entry| {
+            let extension = Path::new(&entry.path).extension().unwrap_or_default().to_string_lossy().to_string();
+            let mut entry_map = HashMap::from([
+                ("name".to_string(), Path::new(&entry.path).file_name().unwrap_or_default().to_string_lossy().to_string()),
+                ("path".to_string(), format!("stream/{}", &entry.path)),
+                ("font".to_string(), get_file_font(&extension)),
+                ("watched".to_string(), format_time(entry.watched)),
+                ("completion".to_string(), format!("{:.0}", entry.completion)),
+            ]);
+            add_thumbnail(&mut entry_map, &entry.path);
+            entry_map
+        })
+        .collect()
 }
 
 /// Represents an iterator structure with optional previous and next elements.
@@ -235,43 +572,166 @@ pub struct Iter {
 /// # Arguments
 ///
 /// * `filepath` - File that is requested for streaming.
-/// * `file_formats` - Vector of file formats (as String) that are allowed.
+/// * `config` - Configuration data for the application.
+/// * `auth_response` - Authentication response of the current session.
+/// * `library` - Library index with the media files.
+/// * `prober` - Prober struct that holds the cached metadata, to follow the order by duration.
+/// * `query` - Query parameters with the sort order chosen in the listing.
+///
+/// ## See Also
+///
+/// - Files that are not accessible to the user are skipped, the same as in the listings.
 ///
 /// # Returns
 ///
 /// An `Iter` struct representing the iterator information.
-pub fn get_iter(filepath: &Path, file_formats: &[String]) -> Iter {
-    let parent = filepath.parent().unwrap();
-    let mut dir_content: Vec<String> = fs::read_dir(parent)
-        .ok().unwrap()
-        .flatten()
-        .filter_map(|entry| {
-            let file_name = entry.file_name().to_string_lossy().to_string();
-            let file_extn = Path::new(&file_name).extension().unwrap_or_default().to_string_lossy().to_string();
-            if file_formats.contains(&file_extn) {
-                Some(file_name)
-            } else {
-                None
-            }
+pub fn get_iter(filepath: &Path,
+                config: &settings::Config,
+                auth_response: &authenticator::AuthToken,
+                library: &index::Library,
+                prober: &probe::Prober,
+                query: &ListingQuery) -> Iter {
+    let file_name = filepath.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let directory = filepath.parent()
+        .and_then(|parent| parent.strip_prefix(&config.media_source).ok())
+        .map(|parent| parent.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut entries: Vec<index::IndexEntry> = library.directory(&directory)
+        .into_iter()
+        .filter(|entry| {
+            let path = Path::new(&entry.path);
+            !authenticator::within_foreign_index(path, &auth_response.username)
+                && authenticator::verify_acl(path, &auth_response.username, config)
         })
         .collect();
-    let re = Regex::new(r"(\D+|\d+)").unwrap();
-    dir_content.sort_by_key(|a| natural_sort_key(&re, a));
-
-    let idx = dir_content.iter().position(|file| file == filepath.file_name().unwrap().to_str().unwrap()).unwrap();
+    sort_entries(&mut entries, query, config, prober);
+    let dir_content: Vec<String> = entries.into_iter().map(|entry| entry.name).collect();
 
-    let previous_ = if idx > 0 {
-        let previous_ = &dir_content[idx - 1];
-        if previous_ == filepath.file_name().unwrap().to_str().unwrap() {
-            None
-        } else {
-            Some(previous_.clone())
-        }
-    } else {
-        None
+    let idx = match dir_content.iter().position(|file| *file == file_name) {
+        Some(idx) => idx,
+        None => return Iter::default()
     };
-
+    let previous_ = if idx > 0 { dir_content.get(idx - 1).cloned() } else { None };
     let next_ = dir_content.get(idx + 1).cloned();
 
     Iter { previous: previous_, next: next_ }
 }
+
+/// Represents the metadata of a media file.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct FileMetadata {
+    /// Name of the file.
+    pub name: String,
+    /// Path of the file, relative to `media_source`.
+    pub path: String,
+    /// Size of the file in bytes.
+    pub size: u64,
+    /// Last modified time of the file as epoch.
+    pub modified: i64,
+    /// Boolean flag to indicate if subtitles are available for the file.
+    pub subtitles: bool,
+}
+
+/// Retrieves the metadata of a media file.
+///
+/// # Arguments
+///
+/// * `filepath` - True path of the file.
+/// * `relative_path` - Path of the file, relative to `media_source`.
+///
+/// # Returns
+///
+/// Returns a `Result` with the `FileMetadata` or the IO error.
+pub fn get_file_metadata(filepath: &Path, relative_path: &str) -> std::io::Result<FileMetadata> {
+    let metadata = fs::metadata(filepath)?;
+    let modified = metadata.modified()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default();
+    let subtitles = subtitles::available(filepath);
+    Ok(FileMetadata {
+        name: filepath.file_name().unwrap_or_default().to_string_lossy().to_string(),
+        path: relative_path.to_string(),
+        size: metadata.len(),
+        modified,
+        subtitles,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, modified: i64, size: u64) -> index::IndexEntry {
+        index::IndexEntry {
+            name: name.to_string(),
+            path: name.to_string(),
+            directory: String::new(),
+            extension: "mp4".to_string(),
+            size,
+            modified,
+            tokens: Vec::new(),
+        }
+    }
+
+    fn names(entries: &[index::IndexEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.name.as_str()).collect()
+    }
+
+    fn query(sort: SortBy, order: SortOrder, page: Option<usize>, limit: Option<usize>) -> ListingQuery {
+        ListingQuery { sort, order, page, limit }
+    }
+
+    #[test]
+    fn sorts_by_natural_order() {
+        let mut entries = vec![entry("ep10.mp4", 0, 0), entry("ep2.mp4", 0, 0), entry("ep1.mp4", 0, 0)];
+        sort_by(&mut entries, &query(SortBy::Name, SortOrder::Asc, None, None), |_| 0);
+        assert_eq!(names(&entries), vec!["ep1.mp4", "ep2.mp4", "ep10.mp4"]);
+        sort_by(&mut entries, &query(SortBy::Name, SortOrder::Desc, None, None), |_| 0);
+        assert_eq!(names(&entries), vec!["ep10.mp4", "ep2.mp4", "ep1.mp4"]);
+    }
+
+    #[test]
+    fn keeps_natural_order_for_equal_values() {
+        let mut entries = vec![
+            entry("c.mp4", 2, 10), entry("b.mp4", 1, 20), entry("a.mp4", 2, 20), entry("d.mp4", 1, 10)
+        ];
+        sort_by(&mut entries, &query(SortBy::Mtime, SortOrder::Asc, None, None), |_| 0);
+        assert_eq!(names(&entries), vec!["b.mp4", "d.mp4", "a.mp4", "c.mp4"]);
+        sort_by(&mut entries, &query(SortBy::Size, SortOrder::Desc, None, None), |_| 0);
+        assert_eq!(names(&entries), vec!["a.mp4", "b.mp4", "c.mp4", "d.mp4"]);
+        // Files that are not probed yet have no duration, and are listed first in the ascending order
+        sort_by(&mut entries, &query(SortBy::Duration, SortOrder::Asc, None, None),
+                |entry| if entry.name == "a.mp4" { 5_000 } else { 0 });
+        assert_eq!(names(&entries), vec!["b.mp4", "c.mp4", "d.mp4", "a.mp4"]);
+    }
+
+    #[test]
+    fn slices_the_requested_page() {
+        let entries: Vec<index::IndexEntry> = (1..=5).map(|n| entry(&format!("{}.mp4", n), 0, 0)).collect();
+        let (files, pagination) = page(entries.clone(), &query(SortBy::Name, SortOrder::Asc, Some(2), Some(2)));
+        assert_eq!(names(&files), vec!["3.mp4", "4.mp4"]);
+        assert_eq!((pagination.page, pagination.pages, pagination.limit, pagination.total), (2, 3, 2, 5));
+        // Pages beyond the last one return the last page
+        let (files, pagination) = page(entries.clone(), &query(SortBy::Name, SortOrder::Asc, Some(10), Some(2)));
+        assert_eq!(names(&files), vec!["5.mp4"]);
+        assert_eq!(pagination.page, 3);
+        let (files, pagination) = page(entries.clone(), &query(SortBy::Name, SortOrder::Asc, Some(0), None));
+        assert_eq!(files.len(), 5);
+        assert_eq!((pagination.page, pagination.pages, pagination.limit), (1, 1, DEFAULT_PAGE_SIZE));
+    }
+
+    #[test]
+    fn clamps_the_limit() {
+        let entries: Vec<index::IndexEntry> = (1..=3).map(|n| entry(&format!("{}.mp4", n), 0, 0)).collect();
+        let (files, pagination) = page(entries.clone(), &query(SortBy::Name, SortOrder::Asc, None, Some(0)));
+        assert_eq!(names(&files), vec!["1.mp4"]);
+        assert_eq!((pagination.limit, pagination.pages), (1, 3));
+        let (files, pagination) = page(entries, &query(SortBy::Name, SortOrder::Asc, None, Some(5000)));
+        assert_eq!(files.len(), 3);
+        assert_eq!((pagination.limit, pagination.pages), (MAX_PAGE_SIZE, 1));
+        let (files, pagination) = page(Vec::new(), &query(SortBy::Name, SortOrder::Asc, Some(3), None));
+        assert!(files.is_empty());
+        assert_eq!((pagination.page, pagination.pages, pagination.total), (1, 1, 0));
+    }
+}
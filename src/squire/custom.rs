@@ -1,8 +1,69 @@
-use actix_web::{HttpRequest, HttpResponse};
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web::error::{InternalError, QueryPayloadError};
 use actix_web::http::StatusCode;
 use minijinja::Template;
 
 use crate::constant;
+use crate::squire::settings;
+
+/// Returns the IP address of the client, honoring the `X-Forwarded-For` header only from the trusted proxies.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `config` - Configuration data for the application.
+///
+/// ## See Also
+///
+/// - `X-Forwarded-For` and `Forwarded` headers can be set by any client, so they are ignored by default.
+/// - The `Forwarded` header is always ignored, since the trusted proxies are expected to set `X-Forwarded-For`.
+///
+/// # Returns
+///
+/// Returns the IP address of the client as a string, empty if it couldn't be determined.
+pub fn client_ip(request: &HttpRequest, config: &settings::Config) -> String {
+    let peer = request.peer_addr().map(|addr| addr.ip().to_string()).unwrap_or_default();
+    let forwarded_for: Vec<&str> = request.headers().get_all("x-forwarded-for")
+        .filter_map(|value| value.to_str().ok())
+        .collect();
+    forwarded_ip(&peer, &forwarded_for.join(","), &config.trusted_proxies)
+}
+
+/// Walks the `X-Forwarded-For` chain from the right, and returns the first hop that is not a trusted proxy.
+///
+/// # Arguments
+///
+/// * `peer` - IP address of the connecting peer.
+/// * `forwarded_for` - Comma separated values of the `X-Forwarded-For` headers.
+/// * `trusted_proxies` - IP addresses of the reverse proxies.
+///
+/// ## See Also
+///
+/// - Each proxy appends the address it received the request from, so only the right end of the chain can be trusted.
+/// - Entries to the left of the first untrusted hop are set by the client, and are never used.
+///
+/// # Returns
+///
+/// Returns the IP address of the client as a string.
+fn forwarded_ip(peer: &str, forwarded_for: &str, trusted_proxies: &[String]) -> String {
+    let mut client = peer.to_string();
+    if !trusted_proxies.iter().any(|proxy| proxy == &client) {
+        return client;
+    }
+    for hop in forwarded_for.rsplit(',').map(str::trim).filter(|hop| !hop.is_empty()) {
+        match hop.parse::<IpAddr>() {
+            Ok(address) => client = address.to_string(),
+            Err(_) => break,
+        }
+        if !trusted_proxies.iter().any(|proxy| proxy == &client) {
+            break;
+        }
+    }
+    client
+}
 
 /// Logs connection information for an incoming HTTP request.
 ///
@@ -26,7 +87,7 @@ pub fn log_connection(request: &HttpRequest, session: &constant::Session) -> (St
             log::info!("User agent: {}", user_agent.to_str().unwrap())
         }
     }
-    return (host.clone(), tracker.get(&host).map_or("".to_string(), |s| s.to_string()));
+    (host.clone(), tracker.get(&host).map_or("".to_string(), |s| s.to_string()))
 }
 
 /// Frames a custom response into an error page.
@@ -58,3 +119,52 @@ pub fn error(title: &str,
             block_navigation => true
         )).unwrap())
 }
+
+/// Frames the errors from the query string extractors, like an unsupported `sort` or `order` in the listings.
+///
+/// # Arguments
+///
+/// * `err` - Error from deserializing the query string.
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+///
+/// ## See Also
+///
+/// - Requests to the `/api` endpoints receive a JSON response with the `detail`, instead of the error page.
+///
+/// # Returns
+///
+/// Returns an `Error` that responds with the status code `400`.
+pub fn query_error(err: QueryPayloadError, request: &HttpRequest) -> actix_web::Error {
+    let description = format!("Invalid query parameters: {}", err);
+    let template = request.app_data::<web::Data<Arc<minijinja::Environment<'static>>>>();
+    let metadata = request.app_data::<web::Data<Arc<constant::MetaData>>>();
+    let response = match (template, metadata) {
+        (Some(template), Some(metadata)) if !request.path().starts_with("/api/") => error(
+            "INVALID REQUEST",
+            template.get_template("error").unwrap(),
+            &metadata.pkg_version,
+            description,
+            StatusCode::BAD_REQUEST
+        ),
+        _ => HttpResponse::BadRequest().json(serde_json::json!({ "detail": description }))
+    };
+    InternalError::from_response(err, response).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forwarded_ip_ignores_spoofed_entries() {
+        let trusted = vec!["10.0.0.1".to_string(), "10.0.0.2".to_string()];
+        // Client prepends a fake address, the proxies append the real one
+        assert_eq!(forwarded_ip("10.0.0.1", "1.1.1.1, 203.0.113.7, 10.0.0.2", &trusted), "203.0.113.7");
+        assert_eq!(forwarded_ip("10.0.0.1", "1.1.1.1,203.0.113.7", &trusted), "203.0.113.7");
+        // Header is not trusted from an untrusted peer
+        assert_eq!(forwarded_ip("198.51.100.4", "1.1.1.1", &trusted), "198.51.100.4");
+        // Missing or malformed header falls back to the last trusted hop
+        assert_eq!(forwarded_ip("10.0.0.1", "", &trusted), "10.0.0.1");
+        assert_eq!(forwarded_ip("10.0.0.1", "1.1.1.1, garbage, 10.0.0.2", &trusted), "10.0.0.2");
+    }
+}
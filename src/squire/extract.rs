@@ -0,0 +1,450 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded;
+
+use crate::squire::{probe, settings, subtitles, transcode};
+
+/// Containers that support moving the index to the start of the file, so the remuxed variant can be played while loading.
+const FASTSTART_FORMATS: [&str; 3] = ["mp4", "m4v", "mov"];
+
+/// Maximum number of jobs waiting for a worker, further jobs are dropped until they are requested again.
+const MAX_QUEUE: usize = 64;
+
+/// Represents a job that is queued for the workers.
+enum Task {
+    /// Extracts the text subtitle streams from the media file.
+    Subtitles { filepath: PathBuf, key: String, streams: Vec<probe::SubtitleStream> },
+    /// Remuxes the media file with the chosen audio stream.
+    Remux { filepath: PathBuf, key: String, index: u32 },
+}
+
+/// Represents an audio track that the media file can be played with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioSource {
+    /// Index of the stream within the container.
+    pub index: u32,
+    /// Label of the track with the language, codec and channels.
+    pub label: String,
+    /// Language tag of the track, if available.
+    pub language: Option<String>,
+    /// URL of the media file with only this audio track, the original file is used for the first track.
+    pub url: String,
+}
+
+/// Struct to extract the embedded subtitles and remux the audio tracks using `ffmpeg`, and cache them outside the media tree.
+///
+/// ## Fields
+///
+/// * `enabled` - Boolean flag to indicate if `ffmpeg` is available.
+/// * `ffmpeg` - Path to the `ffmpeg` binary.
+/// * `subtitles` - Directory where the subtitle streams are cached in WebVTT format.
+/// * `remuxes` - Directory where the variants of the media files with a single audio track are cached.
+/// * `pending` - Jobs queued or running in the background, as the cache key and the output (`subtitles` or `audio_<index>`).
+/// * `failed` - Jobs that failed, which are not retried until the media file is modified (which changes the cache key).
+/// * `queue` - Jobs waiting for a worker, along with their identifiers.
+/// * `queued` - Condition variable to wake up the workers, when a job is queued.
+/// * `accessed` - Time when each remuxed variant was last requested, to remove the least recently used.
+/// * `max_cache_size` - Maximum size of the remuxed variants in bytes.
+pub struct Extractor {
+    pub enabled: bool,
+    ffmpeg: PathBuf,
+    subtitles: PathBuf,
+    remuxes: PathBuf,
+    pending: Mutex<HashSet<String>>,
+    failed: Mutex<HashSet<String>>,
+    queue: Mutex<VecDeque<(String, Task)>>,
+    queued: Condvar,
+    accessed: Mutex<HashMap<PathBuf, SystemTime>>,
+    max_cache_size: u64,
+}
+
+/// Instantiates the `Extractor` struct, after verifying that `ffmpeg` can be executed.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+///
+/// ## See Also
+///
+/// - Jobs are run by `max_extractions` worker threads, so the number of `ffmpeg` processes is bounded.
+///
+/// # Returns
+///
+/// Returns the constructed `Arc` for the `Extractor` struct.
+pub fn extractor_info(config: &settings::Config) -> Arc<Extractor> {
+    let enabled = transcode::available(&config.ffmpeg, "embedded subtitles and audio tracks will not be available",
+                                       log::Level::Info);
+    let extractor = Arc::new(Extractor {
+        enabled,
+        ffmpeg: config.ffmpeg.clone(),
+        subtitles: config.subtitle_cache.join("embedded"),
+        remuxes: config.cache_dir.join("remux"),
+        pending: Mutex::new(HashSet::new()),
+        failed: Mutex::new(HashSet::new()),
+        queue: Mutex::new(VecDeque::new()),
+        queued: Condvar::new(),
+        accessed: Mutex::new(HashMap::new()),
+        max_cache_size: config.remux_cache_size as u64,
+    });
+    if enabled {
+        for _ in 0..config.max_extractions {
+            let worker = extractor.clone();
+            std::thread::spawn(move || worker.work());
+        }
+    }
+    extractor
+}
+
+/// Lists the audio tracks that the media file can be played with.
+///
+/// # Arguments
+///
+/// * `extractor` - Extractor struct to remux the media file with the chosen audio track.
+/// * `info` - Metadata of the media file, with the audio tracks.
+/// * `relative_path` - Path of the media file, relative to `media_source`.
+///
+/// # Returns
+///
+/// Returns a vector of the `AudioSource`, which is empty unless there are multiple audio tracks to choose from.
+pub fn audio_sources(extractor: &Extractor, info: &probe::MediaInfo, relative_path: &str) -> Vec<AudioSource> {
+    if !extractor.enabled || info.audio_tracks.len() < 2 {
+        return Vec::new();
+    }
+    let encoded = form_urlencoded::byte_serialize(relative_path.as_bytes()).collect::<String>();
+    info.audio_tracks.iter().enumerate()
+        .map(|(position, audio)| AudioSource {
+            index: audio.index,
+            label: audio.label(),
+            language: audio.language.clone(),
+            // Browsers play the first audio track, so the original file is used for it
+            url: if position == 0 {
+                format!("/media?file={}", encoded)
+            } else {
+                format!("/remux?file={}&audio={}", encoded, audio.index)
+            },
+        })
+        .collect()
+}
+
+impl Extractor {
+    /// Queues a job for the workers, unless it is already queued or has failed before.
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - Job identifier, which is the cache key and the output.
+    /// * `task` - Job to be run by the workers.
+    fn enqueue(&self, job: String, task: Task) {
+        if self.failed.lock().unwrap().contains(&job) || !self.pending.lock().unwrap().insert(job.clone()) {
+            return;
+        }
+        let mut queue = self.queue.lock().unwrap();
+        if queue.len() >= MAX_QUEUE {
+            log::warn!("Unable to queue '{}', {} jobs are already waiting", job, queue.len());
+            drop(queue);
+            self.pending.lock().unwrap().remove(&job);
+            return;
+        }
+        queue.push_back((job, task));
+        self.queued.notify_one();
+    }
+
+    /// Runs the queued jobs one after the other, waiting for a job when the queue is empty.
+    fn work(&self) {
+        loop {
+            let (job, task) = {
+                let mut queue = self.queue.lock().unwrap();
+                loop {
+                    match queue.pop_front() {
+                        Some(next) => break next,
+                        None => queue = self.queued.wait(queue).unwrap()
+                    }
+                }
+            };
+            let result = match &task {
+                Task::Subtitles { filepath, key, streams } => self.extract_subtitles(filepath, key, streams)
+                    .map(|extracted| log::info!("Extracted {} subtitle streams from {:?}", extracted, filepath)),
+                Task::Remux { filepath, key, index } => self.generate_remux(filepath, key, *index)
+                    .map(|remux| {
+                        log::info!("Remuxed {:?} with the audio stream {} into {:?}", filepath, index, remux);
+                        self.evict(&remux);
+                    })
+            };
+            if let Err(err) = result {
+                log::warn!("{}", err);
+                self.failed.lock().unwrap().insert(job.clone());
+            }
+            self.pending.lock().unwrap().remove(&job);
+        }
+    }
+
+    /// Generates the path of a subtitle stream in the cache.
+    fn subtitle_path(&self, key: &str, index: u32) -> PathBuf {
+        self.subtitles.join(format!("{}_{}.vtt", key, index))
+    }
+
+    /// Looks up the subtitle stream extracted from the media file, without extracting it.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepath` - True path of the media file.
+    /// * `relative_path` - Path of the media file, relative to `media_source`.
+    /// * `index` - Index of the subtitle stream within the container.
+    ///
+    /// # Returns
+    ///
+    /// Returns the path to the WebVTT file, if the stream was extracted from the current version of the file.
+    pub fn subtitle(&self, filepath: &Path, relative_path: &str, index: u32) -> Option<PathBuf> {
+        let key = transcode::cache_key(filepath, relative_path)?;
+        let subtitle = self.subtitle_path(&key, index);
+        if subtitle.exists() { Some(subtitle) } else { None }
+    }
+
+    /// Lists the tracks for the subtitle streams that were extracted from the media file.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepath` - True path of the media file.
+    /// * `relative_path` - Path of the media file, relative to `media_source`.
+    /// * `info` - Metadata of the media file, with the subtitle streams.
+    ///
+    /// # Returns
+    ///
+    /// Returns a vector of the `Track` for the text subtitle streams that are available in the cache.
+    pub fn tracks(&self, filepath: &Path, relative_path: &str, info: &probe::MediaInfo) -> Vec<subtitles::Track> {
+        info.subtitle_streams.iter()
+            .filter(|stream| self.subtitle(filepath, relative_path, stream.index).is_some())
+            .map(|stream| subtitles::embedded(stream, relative_path))
+            .collect()
+    }
+
+    /// Extracts the text subtitle streams from the media file into WebVTT, with a single pass over the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepath` - True path of the media file.
+    /// * `key` - Cache key of the media file.
+    /// * `streams` - Subtitle streams that have to be extracted.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the number of streams extracted, or an error message.
+    fn extract_subtitles(&self,
+                         filepath: &Path,
+                         key: &str,
+                         streams: &[probe::SubtitleStream]) -> Result<usize, String> {
+        fs::create_dir_all(&self.subtitles)
+            .map_err(|err| format!("Failed to create {:?}: {}", self.subtitles, err))?;
+        let mut command = Command::new(&self.ffmpeg);
+        command
+            .args(["-hide_banner", "-loglevel", "error", "-nostdin", "-y", "-i"])
+            .arg(filepath);
+        // Each stream is written to a temporary file first, so a partial track is never served
+        let outputs: Vec<(PathBuf, PathBuf)> = streams.iter()
+            .map(|stream| {
+                let subtitle = self.subtitle_path(key, stream.index);
+                (subtitle.with_extension("vtt.part"), subtitle)
+            })
+            .collect();
+        for (stream, (temporary, _)) in streams.iter().zip(&outputs) {
+            command
+                .args(["-map", &format!("0:{}", stream.index), "-c:s", "webvtt", "-f", "webvtt"])
+                .arg(temporary);
+        }
+        let result = command
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|err| format!("Failed to run {:?}: {}", self.ffmpeg, err))?;
+        if !result.status.success() {
+            for (temporary, _) in &outputs {
+                let _ = fs::remove_file(temporary);
+            }
+            return Err(format!("Failed to extract the subtitles from {:?}: {}",
+                               filepath, String::from_utf8_lossy(&result.stderr).trim()));
+        }
+        let mut extracted = 0;
+        for (temporary, subtitle) in &outputs {
+            match fs::rename(temporary, subtitle) {
+                Ok(_) => extracted += 1,
+                Err(err) => log::warn!("Failed to store {:?}: {}", subtitle, err)
+            }
+        }
+        Ok(extracted)
+    }
+
+    /// Queues the extraction of the text subtitle streams, since it requires reading the whole file.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepath` - True path of the media file.
+    /// * `relative_path` - Path of the media file, relative to `media_source`.
+    /// * `info` - Metadata of the media file, with the subtitle streams.
+    ///
+    /// ## See Also
+    ///
+    /// - Streams that are already extracted are skipped, and bitmap subtitles are never extracted.
+    /// - Extraction is not retried after a failure, until the media file is modified.
+    pub fn schedule_subtitles(&self, filepath: &Path, relative_path: &str, info: &probe::MediaInfo) {
+        if !self.enabled {
+            return;
+        }
+        let key = match transcode::cache_key(filepath, relative_path) {
+            Some(key) => key,
+            None => return
+        };
+        let streams: Vec<probe::SubtitleStream> = info.subtitle_streams.iter()
+            .filter(|stream| stream.is_text() && !self.subtitle_path(&key, stream.index).exists())
+            .cloned()
+            .collect();
+        if streams.is_empty() {
+            return;
+        }
+        let job = format!("{}/subtitles", key);
+        self.enqueue(job, Task::Subtitles { filepath: filepath.to_path_buf(), key, streams });
+    }
+
+    /// Generates the path of a remuxed variant in the cache, with the same container as the media file.
+    fn remux_path(&self, key: &str, filepath: &Path, index: u32) -> PathBuf {
+        let extension = filepath.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+        self.remuxes.join(format!("{}_{}.{}", key, index, extension))
+    }
+
+    /// Looks up the variant of the media file with the chosen audio track, without remuxing it.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepath` - True path of the media file.
+    /// * `relative_path` - Path of the media file, relative to `media_source`.
+    /// * `index` - Index of the audio stream within the container.
+    ///
+    /// # Returns
+    ///
+    /// Returns the path to the remuxed variant, if it was generated from the current version of the file.
+    pub fn remux(&self, filepath: &Path, relative_path: &str, index: u32) -> Option<PathBuf> {
+        let key = transcode::cache_key(filepath, relative_path)?;
+        let remux = self.remux_path(&key, filepath, index);
+        if !remux.exists() {
+            return None;
+        }
+        self.accessed.lock().unwrap().insert(remux.clone(), SystemTime::now());
+        Some(remux)
+    }
+
+    /// Removes the least recently requested variants, until the remuxed cache fits within its maximum size.
+    ///
+    /// # Arguments
+    ///
+    /// * `current` - Variant that was just remuxed, which is never removed.
+    ///
+    /// ## See Also
+    ///
+    /// - Variants that were not requested since the server started fall back to their modified time.
+    fn evict(&self, current: &Path) {
+        let accessed = self.accessed.lock().unwrap().clone();
+        let mut entries = Vec::new();
+        let mut total = 0;
+        for entry in fs::read_dir(&self.remuxes).into_iter().flatten().flatten() {
+            let metadata = match entry.metadata() {
+                Ok(metadata) if metadata.is_file() => metadata,
+                _ => continue
+            };
+            total += metadata.len();
+            let path = entry.path();
+            // Partial files are still being written by the workers
+            if path == current || entry.file_name().to_string_lossy().contains(".part.") {
+                continue;
+            }
+            let last_used = accessed.get(&path).copied()
+                .or_else(|| metadata.modified().ok())
+                .unwrap_or(UNIX_EPOCH);
+            entries.push((last_used, metadata.len(), path));
+        }
+        entries.sort_by_key(|(last_used, ..)| *last_used);
+        for (_, size, path) in entries {
+            if total <= self.max_cache_size {
+                break;
+            }
+            match fs::remove_file(&path) {
+                Ok(_) => {
+                    log::info!("Removed {:?} to keep the remuxed cache within its size limit", path);
+                    total = total.saturating_sub(size);
+                    self.accessed.lock().unwrap().remove(&path);
+                }
+                Err(err) => log::error!("Failed to remove {:?}: {}", path, err),
+            }
+        }
+    }
+
+    /// Remuxes the media file with the first video stream and the chosen audio stream, without re-encoding.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepath` - True path of the media file.
+    /// * `key` - Cache key of the media file.
+    /// * `index` - Index of the audio stream within the container.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the path to the remuxed variant, or an error message.
+    fn generate_remux(&self, filepath: &Path, key: &str, index: u32) -> Result<PathBuf, String> {
+        fs::create_dir_all(&self.remuxes)
+            .map_err(|err| format!("Failed to create {:?}: {}", self.remuxes, err))?;
+        let remux = self.remux_path(key, filepath, index);
+        let extension = remux.extension().unwrap_or_default().to_string_lossy().to_string();
+        // Temporary file retains the extension, since the container is inferred from it
+        let temporary = remux.with_extension(format!("part.{}", extension));
+        let mut command = Command::new(&self.ffmpeg);
+        command
+            .args(["-hide_banner", "-loglevel", "error", "-nostdin", "-y", "-i"])
+            .arg(filepath)
+            .args(["-map", "0:v:0?", "-map", &format!("0:{}", index), "-c", "copy", "-disposition:a:0", "default"]);
+        if FASTSTART_FORMATS.contains(&extension.as_str()) {
+            command.args(["-movflags", "+faststart"]);
+        }
+        let result = command
+            .arg(&temporary)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|err| format!("Failed to run {:?}: {}", self.ffmpeg, err))?;
+        if !result.status.success() || !temporary.exists() {
+            let _ = fs::remove_file(&temporary);
+            return Err(format!("Failed to remux {:?} with the audio stream {}: {}",
+                               filepath, index, String::from_utf8_lossy(&result.stderr).trim()));
+        }
+        fs::rename(&temporary, &remux)
+            .map_err(|err| format!("Failed to store {:?}: {}", remux, err))?;
+        Ok(remux)
+    }
+
+    /// Queues remuxing the media file with the chosen audio track.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepath` - True path of the media file.
+    /// * `relative_path` - Path of the media file, relative to `media_source`.
+    /// * `index` - Index of the audio stream within the container.
+    ///
+    /// # Returns
+    ///
+    /// Returns `false` if remuxing failed for the current version of the media file, so it is not retried.
+    pub fn schedule_remux(&self, filepath: &Path, relative_path: &str, index: u32) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let key = match transcode::cache_key(filepath, relative_path) {
+            Some(key) => key,
+            None => return false
+        };
+        let job = format!("{}/audio_{}", key, index);
+        if self.failed.lock().unwrap().contains(&job) {
+            return false;
+        }
+        self.enqueue(job, Task::Remux { filepath: filepath.to_path_buf(), key, index });
+        true
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::{Duration, Instant, SystemTime};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use walkdir::WalkDir;
+
+use crate::squire::{search, settings};
+
+/// Number of seconds between two refreshes of the index, when the filesystem watcher is unavailable.
+const REFRESH_INTERVAL: u64 = 10;
+
+/// Represents a media file in the library index.
+#[derive(Debug, Clone)]
+pub struct IndexEntry {
+    /// Name of the file.
+    pub name: String,
+    /// Path of the file, relative to `media_source`.
+    pub path: String,
+    /// Path of the parent directory, relative to `media_source`.
+    pub directory: String,
+    /// File extension.
+    pub extension: String,
+    /// Size of the file in bytes.
+    pub size: u64,
+    /// Last modified time of the file as epoch.
+    pub modified: i64,
+    /// Search tokens from the filename.
+    pub tokens: Vec<String>,
+}
+
+/// Struct to index the media files in memory, so the library is not walked on every request.
+///
+/// ## Fields
+///
+/// * `media_source` - Source path for the files to be indexed.
+/// * `file_formats` - File formats that are indexed.
+/// * `entries` - Indexed files keyed by the path relative to `media_source`.
+/// * `directories` - Modified time of each indexed directory, to detect the changes.
+/// * `watcher` - Filesystem watcher that keeps the index current, polling is used as a fallback without it.
+pub struct Library {
+    media_source: PathBuf,
+    file_formats: Vec<String>,
+    entries: RwLock<HashMap<String, IndexEntry>>,
+    directories: Mutex<HashMap<PathBuf, SystemTime>>,
+    watcher: Mutex<Option<RecommendedWatcher>>,
+}
+
+/// Checks if the file or directory is hidden from the listings.
+///
+/// # Arguments
+///
+/// * `name` - Name of the file or directory.
+///
+/// # Returns
+///
+/// Returns a boolean flag to indicate if the entry starts with `_` or `.`
+pub fn is_hidden(name: &str) -> bool {
+    name.starts_with('_') || name.starts_with('.')
+}
+
+/// Instantiates the `Library` struct, builds the index by walking the `media_source` and starts watching for changes.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+///
+/// # Returns
+///
+/// Returns the constructed `Arc` for the `Library` struct.
+pub fn library_info(config: &settings::Config) -> Arc<Library> {
+    let library = Arc::new(Library {
+        media_source: config.media_source.clone(),
+        file_formats: config.file_formats.clone(),
+        entries: RwLock::new(HashMap::new()),
+        directories: Mutex::new(HashMap::new()),
+        watcher: Mutex::new(None),
+    });
+    let start = Instant::now();
+    library.scan();
+    log::info!("Indexed {} files in {:?}", library.entries.read().unwrap().len(), start.elapsed());
+    watch(&library);
+    library
+}
+
+/// Starts the filesystem watcher (`inotify` on Linux) for the `media_source`, to keep the index current.
+///
+/// # Arguments
+///
+/// * `library` - Reference counted `Library`, that is updated by the watcher.
+///
+/// ## See Also
+///
+/// - The watcher only holds a weak reference, so it doesn't keep the library alive.
+/// - When the watcher cannot be started (e.g. the limit on watches is reached), the index is refreshed by polling
+///   in a background thread, so the requests are always served from the index as is.
+fn watch(library: &Arc<Library>) {
+    let weak: Weak<Library> = Arc::downgrade(library);
+    let handler = move |result: notify::Result<notify::Event>| {
+        let library = match weak.upgrade() {
+            Some(library) => library,
+            None => return
+        };
+        match result {
+            Ok(event) if !event.kind.is_access() => {
+                for path in &event.paths {
+                    library.sync(path);
+                }
+            }
+            Ok(_) => {}
+            Err(err) => log::warn!("Filesystem watcher error: {}", err)
+        }
+    };
+    let watcher = notify::recommended_watcher(handler)
+        .and_then(|mut watcher| {
+            watcher.watch(&library.media_source, RecursiveMode::Recursive)?;
+            Ok(watcher)
+        });
+    match watcher {
+        Ok(watcher) => {
+            log::info!("Watching {:?} for changes", library.media_source);
+            *library.watcher.lock().unwrap() = Some(watcher);
+        }
+        Err(err) => {
+            log::warn!("Unable to watch {:?}: {}, the index will be refreshed every {} seconds",
+                       library.media_source, err, REFRESH_INTERVAL);
+            let weak: Weak<Library> = Arc::downgrade(library);
+            std::thread::spawn(move || loop {
+                std::thread::sleep(Duration::from_secs(REFRESH_INTERVAL));
+                match weak.upgrade() {
+                    Some(library) => library.refresh(),
+                    None => break
+                }
+            });
+        }
+    }
+}
+
+impl Library {
+    /// Reads the media files directly within a directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` - Path of the directory, relative to `media_source`.
+    ///
+    /// # Returns
+    ///
+    /// Returns a vector of the `IndexEntry` for each media file in the directory.
+    fn read_directory(&self, directory: &Path) -> Vec<IndexEntry> {
+        let read_dir = match fs::read_dir(self.media_source.join(directory)) {
+            Ok(read_dir) => read_dir,
+            Err(err) => {
+                log::warn!("Failed to read {:?}: {}", directory, err);
+                return Vec::new();
+            }
+        };
+        read_dir.flatten()
+            .filter_map(|entry| self.read_file(directory, &entry.file_name().to_string_lossy()))
+            .collect()
+    }
+
+    /// Reads a media file for the index.
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` - Path of the parent directory, relative to `media_source`.
+    /// * `name` - Name of the file.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `IndexEntry` if the file exists, is not hidden and has one of the allowed file formats.
+    fn read_file(&self, directory: &Path, name: &str) -> Option<IndexEntry> {
+        if is_hidden(name) {
+            return None;
+        }
+        let extension = Path::new(name).extension().unwrap_or_default().to_string_lossy().to_string();
+        if !self.file_formats.contains(&extension) {
+            return None;
+        }
+        let metadata = fs::metadata(self.media_source.join(directory).join(name)).ok().filter(|metadata| metadata.is_file())?;
+        let modified = metadata.modified().ok()
+            .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs() as i64)
+            .unwrap_or_default();
+        Some(IndexEntry {
+            tokens: search::tokenize(Path::new(name).file_stem().unwrap_or_default().to_string_lossy().as_ref()),
+            path: directory.join(name).to_string_lossy().to_string(),
+            directory: directory.to_string_lossy().to_string(),
+            name: name.to_string(),
+            extension,
+            size: metadata.len(),
+            modified,
+        })
+    }
+
+    /// Lists the directories under a directory (including itself) with their modified time, skipping the hidden ones.
+    fn walk_directories(&self, root: &Path) -> HashMap<PathBuf, SystemTime> {
+        WalkDir::new(root).into_iter()
+            .filter_entry(|entry| entry.depth() == 0 || !is_hidden(&entry.file_name().to_string_lossy()))
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_dir())
+            .filter_map(|entry| {
+                let modified = entry.metadata().ok()?.modified().ok()?;
+                let relative = entry.path().strip_prefix(&self.media_source).ok()?.to_path_buf();
+                Some((relative, modified))
+            })
+            .collect()
+    }
+
+    /// Builds the index from scratch.
+    fn scan(&self) {
+        let directories = self.walk_directories(&self.media_source);
+        let mut entries = HashMap::new();
+        for directory in directories.keys() {
+            for entry in self.read_directory(directory) {
+                entries.insert(entry.path.clone(), entry);
+            }
+        }
+        *self.entries.write().unwrap() = entries;
+        *self.directories.lock().unwrap() = directories;
+    }
+
+    /// Refreshes the index incrementally, by re-reading only the directories that were modified since the last refresh.
+    ///
+    /// ## See Also
+    ///
+    /// - Adding, removing or renaming a file updates the modified time of its parent directory.
+    /// - Only used by the polling thread, when the filesystem watcher is unavailable.
+    fn refresh(&self) {
+        let current = self.walk_directories(&self.media_source);
+        let mut directories = self.directories.lock().unwrap();
+        let changed: Vec<&PathBuf> = current.iter()
+            .filter(|(directory, modified)| directories.get(*directory) != Some(*modified))
+            .map(|(directory, _)| directory)
+            .collect();
+        let removed: Vec<String> = directories.keys()
+            .filter(|directory| !current.contains_key(*directory))
+            .map(|directory| directory.to_string_lossy().to_string())
+            .collect();
+        if changed.is_empty() && removed.is_empty() {
+            return;
+        }
+        let added: Vec<(String, Vec<IndexEntry>)> = changed.iter()
+            .map(|directory| (directory.to_string_lossy().to_string(), self.read_directory(directory)))
+            .collect();
+        let mut entries = self.entries.write().unwrap();
+        for (directory_str, directory_entries) in added {
+            entries.retain(|_, entry| entry.directory != directory_str);
+            for entry in directory_entries {
+                entries.insert(entry.path.clone(), entry);
+            }
+        }
+        entries.retain(|_, entry| !removed.contains(&entry.directory));
+        log::debug!("Refreshed {} directories in the index, {} removed", changed.len(), removed.len());
+        *directories = current;
+    }
+
+    /// Updates the index for a file or directory that was created, modified, renamed or deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - True path of the file or directory, that was changed.
+    ///
+    /// ## See Also
+    ///
+    /// - Called by the filesystem watcher, and by the uploads and edits so the changes are visible immediately.
+    /// - Directories are indexed recursively, so moving a directory into the library indexes all its files.
+    pub fn sync(&self, path: &Path) {
+        let relative = match path.strip_prefix(&self.media_source) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => return
+        };
+        if relative.as_os_str().is_empty() {
+            return self.scan();
+        }
+        if relative.iter().any(|component| is_hidden(&component.to_string_lossy())) {
+            return;
+        }
+        let relative_str = relative.to_string_lossy().to_string();
+        // The changes are read before acquiring the locks, so the requests are not blocked by the walk
+        let mut added = Vec::new();
+        let mut walked = HashMap::new();
+        if path.is_dir() {
+            walked = self.walk_directories(path);
+            for directory in walked.keys() {
+                added.extend(self.read_directory(directory));
+            }
+        } else {
+            let directory = relative.parent().unwrap_or(Path::new("")).to_path_buf();
+            let name = relative.file_name().unwrap_or_default().to_string_lossy().to_string();
+            added.extend(self.read_file(&directory, &name));
+        }
+        // Locks are acquired in the same order as the refresh, to avoid a deadlock
+        let mut directories = self.directories.lock().unwrap();
+        let mut entries = self.entries.write().unwrap();
+        entries.retain(|key, entry| *key != relative_str && !Path::new(&entry.directory).starts_with(&relative));
+        directories.retain(|directory, _| !directory.starts_with(&relative));
+        for entry in added {
+            entries.insert(entry.path.clone(), entry);
+        }
+        directories.extend(walked);
+    }
+
+    /// Returns a copy of the indexed files.
+    pub fn files(&self) -> Vec<IndexEntry> {
+        self.entries.read().unwrap().values().cloned().collect()
+    }
+
+    /// Returns a copy of the indexed files directly within a directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` - Path of the directory, relative to `media_source`.
+    pub fn directory(&self, directory: &str) -> Vec<IndexEntry> {
+        let directory = directory.trim_matches('/');
+        self.entries.read().unwrap().values()
+            .filter(|entry| entry.directory == directory)
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::squire::{settings, storage};
+
+/// Maximum duration (1 day) for a temporary lockout, regardless of the number of failed attempts.
+const MAX_LOCKOUT: i64 = 86_400;
+
+/// Maximum number of IP addresses for which the failed login attempts are tracked.
+const MAX_TRACKED_IPS: usize = 10_000;
+
+/// Struct to store the failed login attempts for an IP address or a username.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Attempts {
+    /// Number of consecutive failed login attempts.
+    pub failures: usize,
+    /// Epoch time until which the login is blocked.
+    pub locked_until: i64,
+    /// Epoch time of the latest failed login attempt.
+    pub last_failed: i64,
+}
+
+/// Represents the reason for which a login request is blocked.
+pub enum Blocked {
+    /// Temporarily locked, with the number of seconds until the lockout ends.
+    Locked(i64),
+    /// Permanently banned.
+    Banned,
+}
+
+/// Struct to track the failed login attempts per IP address and per username.
+///
+/// ## Fields
+///
+/// * `by_ip` - Failed login attempts keyed by the IP address of the client.
+/// * `by_user` - Failed login attempts keyed by the username, tracked only for the configured users.
+/// * `banned` - IP addresses that are banned forever, persisted in the `storage` directory.
+/// * `banned_file` - JSON file where the banned IP addresses are stored.
+pub struct Lockout {
+    pub by_ip: Mutex<HashMap<String, Attempts>>,
+    pub by_user: Mutex<HashMap<String, Attempts>>,
+    pub banned: Mutex<Vec<String>>,
+    banned_file: PathBuf,
+}
+
+/// Instantiates the `Lockout` struct with the banned IP addresses restored from the `storage` directory.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+///
+/// # Returns
+///
+/// Returns the constructed `Arc` for the `Lockout` struct.
+pub fn lockout_info(config: &settings::Config) -> Arc<Lockout> {
+    let banned_file = config.storage.join("banned.json");
+    let banned: Vec<String> = storage::load_json(&banned_file);
+    if !banned.is_empty() {
+        log::warn!("{} IP address(es) are banned from logging in", banned.len());
+    }
+    Arc::new(Lockout {
+        by_ip: Mutex::new(HashMap::new()),
+        by_user: Mutex::new(HashMap::new()),
+        banned: Mutex::new(banned),
+        banned_file,
+    })
+}
+
+/// Checks if the attempts are currently locked.
+///
+/// # Arguments
+///
+/// * `attempts` - Optional reference to the failed attempts.
+/// * `current_time` - Current epoch time.
+///
+/// # Returns
+///
+/// Returns the number of seconds left in the lockout, if locked.
+fn time_left(attempts: Option<&Attempts>, current_time: i64) -> Option<i64> {
+    attempts
+        .map(|attempts| attempts.locked_until - current_time)
+        .filter(|remaining| *remaining > 0)
+}
+
+/// Checks if the attempts can be forgotten, since the lockout has ended and there were no failures for a day.
+///
+/// # Arguments
+///
+/// * `attempts` - Reference to the failed attempts.
+/// * `current_time` - Current epoch time.
+///
+/// # Returns
+///
+/// Returns a boolean flag to indicate whether the attempts have expired.
+fn expired(attempts: &Attempts, current_time: i64) -> bool {
+    attempts.locked_until <= current_time && current_time - attempts.last_failed > MAX_LOCKOUT
+}
+
+/// Removes the expired attempts, and evicts the least recent ones to make room for a new IP address.
+///
+/// # Arguments
+///
+/// * `by_ip` - Mutable reference to the failed attempts keyed by the IP address.
+/// * `current_time` - Current epoch time.
+///
+/// ## See Also
+///
+/// - Clients can fail logins from any number of IP addresses, so the map is capped at `MAX_TRACKED_IPS`.
+fn prune(by_ip: &mut HashMap<String, Attempts>, current_time: i64) {
+    by_ip.retain(|_, attempts| !expired(attempts, current_time));
+    while by_ip.len() >= MAX_TRACKED_IPS {
+        let oldest = by_ip.iter()
+            .min_by_key(|(_, attempts)| (attempts.locked_until > current_time, attempts.last_failed))
+            .map(|(ip, _)| ip.to_string());
+        match oldest {
+            Some(ip) => by_ip.remove(&ip),
+            None => break,
+        };
+    }
+}
+
+/// Records a failed attempt, and locks it with an exponential backoff once the allowed attempts are exhausted.
+///
+/// # Arguments
+///
+/// * `attempts` - Mutable reference to the failed attempts.
+/// * `config` - Configuration data for the application.
+/// * `current_time` - Current epoch time.
+///
+/// # Returns
+///
+/// Returns the duration of the lockout in seconds, if locked.
+fn record(attempts: &mut Attempts, config: &settings::Config, current_time: i64) -> Option<i64> {
+    attempts.failures += 1;
+    attempts.last_failed = current_time;
+    if attempts.failures < config.max_login_attempts {
+        return None;
+    }
+    // Lockout duration doubles for every failed attempt beyond the allowed limit
+    let exponent = (attempts.failures - config.max_login_attempts).min(16) as u32;
+    let duration = config.lockout_duration.saturating_mul(2i64.pow(exponent)).min(MAX_LOCKOUT);
+    attempts.locked_until = current_time + duration;
+    Some(duration)
+}
+
+impl Lockout {
+    /// Checks if a login request from the IP address, for the username is allowed.
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - IP address of the client.
+    /// * `username` - Username in the login request, empty if it couldn't be extracted.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the reason, if the login request is blocked.
+    pub fn check(&self, ip: &str, username: &str) -> Result<(), Blocked> {
+        if self.banned.lock().unwrap().iter().any(|banned| banned == ip) {
+            return Err(Blocked::Banned);
+        }
+        let current_time = Utc::now().timestamp();
+        let by_ip = time_left(self.by_ip.lock().unwrap().get(ip), current_time);
+        let by_user = time_left(self.by_user.lock().unwrap().get(username), current_time);
+        match by_ip.max(by_user) {
+            Some(remaining) => Err(Blocked::Locked(remaining)),
+            None => Ok(()),
+        }
+    }
+
+    /// Records a failed login attempt for the IP address and the username.
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - IP address of the client.
+    /// * `username` - Username in the login request, empty if it couldn't be extracted.
+    /// * `config` - Configuration data for the application.
+    pub fn failed(&self, ip: &str, username: &str, config: &settings::Config) {
+        let current_time = Utc::now().timestamp();
+        let ip_failures = {
+            let mut by_ip = self.by_ip.lock().unwrap();
+            if !by_ip.contains_key(ip) {
+                prune(&mut by_ip, current_time);
+            }
+            let attempts = by_ip.entry(ip.to_string()).or_default();
+            if let Some(duration) = record(attempts, config, current_time) {
+                log::warn!("{} has been locked out for {}s after {} failed login attempts",
+                    ip, duration, attempts.failures);
+            }
+            attempts.failures
+        };
+        // Usernames are tracked only if they exist, so random usernames don't fill up the memory
+        if config.authorization.contains_key(username) {
+            let mut by_user = self.by_user.lock().unwrap();
+            let attempts = by_user.entry(username.to_string()).or_default();
+            if let Some(duration) = record(attempts, config, current_time) {
+                log::warn!("'{}' has been locked out for {}s after {} failed login attempts",
+                    username, duration, attempts.failures);
+            }
+        }
+        if config.ban_threshold > 0 && ip_failures >= config.ban_threshold {
+            log::error!("{} has been banned after {} failed login attempts", ip, ip_failures);
+            self.by_ip.lock().unwrap().remove(ip);
+            let mut banned = self.banned.lock().unwrap();
+            banned.push(ip.to_string());
+            storage::save_json(&self.banned_file, &*banned);
+        }
+    }
+
+    /// Lifts the ban on an IP address.
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - IP address of the client.
+    ///
+    /// # Returns
+    ///
+    /// Returns a boolean flag to indicate whether the IP address was banned.
+    pub fn unban(&self, ip: &str) -> bool {
+        let mut banned = self.banned.lock().unwrap();
+        let count = banned.len();
+        banned.retain(|banned| banned != ip);
+        if banned.len() == count {
+            return false;
+        }
+        storage::save_json(&self.banned_file, &*banned);
+        true
+    }
+
+    /// Resets the failed login attempts for the IP address and the username after a successful login.
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - IP address of the client.
+    /// * `username` - Username of the authenticated user.
+    pub fn succeeded(&self, ip: &str, username: &str) {
+        self.by_ip.lock().unwrap().remove(ip);
+        self.by_user.lock().unwrap().remove(username);
+    }
+}
@@ -10,7 +10,7 @@ pub mod custom;
 pub mod ascii_art;
 /// Module for the CORS middleware configuration.
 pub mod middleware;
-/// Module for the function that converts the subtitles from `srt` to `vtt` file format.
+/// Module for the functions that decode the subtitles from `srt`, `ass` and `ssa`, and convert them to `vtt` file format in the cache.
 pub mod subtitles;
 /// Module for the functions that scans the source and renders the filenames as a struct.
 pub mod content;
@@ -18,3 +18,27 @@ pub mod content;
 pub mod authenticator;
 /// Module that handles parsing command line arguments.
 pub mod parser;
+/// Module for the backends that persist data beyond the lifetime of the server.
+pub mod storage;
+/// Module that tracks the failed login attempts to lock out brute-force attacks.
+pub mod lockout;
+/// Module that signs and tracks the expiring share links for media files.
+pub mod share;
+/// Module for the long-lived API tokens used by the non-browser clients.
+pub mod tokens;
+/// Module for the functions that transcode the videos into adaptive streams using `ffmpeg`.
+pub mod transcode;
+/// Module that extracts and caches the metadata of the media files using `ffprobe`.
+pub mod probe;
+/// Module that generates and caches the thumbnails for the media files using `ffmpeg`.
+pub mod thumbnail;
+/// Module that extracts the embedded subtitles and remuxes the audio tracks of the media files using `ffmpeg`.
+pub mod extract;
+/// Module that stores the playback positions and the watch history of each user.
+pub mod progress;
+/// Module that stores the subtitle preferences of each user.
+pub mod preferences;
+/// Module for the in-memory index of the media files in the library.
+pub mod index;
+/// Module that tokenizes and ranks the media files for the search.
+pub mod search;
@@ -1,7 +1,8 @@
 use std::env;
+use std::io::{self, BufRead, Write};
 use std::process::exit;
 
-use crate::constant;
+use crate::{constant, squire};
 
 /// Parses and returns the command-line arguments.
 ///
@@ -19,8 +20,9 @@ pub fn arguments(metadata: &constant::MetaData) -> String {
     while i < args.len() {
         match args[i].as_str() {
             "-h" | "--help" => {
-                let helper = "RuStream takes the arguments, --env_file and --version/-v\n\n\
+                let helper = "RuStream takes the arguments, --env_file, --hash and --version/-v\n\n\
                 --env_file: Custom filename to load the environment variables. Defaults to '.env'\n\
+                --hash: Generate the password hash for a username, to be stored in 'authorization'.\n\
                 --version: Get the package version.\n".to_string();
                 println!("Usage: {} [OPTIONS]\n\n{}", args[0], helper);
                 exit(0)
@@ -37,6 +39,15 @@ pub fn arguments(metadata: &constant::MetaData) -> String {
                     exit(1)
                 }
             }
+            "--hash" => {
+                i += 1; // Move to the next argument.
+                if i < args.len() {
+                    hash(&args[i]);
+                } else {
+                    println!("--hash requires a username.");
+                    exit(1)
+                }
+            }
             _ => {
                 println!("Unknown argument: {}", args[i]);
                 exit(1)
@@ -50,3 +61,30 @@ pub fn arguments(metadata: &constant::MetaData) -> String {
     }
     env_file
 }
+
+/// Prompts for a password and prints the hash, that can be stored in the `authorization` map.
+///
+/// # Arguments
+///
+/// * `username` - Username for which the password hash has to be generated.
+///
+/// ## See Also
+///
+/// - The password is read from the standard input, so it doesn't end up in the shell history.
+/// - The hash is bound to the username, so it has to be regenerated when the username changes.
+fn hash(username: &str) -> ! {
+    print!("Password for '{}': ", username);
+    io::stdout().flush().unwrap();
+    let mut password = String::new();
+    if let Err(err) = io::stdin().lock().read_line(&mut password) {
+        println!("\nFailed to read password: {}", err);
+        exit(1)
+    }
+    let password = password.trim_end_matches(['\r', '\n']);
+    if password.len() < 8 {
+        println!("Password should be at least 8 or more characters.");
+        exit(1)
+    }
+    println!("{}", squire::secure::hash_password(username, password));
+    exit(0)
+}
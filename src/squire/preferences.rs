@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::squire::{settings, storage, subtitles};
+
+/// Font sizes of the subtitles as a percentage, that are supported by the player.
+pub const FONT_SIZES: [u32; 9] = [50, 75, 100, 125, 150, 175, 200, 300, 400];
+
+/// Struct to store the subtitle preferences of a user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubtitlePreferences {
+    /// Default offset of the subtitles in milliseconds, positive values delay the subtitles.
+    pub offset: i64,
+    /// Font size of the subtitles as a percentage.
+    pub font_size: u32,
+    /// ISO 639-1 code of the preferred language for the subtitles.
+    pub language: Option<String>,
+}
+
+impl Default for SubtitlePreferences {
+    fn default() -> Self {
+        SubtitlePreferences {
+            offset: 0,
+            font_size: 100,
+            language: None,
+        }
+    }
+}
+
+impl SubtitlePreferences {
+    /// Validates the preferences, and normalizes the language into the ISO 639-1 code.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the validated `SubtitlePreferences`, or an error message.
+    pub fn validate(self) -> Result<SubtitlePreferences, String> {
+        if self.offset.abs() > subtitles::MAX_OFFSET {
+            return Err(format!("Offset must be within {} milliseconds", subtitles::MAX_OFFSET));
+        }
+        if !FONT_SIZES.contains(&self.font_size) {
+            return Err(format!("Font size must be one of {:?}", FONT_SIZES));
+        }
+        let language = match self.language.as_deref().map(str::trim).filter(|language| !language.is_empty()) {
+            Some(language) => match subtitles::language(language) {
+                Some((code, _)) => Some(code.to_string()),
+                None => return Err(format!("Language '{}' is not supported", language))
+            },
+            None => None
+        };
+        Ok(SubtitlePreferences { language, ..self })
+    }
+
+    /// Lists the languages for the subtitles, with the preferred language ahead of the ones from the browser.
+    ///
+    /// # Arguments
+    ///
+    /// * `accepted` - ISO 639-1 codes of the languages from the `Accept-Language` header.
+    pub fn languages(&self, accepted: Vec<String>) -> Vec<String> {
+        let mut languages: Vec<String> = self.language.iter().cloned().collect();
+        languages.extend(accepted.into_iter().filter(|language| self.language.as_ref() != Some(language)));
+        languages
+    }
+}
+
+/// Struct to store the preferences of each user, persisted in the `storage` directory.
+///
+/// ## Fields
+///
+/// * `entries` - Subtitle preferences keyed by the username.
+/// * `filepath` - JSON file where the preferences are stored.
+pub struct Preferences {
+    entries: Mutex<HashMap<String, SubtitlePreferences>>,
+    filepath: PathBuf,
+}
+
+/// Instantiates the `Preferences` struct with the preferences restored from the `storage` directory.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+///
+/// # Returns
+///
+/// Returns the constructed `Arc` for the `Preferences` struct.
+pub fn preferences_info(config: &settings::Config) -> Arc<Preferences> {
+    let filepath = config.storage.join("preferences.json");
+    let entries: HashMap<String, SubtitlePreferences> = storage::load_json(&filepath);
+    Arc::new(Preferences {
+        entries: Mutex::new(entries),
+        filepath,
+    })
+}
+
+impl Preferences {
+    /// Retrieves the subtitle preferences of the user.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username of the user.
+    ///
+    /// # Returns
+    ///
+    /// Returns the stored `SubtitlePreferences`, or the defaults if the user hasn't stored any.
+    pub fn subtitles(&self, username: &str) -> SubtitlePreferences {
+        self.entries.lock().unwrap()
+            .get(username)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Stores the subtitle preferences of the user.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username of the user.
+    /// * `preferences` - Validated subtitle preferences.
+    pub fn update(&self, username: &str, preferences: SubtitlePreferences) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(username.to_string(), preferences);
+        storage::save_json(&self.filepath, &*entries);
+    }
+}
@@ -0,0 +1,390 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::squire::{settings, storage, subtitles, transcode};
+
+/// Version of the probed metadata, bumped whenever `MediaInfo` gains a field so the cached entries are probed again.
+const CACHE_VERSION: u32 = 1;
+
+/// Maximum number of media files waiting to be probed, further files are dropped until they are listed again.
+const MAX_QUEUE: usize = 256;
+
+/// Subtitle codecs that are text based, and can be extracted to WebVTT.
+const TEXT_SUBTITLE_CODECS: [&str; 6] = ["subrip", "ass", "ssa", "webvtt", "mov_text", "text"];
+
+/// Represents an audio track within a media file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioTrack {
+    /// Index of the stream within the container.
+    pub index: u32,
+    /// Name of the audio codec.
+    pub codec: String,
+    /// Number of audio channels.
+    pub channels: u32,
+    /// Language tag of the track, if available.
+    pub language: Option<String>,
+    /// Title of the track, if available.
+    pub title: Option<String>,
+}
+
+impl AudioTrack {
+    /// Returns the label of the track with the title or language, codec and channels, e.g. `English (AC3 5.1)`
+    pub fn label(&self) -> String {
+        let name = self.title.clone()
+            .or_else(|| self.language.as_deref().and_then(subtitles::language).map(|(_, name)| name.to_string()))
+            .or_else(|| self.language.clone().filter(|language| language != "und"))
+            .unwrap_or_else(|| format!("Track {}", self.index));
+        let channels = match self.channels {
+            0 => String::new(),
+            1 => " Mono".to_string(),
+            2 => " Stereo".to_string(),
+            6 => " 5.1".to_string(),
+            8 => " 7.1".to_string(),
+            channels => format!(" {}ch", channels),
+        };
+        format!("{} ({}{})", name, self.codec.to_uppercase(), channels)
+    }
+}
+
+/// Represents a subtitle stream within a media file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubtitleStream {
+    /// Index of the stream within the container.
+    pub index: u32,
+    /// Name of the subtitle codec.
+    pub codec: String,
+    /// Language tag of the stream, if available.
+    pub language: Option<String>,
+    /// Title of the stream, if available.
+    pub title: Option<String>,
+    /// Boolean flag to indicate if the stream only has the forced subtitles.
+    pub forced: bool,
+    /// Boolean flag to indicate if the stream is meant for the hearing impaired.
+    pub sdh: bool,
+}
+
+impl SubtitleStream {
+    /// Returns a boolean flag to indicate if the stream is text based, so it can be converted to WebVTT.
+    ///
+    /// Bitmap subtitles (e.g. PGS and VobSub) would have to be rendered with OCR, so they are skipped.
+    pub fn is_text(&self) -> bool {
+        TEXT_SUBTITLE_CODECS.contains(&self.codec.as_str())
+    }
+}
+
+/// Represents the container metadata of a media file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MediaInfo {
+    /// Name of the container format.
+    pub container: String,
+    /// Duration of the media in seconds.
+    pub duration: f64,
+    /// Overall bitrate in bits per second.
+    pub bitrate: u64,
+    /// Width of the first video stream.
+    pub width: u32,
+    /// Height of the first video stream.
+    pub height: u32,
+    /// Name of the codec for the first video stream.
+    pub video_codec: Option<String>,
+    /// Audio tracks available in the media file.
+    pub audio_tracks: Vec<AudioTrack>,
+    /// Subtitle streams available in the media file.
+    #[serde(default)]
+    pub subtitle_streams: Vec<SubtitleStream>,
+    /// Version of the metadata, entries cached by an older version are discarded and probed again.
+    #[serde(default)]
+    pub version: u32,
+}
+
+impl MediaInfo {
+    /// Returns the duration formatted as `H:MM:SS` or `M:SS`.
+    pub fn duration_str(&self) -> String {
+        let total = self.duration.round() as u64;
+        let (hours, minutes, seconds) = (total / 3600, (total % 3600) / 60, total % 60);
+        if hours > 0 {
+            format!("{}:{:02}:{:02}", hours, minutes, seconds)
+        } else {
+            format!("{}:{:02}", minutes, seconds)
+        }
+    }
+
+    /// Returns the resolution formatted as `WIDTHxHEIGHT`, empty if there is no video stream.
+    pub fn resolution(&self) -> String {
+        if self.width == 0 || self.height == 0 {
+            return String::new();
+        }
+        format!("{}x{}", self.width, self.height)
+    }
+
+    /// Returns a single line summary with the resolution, codecs and duration.
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        let resolution = self.resolution();
+        if !resolution.is_empty() {
+            parts.push(resolution);
+        }
+        let mut codecs = Vec::new();
+        if let Some(video_codec) = &self.video_codec {
+            codecs.push(video_codec.to_uppercase());
+        }
+        if let Some(audio) = self.audio_tracks.first() {
+            codecs.push(audio.codec.to_uppercase());
+        }
+        if !codecs.is_empty() {
+            parts.push(codecs.join(" / "));
+        }
+        if self.audio_tracks.len() > 1 {
+            parts.push(format!("{} audio tracks", self.audio_tracks.len()));
+        }
+        if self.duration > 0.0 {
+            parts.push(self.duration_str());
+        }
+        parts.join(" · ")
+    }
+}
+
+/// Struct to probe the media files with `ffprobe` and cache the results.
+///
+/// ## Fields
+///
+/// * `enabled` - Boolean flag to indicate if `ffprobe` is available.
+/// * `ffprobe` - Path to the `ffprobe` binary.
+/// * `cache` - Probed metadata keyed by the path and modified time of the media file.
+/// * `pending` - Relative paths of the media files that are queued or being probed in the background.
+/// * `queue` - Media files waiting for the worker, as the true path and the path relative to `media_source`.
+/// * `queued` - Condition variable to wake up the worker, when a media file is queued.
+/// * `dirty` - Boolean flag to indicate if the cache has changed since it was last written.
+/// * `flushing` - Lock held while the cache is written, so an older snapshot cannot replace a newer one.
+/// * `filepath` - JSON file where the probed metadata is cached.
+pub struct Prober {
+    pub enabled: bool,
+    ffprobe: PathBuf,
+    cache: Mutex<HashMap<String, MediaInfo>>,
+    pending: Mutex<HashSet<String>>,
+    queue: Mutex<VecDeque<(PathBuf, String)>>,
+    queued: Condvar,
+    dirty: AtomicBool,
+    flushing: Mutex<()>,
+    filepath: PathBuf,
+}
+
+/// Instantiates the `Prober` struct with the cached metadata, after verifying that `ffprobe` can be executed.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+///
+/// ## See Also
+///
+/// - Media files from the listings are probed one after the other by a single worker thread.
+/// - Probed metadata is written to the disk in batches by a background thread.
+/// - Entries cached by an older `CACHE_VERSION` are discarded, so those files are probed again.
+///
+/// # Returns
+///
+/// Returns the constructed `Arc` for the `Prober` struct.
+pub fn prober_info(config: &settings::Config) -> Arc<Prober> {
+    let enabled = transcode::available(&config.ffprobe, "media metadata will not be available", log::Level::Info);
+    let filepath = config.cache_dir.join("probe.json");
+    let mut cache: HashMap<String, MediaInfo> = if enabled { storage::load_json(&filepath) } else { HashMap::new() };
+    cache.retain(|_, info| info.version == CACHE_VERSION);
+    let prober = Arc::new(Prober {
+        enabled,
+        ffprobe: config.ffprobe.clone(),
+        cache: Mutex::new(cache),
+        pending: Mutex::new(HashSet::new()),
+        queue: Mutex::new(VecDeque::new()),
+        queued: Condvar::new(),
+        dirty: AtomicBool::new(false),
+        flushing: Mutex::new(()),
+        filepath,
+    });
+    if enabled {
+        let flusher = prober.clone();
+        storage::periodically(storage::FLUSH_INTERVAL, move || flusher.flush());
+        let worker = prober.clone();
+        std::thread::spawn(move || worker.work());
+    }
+    prober
+}
+
+/// Parses the JSON output from `ffprobe` into the `MediaInfo` struct.
+///
+/// # Arguments
+///
+/// * `output` - JSON output from `ffprobe` with the format and streams.
+///
+/// # Returns
+///
+/// Returns the parsed `MediaInfo`.
+fn parse(output: &Value) -> MediaInfo {
+    let format = &output["format"];
+    let as_number = |value: &Value| -> Option<f64> {
+        value.as_f64().or_else(|| value.as_str().and_then(|value| value.parse().ok()))
+    };
+    let as_string = |value: &Value| value.as_str().map(|value| value.to_string());
+    let mut info = MediaInfo {
+        container: format["format_name"].as_str().unwrap_or_default().to_string(),
+        duration: as_number(&format["duration"]).unwrap_or_default(),
+        bitrate: as_number(&format["bit_rate"]).unwrap_or_default() as u64,
+        version: CACHE_VERSION,
+        ..Default::default()
+    };
+    for stream in output["streams"].as_array().into_iter().flatten() {
+        match stream["codec_type"].as_str() {
+            // Cover arts are stored as video streams with a single frame, so they are skipped
+            Some("video") if info.video_codec.is_none() && stream["disposition"]["attached_pic"] != 1 => {
+                info.video_codec = as_string(&stream["codec_name"]);
+                info.width = stream["width"].as_u64().unwrap_or_default() as u32;
+                info.height = stream["height"].as_u64().unwrap_or_default() as u32;
+            }
+            Some("audio") => info.audio_tracks.push(AudioTrack {
+                index: stream["index"].as_u64().unwrap_or_default() as u32,
+                codec: stream["codec_name"].as_str().unwrap_or_default().to_string(),
+                channels: stream["channels"].as_u64().unwrap_or_default() as u32,
+                language: as_string(&stream["tags"]["language"]),
+                title: as_string(&stream["tags"]["title"]),
+            }),
+            Some("subtitle") => info.subtitle_streams.push(SubtitleStream {
+                index: stream["index"].as_u64().unwrap_or_default() as u32,
+                codec: stream["codec_name"].as_str().unwrap_or_default().to_string(),
+                language: as_string(&stream["tags"]["language"]),
+                title: as_string(&stream["tags"]["title"]),
+                forced: stream["disposition"]["forced"] == 1,
+                sdh: stream["disposition"]["hearing_impaired"] == 1,
+            }),
+            _ => {}
+        }
+    }
+    info
+}
+
+impl Prober {
+    /// Looks up the cached metadata for the media file, without probing.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepath` - True path of the media file.
+    /// * `relative_path` - Path of the media file, relative to `media_source`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the cached `MediaInfo` if the file was probed since it was last modified.
+    pub fn cached(&self, filepath: &Path, relative_path: &str) -> Option<MediaInfo> {
+        let key = transcode::cache_key(filepath, relative_path)?;
+        self.cache.lock().unwrap().get(&key).cloned()
+    }
+
+    /// Probes the media file, unless the metadata is already cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepath` - True path of the media file.
+    /// * `relative_path` - Path of the media file, relative to `media_source`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `MediaInfo` if `ffprobe` is available and the file could be probed.
+    pub fn probe(&self, filepath: &Path, relative_path: &str) -> Option<MediaInfo> {
+        if !self.enabled {
+            return None;
+        }
+        let key = transcode::cache_key(filepath, relative_path)?;
+        if let Some(info) = self.cache.lock().unwrap().get(&key) {
+            return Some(info.clone());
+        }
+        let output = Command::new(&self.ffprobe)
+            .args(["-v", "error", "-print_format", "json", "-show_format", "-show_streams"])
+            .arg(filepath)
+            .stdin(Stdio::null())
+            .output();
+        let output = match output {
+            Ok(output) if output.status.success() => output,
+            Ok(output) => {
+                log::warn!("Failed to probe {:?}: {}", filepath, String::from_utf8_lossy(&output.stderr).trim());
+                return None;
+            }
+            Err(err) => {
+                log::error!("Failed to run {:?}: {}", self.ffprobe, err);
+                return None;
+            }
+        };
+        let info = match serde_json::from_slice::<Value>(&output.stdout) {
+            Ok(value) => parse(&value),
+            Err(err) => {
+                log::error!("Failed to parse the probe output for {:?}: {}", filepath, err);
+                return None;
+            }
+        };
+        self.cache.lock().unwrap().insert(key, info.clone());
+        self.dirty.store(true, Ordering::Relaxed);
+        Some(info)
+    }
+
+    /// Writes the cached metadata to the disk, if it has changed since it was last written.
+    pub fn flush(&self) {
+        let _flushing = self.flushing.lock().unwrap();
+        if self.dirty.swap(false, Ordering::Relaxed) {
+            let cache = self.cache.lock().unwrap().clone();
+            storage::save_json(&self.filepath, &cache);
+        }
+    }
+
+    /// Queues the media files to be probed in the background, so the listings are not blocked by `ffprobe`.
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - Tuples of the true path and the path relative to `media_source` for each media file.
+    ///
+    /// ## See Also
+    ///
+    /// - Files that are already queued by an earlier listing are skipped, so they are probed only once.
+    /// - Files beyond `MAX_QUEUE` are dropped, and queued again when they are listed after the queue drains.
+    pub fn schedule(&self, files: Vec<(PathBuf, String)>) {
+        if !self.enabled {
+            return;
+        }
+        let mut pending = self.pending.lock().unwrap();
+        let mut queue = self.queue.lock().unwrap();
+        let mut dropped = 0;
+        for (filepath, relative_path) in files {
+            if pending.contains(&relative_path) {
+                continue;
+            }
+            if queue.len() >= MAX_QUEUE {
+                dropped += 1;
+                continue;
+            }
+            pending.insert(relative_path.to_string());
+            queue.push_back((filepath, relative_path));
+        }
+        if dropped > 0 {
+            log::warn!("Unable to queue {} media files for probing, {} files are already waiting", dropped, queue.len());
+        }
+        self.queued.notify_one();
+    }
+
+    /// Probes the queued media files one after the other, waiting for a file when the queue is empty.
+    fn work(&self) {
+        loop {
+            let (filepath, relative_path) = {
+                let mut queue = self.queue.lock().unwrap();
+                loop {
+                    match queue.pop_front() {
+                        Some(next) => break next,
+                        None => queue = self.queued.wait(queue).unwrap()
+                    }
+                }
+            };
+            self.probe(&filepath, &relative_path);
+            self.pending.lock().unwrap().remove(&relative_path);
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::squire::{settings, storage};
+
+/// Minimum position in seconds, before a video is considered to be started.
+const MIN_POSITION: f64 = 5.0;
+
+/// Fraction of the duration after which a video is considered to be finished.
+const FINISHED: f64 = 0.95;
+
+/// Maximum number of entries in the watch history of each user.
+const MAX_HISTORY: usize = 200;
+
+/// Number of seconds within which reopening the same file doesn't add a new entry to the watch history.
+const REVISIT_WINDOW: i64 = 3600;
+
+/// Struct to store the playback position of a media file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchProgress {
+    /// Last reported position in seconds.
+    pub position: f64,
+    /// Duration of the media in seconds, as reported by the player.
+    pub duration: f64,
+    /// Epoch time when the position was last reported.
+    pub updated: i64,
+}
+
+impl WatchProgress {
+    /// Returns the completion as a percentage of the duration.
+    pub fn completion(&self) -> f64 {
+        if self.duration <= 0.0 {
+            return 0.0;
+        }
+        (self.position / self.duration * 100.0).clamp(0.0, 100.0)
+    }
+
+    /// Returns a boolean flag to indicate if the playback can be resumed.
+    ///
+    /// Videos that were barely started or almost finished are not resumed.
+    pub fn resumable(&self) -> bool {
+        self.position >= MIN_POSITION && (self.duration <= 0.0 || self.position < self.duration * FINISHED)
+    }
+
+    /// Returns the position formatted as `H:MM:SS` or `M:SS`.
+    pub fn position_str(&self) -> String {
+        let total = self.position as u64;
+        let (hours, minutes, seconds) = (total / 3600, (total % 3600) / 60, total % 60);
+        if hours > 0 {
+            format!("{}:{:02}:{:02}", hours, minutes, seconds)
+        } else {
+            format!("{}:{:02}", minutes, seconds)
+        }
+    }
+}
+
+/// Struct to store an entry in the watch history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Path of the media file, relative to `media_source`.
+    pub path: String,
+    /// Epoch time when the media file was opened.
+    pub watched: i64,
+    /// Completion as a percentage of the duration, updated as the player reports the position.
+    pub completion: f64,
+}
+
+/// Struct to store the playback positions and the watch history, persisted in the `storage` directory.
+///
+/// ## Fields
+///
+/// * `entries` - Playback positions keyed by the username, and then by the path relative to `media_source`.
+/// * `filepath` - JSON file where the playback positions are stored.
+/// * `dirty` - Boolean flag to indicate if the playback positions have changed since they were last written.
+/// * `history` - Watch history keyed by the username, in the order the media files were opened.
+/// * `history_path` - JSON file where the watch history is stored.
+/// * `history_dirty` - Boolean flag to indicate if the watch history has changed since it was last written.
+pub struct Progress {
+    entries: Mutex<HashMap<String, HashMap<String, WatchProgress>>>,
+    filepath: PathBuf,
+    dirty: AtomicBool,
+    history: Mutex<HashMap<String, Vec<HistoryEntry>>>,
+    history_path: PathBuf,
+    history_dirty: AtomicBool,
+}
+
+/// Instantiates the `Progress` struct with the playback positions and history restored from the `storage` directory.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+///
+/// # Returns
+///
+/// Returns the constructed `Arc` for the `Progress` struct.
+pub fn progress_info(config: &settings::Config) -> Arc<Progress> {
+    let filepath = config.storage.join("progress.json");
+    let entries: HashMap<String, HashMap<String, WatchProgress>> = storage::load_json(&filepath);
+    let history_path = config.storage.join("history.json");
+    let history: HashMap<String, Vec<HistoryEntry>> = storage::load_json(&history_path);
+    let progress = Arc::new(Progress {
+        entries: Mutex::new(entries),
+        filepath,
+        dirty: AtomicBool::new(false),
+        history: Mutex::new(history),
+        history_path,
+        history_dirty: AtomicBool::new(false),
+    });
+    // Positions are reported every few seconds, so the files are written in batches instead of per report
+    let flusher = progress.clone();
+    storage::periodically(storage::FLUSH_INTERVAL, move || flusher.flush());
+    progress
+}
+
+impl Progress {
+    /// Stores the playback position reported by the player.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username of the user watching the media.
+    /// * `path` - Path of the media file, relative to `media_source`.
+    /// * `position` - Current position in seconds.
+    /// * `duration` - Duration of the media in seconds.
+    pub fn update(&self, username: &str, path: &str, position: f64, duration: f64) {
+        let watched = WatchProgress {
+            position,
+            duration,
+            updated: Utc::now().timestamp(),
+        };
+        let completion = watched.completion();
+        let mut entries = self.entries.lock().unwrap();
+        entries.entry(username.to_string()).or_default().insert(path.to_string(), watched);
+        drop(entries);
+        self.dirty.store(true, Ordering::Relaxed);
+        let mut history = self.history.lock().unwrap();
+        if let Some(entry) = history.get_mut(username)
+            .and_then(|entries| entries.iter_mut().rev().find(|entry| entry.path == path)) {
+            entry.completion = completion;
+            self.history_dirty.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Writes the playback positions and the watch history to the disk, if they have changed since they were last written.
+    pub fn flush(&self) {
+        if self.dirty.swap(false, Ordering::Relaxed) {
+            let entries = self.entries.lock().unwrap().clone();
+            storage::save_json(&self.filepath, &entries);
+        }
+        if self.history_dirty.swap(false, Ordering::Relaxed) {
+            let history = self.history.lock().unwrap().clone();
+            storage::save_json(&self.history_path, &history);
+        }
+    }
+
+    /// Adds the media file to the watch history of the user.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username of the user watching the media.
+    /// * `path` - Path of the media file, relative to `media_source`.
+    ///
+    /// ## See Also
+    ///
+    /// - Reopening the most recent file within an hour refreshes the timestamp, instead of adding a new entry.
+    pub fn record(&self, username: &str, path: &str) {
+        let current_time = Utc::now().timestamp();
+        let completion = self.entries.lock().unwrap()
+            .get(username)
+            .and_then(|files| files.get(path))
+            .map(|watched| watched.completion())
+            .unwrap_or_default();
+        let mut history = self.history.lock().unwrap();
+        let entries = history.entry(username.to_string()).or_default();
+        match entries.last_mut() {
+            Some(last) if last.path == path && current_time - last.watched < REVISIT_WINDOW => {
+                last.watched = current_time;
+            }
+            _ => entries.push(HistoryEntry {
+                path: path.to_string(),
+                watched: current_time,
+                completion,
+            })
+        }
+        if entries.len() > MAX_HISTORY {
+            let excess = entries.len() - MAX_HISTORY;
+            entries.drain(..excess);
+        }
+        self.history_dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// Lists the watch history of the user.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username of the user.
+    ///
+    /// # Returns
+    ///
+    /// Returns a vector of the `HistoryEntry`, most recently watched first.
+    pub fn history(&self, username: &str) -> Vec<HistoryEntry> {
+        self.history.lock().unwrap()
+            .get(username)
+            .map(|entries| entries.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Retrieves the playback position of a media file for the user.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username of the user watching the media.
+    /// * `path` - Path of the media file, relative to `media_source`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `WatchProgress` if the playback can be resumed.
+    pub fn resume(&self, username: &str, path: &str) -> Option<WatchProgress> {
+        self.entries.lock().unwrap()
+            .get(username)
+            .and_then(|files| files.get(path))
+            .filter(|progress| progress.resumable())
+            .cloned()
+    }
+
+    /// Lists the media files that were started but not finished by the user.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username of the user.
+    /// * `limit` - Maximum number of entries to return.
+    ///
+    /// # Returns
+    ///
+    /// Returns a vector of the relative paths and the `WatchProgress`, most recently watched first.
+    pub fn continue_watching(&self, username: &str, limit: usize) -> Vec<(String, WatchProgress)> {
+        let entries = self.entries.lock().unwrap();
+        let mut watching: Vec<(String, WatchProgress)> = entries.get(username)
+            .map(|files| files.iter()
+                .filter(|(_, progress)| progress.resumable())
+                .map(|(path, progress)| (path.clone(), progress.clone()))
+                .collect())
+            .unwrap_or_default();
+        watching.sort_by_key(|(_, progress)| std::cmp::Reverse(progress.updated));
+        watching.truncate(limit);
+        watching
+    }
+}
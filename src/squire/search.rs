@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use chrono::NaiveDate;
+use regex::Regex;
+
+use crate::squire::{authenticator, content, index, settings};
+
+/// Score for a query token that matches a filename token exactly.
+const EXACT_MATCH: u32 = 4;
+
+/// Score for a query token that matches the start of a filename token.
+const PREFIX_MATCH: u32 = 2;
+
+/// Score for a query token that is found anywhere else in the filename or the directory.
+const PARTIAL_MATCH: u32 = 1;
+
+/// Pre-compiled regex to split the words and the numbers, since the tokenizer runs for every file in the index.
+static WORDS_AND_NUMBERS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\D+|\d+)").unwrap());
+
+/// Represents the filters applied to a search.
+#[derive(Debug, Default)]
+pub struct SearchFilter {
+    /// Free text query, matched against the filename and the directory.
+    pub query: String,
+    /// File extensions to include, all extensions are included when empty.
+    pub extensions: Vec<String>,
+    /// Directory (relative to `media_source`) to search within, including the subdirectories.
+    pub directory: Option<String>,
+    /// Only include the files modified on or after this date.
+    pub after: Option<NaiveDate>,
+    /// Only include the files modified on or before this date.
+    pub before: Option<NaiveDate>,
+}
+
+/// Splits the text into lowercase tokens, separating the words and the numbers.
+///
+/// # Arguments
+///
+/// * `text` - Text to be tokenized, like a filename or a search query.
+///
+/// ## See Also
+///
+/// - `S01E02.Pilot` is tokenized as `s`, `01`, `e`, `02` and `pilot`
+///
+/// # Returns
+///
+/// Returns a vector of the tokens.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .flat_map(|word| WORDS_AND_NUMBERS.find_iter(word)
+            .map(|part| part.as_str().to_string())
+            .collect::<Vec<_>>())
+        .collect()
+}
+
+/// Scores a single query token against the tokens of a filename.
+///
+/// # Arguments
+///
+/// * `token` - Token from the search query.
+/// * `tokens` - Tokens from the filename.
+///
+/// # Returns
+///
+/// Returns the best score for the token, where numbers are compared by value so `1` matches `01`
+fn score_token(token: &str, tokens: &[String]) -> u32 {
+    let number = token.parse::<u64>().ok();
+    tokens.iter()
+        .map(|candidate| {
+            if candidate == token || (number.is_some() && candidate.parse::<u64>().ok() == number) {
+                EXACT_MATCH
+            } else if number.is_none() && candidate.starts_with(token) {
+                PREFIX_MATCH
+            } else {
+                0
+            }
+        })
+        .max()
+        .unwrap_or_default()
+}
+
+/// Scores an indexed file against the query, every token in the query has to match.
+///
+/// # Arguments
+///
+/// * `entry` - Indexed file.
+/// * `tokens` - Tokens from the search query.
+///
+/// # Returns
+///
+/// Returns the score if all the tokens matched, `None` otherwise.
+fn score(entry: &index::IndexEntry, tokens: &[String]) -> Option<u32> {
+    let name = entry.name.to_lowercase();
+    let directory = entry.directory.to_lowercase();
+    let mut total = 0;
+    for token in tokens {
+        let token_score = match score_token(token, &entry.tokens) {
+            0 if name.contains(token.as_str()) || directory.contains(token.as_str()) => PARTIAL_MATCH,
+            0 => return None,
+            token_score => token_score
+        };
+        total += token_score;
+    }
+    Some(total)
+}
+
+/// Converts the epoch time into a date, for the date filters.
+fn to_date(epoch: i64) -> Option<NaiveDate> {
+    chrono::DateTime::from_timestamp(epoch, 0).map(|datetime| datetime.date_naive())
+}
+
+/// Searches the library index for the files that match the filter, and are accessible to the user.
+///
+/// # Arguments
+///
+/// * `library` - Library index with the media files.
+/// * `filter` - Query and filters for the search.
+/// * `config` - Configuration data for the application.
+/// * `auth_response` - Authentication response of the current session.
+/// * `limit` - Maximum number of results to return.
+///
+/// ## See Also
+///
+/// - Results are ranked by the score, and then by the natural sort order of the filenames.
+/// - Files within the secure index of other users are skipped.
+///
+/// # Returns
+///
+/// A vector of the files with their names, paths, font icons, thumbnails and modified time.
+pub fn search(library: &index::Library,
+              filter: &SearchFilter,
+              config: &settings::Config,
+              auth_response: &authenticator::AuthToken,
+              limit: usize) -> Vec<HashMap<String, String>> {
+    let tokens = tokenize(&filter.query);
+    let directory = filter.directory.as_ref().map(|directory| directory.trim_matches('/').to_string());
+    let mut results: Vec<(u32, index::IndexEntry)> = library.files()
+        .into_iter()
+        .filter(|entry| filter.extensions.is_empty() || filter.extensions.contains(&entry.extension.to_lowercase()))
+        .filter(|entry| match &directory {
+            Some(directory) => directory.is_empty() || Path::new(&entry.directory).starts_with(directory),
+            None => true
+        })
+        .filter(|entry| match (filter.after, filter.before, to_date(entry.modified)) {
+            (Some(after), _, Some(modified)) if modified < after => false,
+            (_, Some(before), Some(modified)) if modified > before => false,
+            _ => true
+        })
+        .filter_map(|entry| score(&entry, &tokens).map(|score| (score, entry)))
+        .filter(|(_, entry)| {
+            let path = Path::new(&entry.path);
+            !authenticator::within_foreign_index(path, &auth_response.username)
+                && authenticator::verify_acl(path, &auth_response.username, config)
+        })
+        .collect();
+    results.sort_by(|(score_a, a), (score_b, b)| {
+        score_b.cmp(score_a)
+            .then_with(|| content::natural_sort_key(&WORDS_AND_NUMBERS, &a.name)
+                .cmp(&content::natural_sort_key(&WORDS_AND_NUMBERS, &b.name)))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    results.into_iter()
+        .take(limit)
+        .map(|(_, entry)| content::file_entry(&entry.path, &entry.extension, entry.modified))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(directory: &str, name: &str) -> index::IndexEntry {
+        index::IndexEntry {
+            name: name.to_string(),
+            path: Path::new(directory).join(name).to_string_lossy().to_string(),
+            directory: directory.to_string(),
+            extension: Path::new(name).extension().unwrap_or_default().to_string_lossy().to_string(),
+            size: 0,
+            modified: 0,
+            tokens: tokenize(Path::new(name).file_stem().unwrap_or_default().to_string_lossy().as_ref()),
+        }
+    }
+
+    #[test]
+    fn tokenizes_words_and_numbers() {
+        assert_eq!(tokenize("S01E02.Pilot"), vec!["s", "01", "e", "02", "pilot"]);
+        assert_eq!(tokenize("The_Matrix (1999) - 1080p"), vec!["the", "matrix", "1999", "1080", "p"]);
+        assert_eq!(tokenize("  --  "), Vec::<String>::new());
+    }
+
+    #[test]
+    fn scores_exact_prefix_and_numbers() {
+        let tokens = tokenize("Breaking.Bad.S01E02");
+        assert_eq!(score_token("breaking", &tokens), EXACT_MATCH);
+        assert_eq!(score_token("break", &tokens), PREFIX_MATCH);
+        assert_eq!(score_token("2", &tokens), EXACT_MATCH);
+        // Numbers are only compared by value, so `0` is not a prefix of `01`
+        assert_eq!(score_token("0", &tokens), 0);
+        assert_eq!(score_token("reaking", &tokens), 0);
+    }
+
+    #[test]
+    fn scores_substring_and_directory_matches() {
+        let tokens = tokenize("reaking");
+        assert_eq!(score(&entry("Shows", "Breaking.Bad.mp4"), &tokens), Some(PARTIAL_MATCH));
+        let tokens = tokenize("shows pilot");
+        assert_eq!(score(&entry("Shows", "Pilot.mp4"), &tokens), Some(PARTIAL_MATCH + EXACT_MATCH));
+    }
+
+    #[test]
+    fn requires_every_token_to_match() {
+        let file = entry("Shows", "Breaking.Bad.S01E02.mp4");
+        assert_eq!(score(&file, &tokenize("breaking bad")), Some(EXACT_MATCH * 2));
+        assert_eq!(score(&file, &tokenize("bre s1 e2")), Some(PREFIX_MATCH + EXACT_MATCH * 4));
+        assert_eq!(score(&file, &tokenize("breaking good")), None);
+        // Prefix matches outrank the substring matches
+        assert!(score(&file, &tokenize("bad")) > score(&file, &tokenize("ad")));
+    }
+}
@@ -2,9 +2,19 @@ extern crate base64;
 extern crate sha2;
 
 use base64::{Engine as _, engine::general_purpose::URL_SAFE};
+use hmac::{Hmac, Mac};
 use rand::{Rng, thread_rng};
 use sha2::{Digest, Sha512};
 
+/// Number of PBKDF2 iterations for the new hashes, as per the OWASP guidance for PBKDF2-HMAC-SHA512.
+pub const SCRAM_ITERATIONS: u32 = 210_000;
+
+/// Number of random bytes used as salt for the new hashes.
+const SALT_LENGTH: usize = 16;
+
+/// Prefix of the server key stored in the authorization map.
+const SCRAM_PREFIX: &str = "$scram-sha512$";
+
 /// Calculates the SHA-512 hash value for the given payload.
 ///
 /// # Arguments
@@ -127,3 +137,215 @@ pub fn keygen() -> String {
         .collect();
     token
 }
+
+/// Calculates the digest of the username and password, that the salted password is derived from.
+///
+/// # Arguments
+///
+/// * `username` - Username of the user.
+/// * `password` - Password in plain text.
+///
+/// # Returns
+///
+/// A hexadecimal string representing the SHA-512 hash of the hex encoded username and password.
+pub fn password_digest(username: &str, password: &str) -> String {
+    calculate_hash(format!("{}{}", hex_encode(username), hex_encode(password)))
+}
+
+/// Server key (`StoredKey` in SCRAM) along with the parameters it was derived with.
+pub struct ServerKey {
+    pub iterations: u32,
+    pub salt: String,
+    stored_key: Vec<u8>,
+}
+
+/// Checks if the stored value is a server key generated with `--hash`.
+///
+/// # Arguments
+///
+/// * `value` - Value stored in the authorization map.
+///
+/// # Returns
+///
+/// A boolean flag to indicate whether the value is hashed.
+pub fn is_hashed(value: &str) -> bool {
+    value.starts_with(SCRAM_PREFIX)
+}
+
+/// Parses the value stored in the authorization map, in the format `$scram-sha512$<iterations>$<salt>$<key>`.
+///
+/// # Arguments
+///
+/// * `value` - Value stored in the authorization map.
+///
+/// # Returns
+///
+/// An `Option` with the `ServerKey`, or `None` if the value is not a well-formed hash.
+pub fn parse_hash(value: &str) -> Option<ServerKey> {
+    let mut parts = value.strip_prefix(SCRAM_PREFIX)?.split('$');
+    let iterations = parts.next()?.parse::<u32>().ok().filter(|iterations| *iterations > 0)?;
+    let salt = parts.next().filter(|salt| bytes_from_hex(salt).is_some_and(|bytes| !bytes.is_empty()))?;
+    let stored_key = parts.next().and_then(bytes_from_hex).filter(|stored_key| stored_key.len() == 64)?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(ServerKey { iterations, salt: salt.to_string(), stored_key })
+}
+
+/// Checks if the stored value is a well-formed server key.
+///
+/// # Arguments
+///
+/// * `value` - Value stored in the authorization map.
+///
+/// # Returns
+///
+/// A boolean flag to indicate whether the value holds the iterations, the salt and a hex encoded key of 64 bytes.
+pub fn is_valid_hash(value: &str) -> bool {
+    parse_hash(value).is_some()
+}
+
+/// Calculates the HMAC-SHA512 of the message.
+///
+/// # Arguments
+///
+/// * `key` - Key for the HMAC.
+/// * `message` - Message to be signed.
+///
+/// # Returns
+///
+/// A vector of 64 bytes with the HMAC.
+fn hmac_sha512(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key)
+        .expect("HMAC can take key of any size");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Encodes bytes into a hexadecimal string.
+///
+/// # Arguments
+///
+/// * `bytes` - Bytes to be encoded.
+///
+/// # Returns
+///
+/// A lowercase hexadecimal string.
+fn bytes_to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decodes a hexadecimal string into bytes.
+///
+/// # Arguments
+///
+/// * `value` - Hexadecimal string.
+///
+/// # Returns
+///
+/// An `Option` with the decoded bytes, or `None` if the string is not valid hexadecimal.
+pub fn bytes_from_hex(value: &str) -> Option<Vec<u8>> {
+    if value.len() % 2 != 0 {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|idx| value.get(idx..idx + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
+
+/// Generates a decoy salt for usernames that are not in the authorization map.
+///
+/// # Arguments
+///
+/// * `secret` - Secret key of the server.
+/// * `username` - Username received in the challenge request.
+///
+/// # Returns
+///
+/// A hex encoded salt, that is stable for the username but cannot be told apart from a real one.
+///
+/// ## See Also
+///
+/// - Returning a stable salt for unknown users prevents the challenge endpoint from revealing the valid usernames.
+pub fn decoy_salt(secret: &str, username: &str) -> String {
+    let salt = hmac_sha512(secret.as_bytes(), format!("salt:{}", username).as_bytes());
+    bytes_to_hex(&salt[..SALT_LENGTH])
+}
+
+/// Calculates the server key (`StoredKey` in SCRAM), that is stored in place of the password.
+///
+/// # Arguments
+///
+/// * `username` - Username of the user.
+/// * `password` - Password in plain text.
+/// * `salt` - Random bytes used as the salt.
+/// * `iterations` - Number of PBKDF2 iterations.
+///
+/// # Returns
+///
+/// A vector of 64 bytes with the SHA-512 hash of the client key.
+///
+/// ## See Also
+///
+/// - The salted password is the PBKDF2-HMAC-SHA512 of the digest of username and password.
+/// - The client key is the HMAC of the salted password, and never leaves the browser in the clear.
+fn server_key(username: &str, password: &str, salt: &[u8], iterations: u32) -> Vec<u8> {
+    let mut salted = [0u8; 64];
+    pbkdf2::pbkdf2_hmac::<Sha512>(password_digest(username, password).as_bytes(), salt, iterations, &mut salted);
+    let client_key = hmac_sha512(&salted, b"Client Key");
+    Sha512::digest(client_key).to_vec()
+}
+
+/// Generates the server key for the password with a random salt, that can be stored in the authorization map.
+///
+/// # Arguments
+///
+/// * `username` - Username of the user.
+/// * `password` - Password in plain text.
+///
+/// # Returns
+///
+/// A string with the prefix, the number of iterations, the hex encoded salt and the hex encoded server key.
+pub fn hash_password(username: &str, password: &str) -> String {
+    let salt: [u8; SALT_LENGTH] = thread_rng().gen();
+    let stored_key = server_key(username, password, &salt, SCRAM_ITERATIONS);
+    format!("{}{}${}${}", SCRAM_PREFIX, SCRAM_ITERATIONS, bytes_to_hex(&salt), bytes_to_hex(&stored_key))
+}
+
+/// Verifies the proof received from the UI against the server key, in a SCRAM style challenge-response.
+///
+/// # Arguments
+///
+/// * `username` - Username of the user.
+/// * `stored` - Server key stored in the authorization map.
+/// * `proof` - Hex encoded client proof calculated by the UI.
+/// * `auth_message` - Message signed by the UI, that includes the timestamp and the nonce issued by the server.
+///
+/// ## See Also
+///
+/// - The proof is the client key XOR'ed with the HMAC of the auth message, keyed by the server key.
+/// - Recovering the client key from the proof and hashing it should yield the server key.
+/// - Since the auth message includes the one-time nonce, a captured proof cannot be replayed.
+///
+/// # Returns
+///
+/// A boolean flag to indicate whether the proof is valid.
+pub fn verify_password(username: &str, stored: &str, proof: &str, auth_message: &str) -> bool {
+    let stored_key = match parse_hash(stored) {
+        Some(server_key) => server_key.stored_key,
+        None => {
+            log::error!("Invalid password hash stored for '{}'", username);
+            return false;
+        }
+    };
+    let proof = match bytes_from_hex(proof) {
+        Some(proof) if proof.len() == stored_key.len() => proof,
+        _ => return false
+    };
+    let signature = hmac_sha512(&stored_key, auth_message.as_bytes());
+    let client_key: Vec<u8> = proof.iter().zip(&signature).map(|(byte, sign)| byte ^ sign).collect();
+    let calculated = Sha512::digest(client_key);
+    // Constant time comparison, so the server key cannot be guessed byte by byte
+    calculated.iter().zip(&stored_key).fold(0u8, |diff, (left, right)| diff | (left ^ right)) == 0
+}
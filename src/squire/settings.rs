@@ -2,10 +2,51 @@ use std::{path, thread};
 use std::collections::HashMap;
 use std::net::ToSocketAddrs;
 
+use fernet::Fernet;
+use serde::{Deserialize, Serialize};
+
+/// Represents the backends that are supported to store the session information.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionBackend {
+    /// Sessions are stored only in memory and lost on every restart.
+    Memory,
+    /// Sessions are stored in a JSON file within the `storage` directory.
+    File,
+}
+
+/// Represents the roles that can be assigned to a user, in the increasing order of permissions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Can only browse and stream the content.
+    Viewer,
+    /// Can also upload files.
+    Uploader,
+    /// Can also rename and delete files and directories.
+    Admin,
+}
+
+impl Role {
+    /// Returns the role as a lowercase string, to be used in the templates.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Uploader => "uploader",
+            Role::Admin => "admin",
+        }
+    }
+}
+
 /// Represents the configuration parameters for RuStream.
 pub struct Config {
     /// Dictionary of key-value pairs for authorization (username and password).
     pub authorization: HashMap<String, String>,
+    /// Dictionary of key-value pairs for the role assigned to each user.
+    pub roles: HashMap<String, Role>,
+    /// Dictionary of key-value pairs for group names and the users in each group.
+    pub groups: HashMap<String, Vec<String>>,
+    /// Dictionary of key-value pairs for paths (relative to `media_source`) and the users or `@groups` allowed.
+    pub acl: HashMap<String, Vec<String>>,
     /// Source path for media files.
     pub media_source: path::PathBuf,
 
@@ -19,6 +60,16 @@ pub struct Config {
     pub media_port: u16,
     /// Duration of a session in seconds.
     pub session_duration: i64,
+    /// Time window in seconds, within which a login request and its challenge are considered fresh.
+    pub login_window: i64,
+    /// Number of failed login attempts allowed before a temporary lockout.
+    pub max_login_attempts: usize,
+    /// Duration of the first lockout in seconds, which doubles for every subsequent failure.
+    pub lockout_duration: i64,
+    /// Number of failed login attempts from an IP address before it is banned forever. Zero disables the ban.
+    pub ban_threshold: usize,
+    /// IP addresses of the reverse proxies, whose `X-Forwarded-For` and `Forwarded` headers are trusted.
+    pub trusted_proxies: Vec<String>,
     /// List of supported file formats.
     pub file_formats: Vec<String>,
 
@@ -38,8 +89,53 @@ pub struct Config {
     pub key_file: path::PathBuf,
     /// Path to the full certificate chain file for SSL certificate
     pub cert_file: path::PathBuf,
+
+    /// Fernet key to encrypt and decrypt the session token.
+    pub secret_key: String,
+    /// Backend to store the session information.
+    pub session_backend: SessionBackend,
+    /// Directory to store the persistent data generated by the server.
+    pub storage: path::PathBuf,
+
+    /// Boolean flag to enable HLS adaptive streaming by transcoding the videos with `ffmpeg`.
+    pub hls: bool,
+    /// Boolean flag to enable MPEG-DASH adaptive streaming by transcoding the videos with `ffmpeg`.
+    pub dash: bool,
+    /// Path to the `ffmpeg` binary used for transcoding.
+    pub ffmpeg: path::PathBuf,
+    /// Maximum number of `ffmpeg` transcoding jobs that can run at the same time.
+    pub max_transcodes: usize,
+    /// Number of seconds after which a transcoding job is killed, if its output is not requested.
+    pub transcode_timeout: i64,
+    /// Maximum size of the transcoded segments in bytes, beyond which the least recently used are removed.
+    pub transcode_cache_size: usize,
+    /// Maximum number of `ffmpeg` jobs that extract the subtitles or remux the audio tracks, and that generate the seek-previews.
+    pub max_extractions: usize,
+    /// Maximum size of the remuxed audio tracks in bytes, beyond which the least recently used are removed.
+    pub remux_cache_size: usize,
+    /// Path to the `ffprobe` binary used to extract the metadata of the media files.
+    pub ffprobe: path::PathBuf,
+    /// Directory to cache the content generated by the server, like the transcoded segments.
+    pub cache_dir: path::PathBuf,
+    /// Directory to cache the subtitles converted to WebVTT, so the media source is never written to.
+    pub subtitle_cache: path::PathBuf,
 }
 
+impl Config {
+    /// Returns the role assigned to the user, defaults to `viewer` for the users that are not listed.
+    pub fn role(&self, username: &str) -> Role {
+        self.roles.get(username).copied().unwrap_or(Role::Viewer)
+    }
+}
+
+/// Returns the default roles, which makes every user an admin to retain the permissions prior to roles.
+pub fn default_roles(authorization: &HashMap<String, String>) -> HashMap<String, Role> {
+    authorization.keys().map(|username| (username.to_string(), Role::Admin)).collect()
+}
+
+/// Returns an empty dictionary as the default for groups and access control lists.
+pub fn default_access() -> HashMap<String, Vec<String>> { HashMap::new() }
+
 /// Returns the default value for debug flag.
 pub fn default_debug() -> bool { false }
 
@@ -71,6 +167,21 @@ pub fn default_media_port() -> u16 { 8000 }
 /// Returns the default session duration (3600 seconds)
 pub fn default_session_duration() -> i64 { 3600 }
 
+/// Returns the default login window (30 seconds)
+pub fn default_login_window() -> i64 { 30 }
+
+/// Returns the default number of failed login attempts allowed before a lockout (3)
+pub fn default_max_login_attempts() -> usize { 3 }
+
+/// Returns the default duration of the first lockout (60 seconds)
+pub fn default_lockout_duration() -> i64 { 60 }
+
+/// Returns the default number of failed login attempts before an IP address is banned (10)
+pub fn default_ban_threshold() -> usize { 10 }
+
+/// Returns an empty list as the default trusted proxies, so the forwarded headers are ignored
+pub fn default_trusted_proxies() -> Vec<String> { Vec::new() }
+
 /// Returns the file formats supported by default.
 pub fn default_file_formats() -> Vec<String> {
     vec!["mp4".to_string(), "mov".to_string(), "jpg".to_string(), "jpeg".to_string()]
@@ -99,3 +210,46 @@ pub fn default_websites() -> Vec<String> { Vec::new() }
 
 /// Returns the default value for secure_session
 pub fn default_secure_session() -> bool { false }
+
+/// Returns a randomly generated Fernet key, which is only valid until the server restarts.
+pub fn default_secret_key() -> String { Fernet::generate_key() }
+
+/// Returns the default session backend (memory)
+pub fn default_session_backend() -> SessionBackend { SessionBackend::Memory }
+
+/// Returns the default storage directory (`storage` within the current working directory)
+pub fn default_storage() -> path::PathBuf {
+    std::env::current_dir().unwrap_or_default().join("storage")
+}
+
+/// Returns the default value for HLS streaming
+pub fn default_hls() -> bool { false }
+
+/// Returns the default value for MPEG-DASH streaming
+pub fn default_dash() -> bool { false }
+
+/// Returns the default `ffmpeg` binary, which is looked up in the `PATH`
+pub fn default_ffmpeg() -> path::PathBuf { path::PathBuf::from("ffmpeg") }
+
+/// Returns the default `ffprobe` binary, which is looked up in the `PATH`
+pub fn default_ffprobe() -> path::PathBuf { path::PathBuf::from("ffprobe") }
+
+/// Returns the default number of concurrent transcoding jobs (2)
+pub fn default_max_transcodes() -> usize { 2 }
+
+/// Returns the default idle timeout for the transcoding jobs (60 seconds)
+pub fn default_transcode_timeout() -> i64 { 60 }
+
+/// Returns the default max size of the transcoded segments (10 GB)
+pub fn default_transcode_cache_size() -> usize { 10 * 1024 * 1024 * 1024 }
+
+/// Returns the default number of concurrent extraction and remuxing jobs (1)
+pub fn default_max_extractions() -> usize { 1 }
+
+/// Returns the default max size of the remuxed audio tracks (10 GB)
+pub fn default_remux_cache_size() -> usize { 10 * 1024 * 1024 * 1024 }
+
+/// Returns the default cache directory (`cache` within the current working directory)
+pub fn default_cache_dir() -> path::PathBuf {
+    std::env::current_dir().unwrap_or_default().join("cache")
+}
@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::squire::{secure, settings, storage};
+
+/// Struct to store the information of a share link.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareLink {
+    /// Unique identifier of the share link.
+    pub id: String,
+    /// Path of the shared file, relative to `media_source`.
+    pub path: String,
+    /// Username of the user who created the share link.
+    pub issuer: String,
+    /// Epoch time when the share link was created.
+    pub created: i64,
+    /// Epoch time when the share link expires.
+    pub expiry: i64,
+    /// Maximum number of views allowed, unlimited if not set.
+    pub max_views: Option<u32>,
+    /// Number of times the share link has been viewed.
+    pub views: u32,
+}
+
+/// Represents the kind of request made with a share link.
+pub enum View<'a> {
+    /// Landing page, that is checked against the view limit before the view is counted.
+    Landing,
+    /// Media request, with the view token issued by the landing page.
+    Media(Option<&'a str>),
+}
+
+/// Struct to store the share links, persisted in the `storage` directory.
+///
+/// ## Fields
+///
+/// * `links` - Share links keyed by their ID.
+/// * `viewers` - ID of the share link keyed by the view tokens, issued for the links with a view limit.
+/// * `filepath` - JSON file where the share links are stored.
+pub struct Shares {
+    pub links: Mutex<HashMap<String, ShareLink>>,
+    viewers: Mutex<HashMap<String, String>>,
+    filepath: PathBuf,
+}
+
+/// Instantiates the `Shares` struct with the share links restored from the `storage` directory.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+///
+/// # Returns
+///
+/// Returns the constructed `Arc` for the `Shares` struct.
+pub fn shares_info(config: &settings::Config) -> Arc<Shares> {
+    let filepath = config.storage.join("shares.json");
+    let mut links: HashMap<String, ShareLink> = storage::load_json(&filepath);
+    let current_time = Utc::now().timestamp();
+    links.retain(|_, link| link.expiry > current_time);
+    Arc::new(Shares {
+        links: Mutex::new(links),
+        viewers: Mutex::new(HashMap::new()),
+        filepath,
+    })
+}
+
+/// Creates the HMAC for a share link, with the ID, path and expiry.
+///
+/// # Arguments
+///
+/// * `secret` - Secret key used to sign the share links.
+/// * `link` - Share link that has to be signed.
+///
+/// # Returns
+///
+/// Returns the HMAC-SHA256 instance that can be finalized or verified.
+fn mac(secret: &str, link: &ShareLink) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(format!("{}:{}:{}", link.id, link.path, link.expiry).as_bytes());
+    mac
+}
+
+/// Calculates the HMAC signature for a share link.
+///
+/// # Arguments
+///
+/// * `secret` - Secret key used to sign the share links.
+/// * `link` - Share link that has to be signed.
+///
+/// # Returns
+///
+/// A hexadecimal string representing the HMAC-SHA256 signature of the ID, path and expiry.
+pub fn signature(secret: &str, link: &ShareLink) -> String {
+    mac(secret, link).finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Verifies the signature received for a share link in constant time.
+///
+/// # Arguments
+///
+/// * `secret` - Secret key used to sign the share links.
+/// * `link` - Share link stored in the server.
+/// * `signature_received` - Hexadecimal signature received in the URL.
+///
+/// # Returns
+///
+/// A boolean flag to indicate whether the signature is valid.
+fn verify_signature(secret: &str, link: &ShareLink, signature_received: &str) -> bool {
+    match secure::bytes_from_hex(signature_received) {
+        Some(received) => mac(secret, link).verify_slice(&received).is_ok(),
+        None => false
+    }
+}
+
+/// Constructs the URL for a share link.
+///
+/// # Arguments
+///
+/// * `secret` - Secret key used to sign the share links.
+/// * `link` - Share link for which the URL has to be constructed.
+///
+/// # Returns
+///
+/// Returns the relative URL with the expiry and signature as query parameters.
+pub fn url(secret: &str, link: &ShareLink) -> String {
+    format!("/shared/{}?expiry={}&signature={}", link.id, link.expiry, signature(secret, link))
+}
+
+impl Shares {
+    /// Writes the current share links to the `storage` directory.
+    fn persist(&self, links: &HashMap<String, ShareLink>) {
+        storage::save_json(&self.filepath, links);
+    }
+
+    /// Creates a new share link.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the file to be shared, relative to `media_source`.
+    /// * `issuer` - Username of the user creating the share link.
+    /// * `duration` - Number of seconds the share link should be valid for.
+    /// * `max_views` - Maximum number of views allowed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the created `ShareLink`.
+    pub fn mint(&self, path: &str, issuer: &str, duration: i64, max_views: Option<u32>) -> ShareLink {
+        let current_time = Utc::now().timestamp();
+        let link = ShareLink {
+            id: secure::keygen(),
+            path: path.to_string(),
+            issuer: issuer.to_string(),
+            created: current_time,
+            expiry: current_time + duration,
+            max_views,
+            views: 0,
+        };
+        let mut links = self.links.lock().unwrap();
+        links.retain(|_, stored| stored.expiry > current_time);
+        links.insert(link.id.to_string(), link.clone());
+        self.persist(&links);
+        link
+    }
+
+    /// Validates the share link with its signature, expiry and views.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ID of the share link.
+    /// * `expiry` - Expiry received in the URL.
+    /// * `signature_received` - Signature received in the URL.
+    /// * `secret` - Secret key used to sign the share links.
+    /// * `view` - Kind of the request, either the landing page or the media.
+    ///
+    /// ## See Also
+    ///
+    /// - Validating the landing page doesn't count the view, use `count_view` once the request is served.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the `ShareLink`, or an error message.
+    pub fn validate(&self,
+                    id: &str,
+                    expiry: i64,
+                    signature_received: &str,
+                    secret: &str,
+                    view: View) -> Result<ShareLink, &'static str> {
+        let links = self.links.lock().unwrap();
+        let link = match links.get(id) {
+            Some(link) => link,
+            None => return Err("This link is invalid or has been revoked"),
+        };
+        if link.expiry != expiry || !verify_signature(secret, link, signature_received) {
+            log::warn!("Share link '{}' was requested with an invalid signature", id);
+            return Err("This link is invalid or has been revoked");
+        }
+        if link.expiry <= Utc::now().timestamp() {
+            return Err("This link has expired");
+        }
+        match view {
+            View::Media(token) => {
+                let counted = token
+                    .and_then(|token| self.viewers.lock().unwrap().get(token).cloned())
+                    .is_some_and(|viewed| viewed == link.id);
+                if link.max_views.is_some() && !counted {
+                    log::warn!("Share link '{}' was requested without a counted view", id);
+                    return Err("This link has to be opened from its landing page");
+                }
+            }
+            View::Landing => {
+                if link.max_views.is_some_and(|max_views| link.views >= max_views) {
+                    return Err("This link has reached the maximum number of views");
+                }
+            }
+        }
+        Ok(link.clone())
+    }
+
+    /// Counts a view of the landing page, after the share link has been validated.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ID of the share link.
+    ///
+    /// ## See Also
+    ///
+    /// - Only the landing page counts as a view, since the media is requested in several chunks.
+    /// - For the links with a view limit, each counted view issues a token that the media requests must carry.
+    /// - The limit is checked again, since another view could have been counted after the validation.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the view token for the links with a view limit, or an error message.
+    pub fn count_view(&self, id: &str) -> Result<Option<String>, &'static str> {
+        let mut links = self.links.lock().unwrap();
+        let link = match links.get_mut(id) {
+            Some(link) => link,
+            None => return Err("This link is invalid or has been revoked"),
+        };
+        if link.max_views.is_some_and(|max_views| link.views >= max_views) {
+            return Err("This link has reached the maximum number of views");
+        }
+        link.views += 1;
+        let token = link.max_views.map(|_| {
+            let token = secure::keygen();
+            let mut viewers = self.viewers.lock().unwrap();
+            // Tokens of the links that were revoked or have expired are no longer needed
+            viewers.retain(|_, viewed| links.contains_key(viewed));
+            viewers.insert(token.to_string(), id.to_string());
+            token
+        });
+        self.persist(&links);
+        Ok(token)
+    }
+
+    /// Lists the active share links created by a user.
+    ///
+    /// # Arguments
+    ///
+    /// * `issuer` - Username of the user.
+    ///
+    /// # Returns
+    ///
+    /// Returns a vector of `ShareLink` sorted by the creation time.
+    pub fn list(&self, issuer: &str) -> Vec<ShareLink> {
+        let current_time = Utc::now().timestamp();
+        let mut links: Vec<ShareLink> = self.links.lock().unwrap().values()
+            .filter(|link| link.issuer == issuer && link.expiry > current_time)
+            .cloned()
+            .collect();
+        links.sort_by_key(|link| link.created);
+        links
+    }
+
+    /// Revokes a share link created by a user.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ID of the share link.
+    /// * `issuer` - Username of the user.
+    ///
+    /// # Returns
+    ///
+    /// Returns a boolean flag to indicate whether the share link was revoked.
+    pub fn revoke(&self, id: &str, issuer: &str) -> bool {
+        let mut links = self.links.lock().unwrap();
+        if links.get(id).is_some_and(|link| link.issuer == issuer) {
+            links.remove(id);
+            self.persist(&links);
+            return true;
+        }
+        false
+    }
+}
@@ -80,7 +80,10 @@ fn mandatory_vars() -> (std::collections::HashMap<String, String>, std::path::Pa
             );
         }
     };
-    (authorization, std::path::PathBuf::from(media_source_str))
+    // Paths reported by the filesystem watcher are absolute, so the source has to be absolute to strip it
+    let media_source = std::path::PathBuf::from(media_source_str);
+    let media_source = media_source.canonicalize().unwrap_or(media_source);
+    (authorization, media_source)
 }
 
 /// Extracts the env var by key and parses it as a `bool`
@@ -280,6 +283,83 @@ fn parse_max_payload(key: &str) -> Option<usize> {
     }
 }
 
+/// Extracts the env var by key and parses it as a `HashMap<String, Role>`
+///
+/// # Arguments
+///
+/// * `key` - Key for the environment variable.
+///
+/// # Returns
+///
+/// Returns an `Option<HashMap<String, Role>>` if the value is available.
+///
+/// # Panics
+///
+/// If the value is present, but it is an invalid JSON or has an unsupported role.
+fn parse_roles(key: &str) -> Option<std::collections::HashMap<String, settings::Role>> {
+    match std::env::var(key) {
+        Ok(val) => match serde_json::from_str(&val) {
+            Ok(parsed) => Some(parsed),
+            Err(_) => {
+                panic!("\n{}\n\texpected a HashMap of 'viewer', 'uploader' or 'admin', received '{}' [value=invalid]\n",
+                       key, val);
+            }
+        },
+        Err(_) => None,
+    }
+}
+
+/// Extracts the env var by key and parses it as a `HashMap<String, Vec<String>>`
+///
+/// # Arguments
+///
+/// * `key` - Key for the environment variable.
+///
+/// # Returns
+///
+/// Returns an `Option<HashMap<String, Vec<String>>>` if the value is available.
+///
+/// # Panics
+///
+/// If the value is present, but it is an invalid data-type.
+fn parse_map(key: &str) -> Option<std::collections::HashMap<String, Vec<String>>> {
+    match std::env::var(key) {
+        Ok(val) => match serde_json::from_str(&val) {
+            Ok(parsed) => Some(parsed),
+            Err(_) => {
+                panic!("\n{}\n\texpected a HashMap of vec, received '{}' [value=invalid]\n", key, val);
+            }
+        },
+        Err(_) => None,
+    }
+}
+
+/// Extracts the env var by key and parses it as a `SessionBackend`
+///
+/// # Arguments
+///
+/// * `key` - Key for the environment variable.
+///
+/// # Returns
+///
+/// Returns an `Option<SessionBackend>` if the value is available.
+///
+/// # Panics
+///
+/// If the value is present, but it is not one of the supported backends.
+fn parse_session_backend(key: &str) -> Option<settings::SessionBackend> {
+    match std::env::var(key) {
+        Ok(val) => match val.to_lowercase().as_str() {
+            "memory" => Some(settings::SessionBackend::Memory),
+            "file" => Some(settings::SessionBackend::File),
+            _ => {
+                panic!("\n{}\n\texpected 'memory' or 'file', received '{}' [value=invalid]\n", key, val);
+            }
+        },
+        Err(_) => None,
+    }
+}
+
 fn parse_memory(memory: &str) -> Option<usize> {
     let value = memory.trim();
     let (size_str, unit) = value.split_at(value.len() - 2);
@@ -305,11 +385,19 @@ fn parse_memory(memory: &str) -> Option<usize> {
 /// Instantiates the `Config` struct with the required parameters.
 fn load_env_vars() -> settings::Config {
     let (authorization, media_source) = mandatory_vars();
+    let roles = parse_roles("roles").unwrap_or(settings::default_roles(&authorization));
+    let groups = parse_map("groups").unwrap_or(settings::default_access());
+    let acl = parse_map("acl").unwrap_or(settings::default_access());
     let debug = parse_bool("debug").unwrap_or(settings::default_debug());
     let utc_logging = parse_bool("utc_logging").unwrap_or(settings::default_utc_logging());
     let media_host = std::env::var("media_host").unwrap_or(settings::default_media_host());
     let media_port = parse_u16("media_port").unwrap_or(settings::default_media_port());
     let session_duration = parse_i64("session_duration").unwrap_or(settings::default_session_duration());
+    let login_window = parse_i64("login_window").unwrap_or(settings::default_login_window());
+    let max_login_attempts = parse_usize("max_login_attempts").unwrap_or(settings::default_max_login_attempts());
+    let lockout_duration = parse_i64("lockout_duration").unwrap_or(settings::default_lockout_duration());
+    let ban_threshold = parse_usize("ban_threshold").unwrap_or(settings::default_ban_threshold());
+    let trusted_proxies = parse_vec("trusted_proxies").unwrap_or(settings::default_trusted_proxies());
     let file_formats = parse_vec("file_formats").unwrap_or(settings::default_file_formats());
     let workers = parse_usize("workers").unwrap_or(settings::default_workers());
     let max_connections = parse_usize("max_connections").unwrap_or(settings::default_max_connections());
@@ -318,14 +406,37 @@ fn load_env_vars() -> settings::Config {
     let key_file = parse_path("key_file").unwrap_or(settings::default_ssl());
     let cert_file = parse_path("cert_file").unwrap_or(settings::default_ssl());
     let max_payload_size = parse_max_payload("max_payload_size").unwrap_or(settings::default_max_payload_size());
+    let secret_key = std::env::var("secret_key").unwrap_or(settings::default_secret_key());
+    let session_backend = parse_session_backend("session_backend").unwrap_or(settings::default_session_backend());
+    let storage = parse_path("storage").unwrap_or(settings::default_storage());
+    let hls = parse_bool("hls").unwrap_or(settings::default_hls());
+    let dash = parse_bool("dash").unwrap_or(settings::default_dash());
+    let ffmpeg = parse_path("ffmpeg").unwrap_or(settings::default_ffmpeg());
+    let max_transcodes = parse_usize("max_transcodes").unwrap_or(settings::default_max_transcodes());
+    let transcode_timeout = parse_i64("transcode_timeout").unwrap_or(settings::default_transcode_timeout());
+    let transcode_cache_size = parse_max_payload("transcode_cache_size")
+        .unwrap_or(settings::default_transcode_cache_size());
+    let max_extractions = parse_usize("max_extractions").unwrap_or(settings::default_max_extractions());
+    let remux_cache_size = parse_max_payload("remux_cache_size").unwrap_or(settings::default_remux_cache_size());
+    let ffprobe = parse_path("ffprobe").unwrap_or(settings::default_ffprobe());
+    let cache_dir = parse_path("cache_dir").unwrap_or(settings::default_cache_dir());
+    let subtitle_cache = parse_path("subtitle_cache").unwrap_or(cache_dir.join("subtitles"));
     settings::Config {
         authorization,
+        roles,
+        groups,
+        acl,
         media_source,
         debug,
         utc_logging,
         media_host,
         media_port,
         session_duration,
+        login_window,
+        max_login_attempts,
+        lockout_duration,
+        ban_threshold,
+        trusted_proxies,
         file_formats,
         workers,
         max_connections,
@@ -334,6 +445,20 @@ fn load_env_vars() -> settings::Config {
         secure_session,
         key_file,
         cert_file,
+        secret_key,
+        session_backend,
+        storage,
+        hls,
+        dash,
+        ffmpeg,
+        max_transcodes,
+        transcode_timeout,
+        transcode_cache_size,
+        max_extractions,
+        remux_cache_size,
+        ffprobe,
+        cache_dir,
+        subtitle_cache,
     }
 }
 
@@ -441,14 +566,87 @@ fn validate_vars(metadata: &constant::MetaData) -> settings::Config {
             );
             errors.push_str(&err2);
         }
-        if password.len() < 8 {
+        if !squire::secure::is_hashed(password) {
             let err3 = format!(
-                "\nauthorization\n\t[{}: {}] password should be at least 8 or more characters [value=invalid]\n",
-                username, "*".repeat(password.len())
+                "\nauthorization\n\t[{}: {}] passwords in plain text are not accepted, generate a hash with '--hash {}' [value=invalid]\n",
+                username, "*".repeat(password.len()), username
+            );
+            errors.push_str(&err3);
+        } else if !squire::secure::is_valid_hash(password) {
+            let err3 = format!(
+                "\nauthorization\n\t[{}: {}] password hash is not a valid server key, regenerate it with '--hash {}' [value=invalid]\n",
+                username, "*".repeat(password.len()), username
             );
             errors.push_str(&err3);
         }
     }
+    for username in config.roles.keys() {
+        if !config.authorization.contains_key(username) {
+            let err5 = format!(
+                "\nroles\n\t[{}] username is not present in authorization [value=invalid]\n", username
+            );
+            errors.push_str(&err5);
+        }
+    }
+    for (group, users) in &config.groups {
+        for username in users {
+            if !config.authorization.contains_key(username) {
+                let err6 = format!(
+                    "\ngroups\n\t[{}: {}] username is not present in authorization [value=invalid]\n", group, username
+                );
+                errors.push_str(&err6);
+            }
+        }
+    }
+    for (path, allowed) in &config.acl {
+        for entry in allowed {
+            let missing = match entry.strip_prefix('@') {
+                Some(group) => !config.groups.contains_key(group),
+                None => !config.authorization.contains_key(entry),
+            };
+            if missing {
+                let err7 = format!(
+                    "\nacl\n\t[{}: {}] user or group is not defined [value=invalid]\n", path, entry
+                );
+                errors.push_str(&err7);
+            }
+        }
+    }
+    if fernet::Fernet::new(&config.secret_key).is_none() {
+        errors.push_str(
+            "\nsecret_key\n\texpected a 32-byte URL-safe base64 encoded key [value=invalid]\n"
+        );
+    }
+    if config.session_backend == settings::SessionBackend::File && !config.storage.exists() {
+        if let Err(err) = std::fs::create_dir_all(&config.storage) {
+            let err4 = format!(
+                "\nstorage\n\tUnable to create directory [{}]: {} [value=invalid]\n",
+                config.storage.to_string_lossy(), err
+            );
+            errors.push_str(&err4);
+        }
+    }
+    if (config.hls || config.dash) && (config.max_transcodes == 0 || config.transcode_timeout <= 0) {
+        let err9 = format!(
+            "\nmax_transcodes/transcode_timeout\n\texpected positive values, received [{}, {}] [value=invalid]\n",
+            config.max_transcodes, config.transcode_timeout
+        );
+        errors.push_str(&err9);
+    }
+    if config.max_extractions == 0 {
+        errors.push_str(
+            "\nmax_extractions\n\texpected a positive value, received [0] [value=invalid]\n"
+        );
+    }
+    if (config.hls || config.dash) && !config.cache_dir.exists() {
+        if let Err(err) = std::fs::create_dir_all(&config.cache_dir) {
+            let err8 = format!(
+                "\ncache_dir\n\tUnable to create directory [{}]: {} [value=invalid]\n",
+                config.cache_dir.to_string_lossy(), err
+            );
+            errors.push_str(&err8);
+        }
+    }
     if !errors.is_empty() {
         panic!("{}", errors);
     }
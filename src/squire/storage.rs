@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::constant;
+use crate::squire::settings;
+
+/// Interval at which the frequently updated JSON files are written to the disk.
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Counter to give each write its own temporary file.
+static TEMPORARY_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Trait for the backends that can hold the session mapping beyond the in-memory `HashMap`.
+///
+/// ## See Also
+///
+/// - `load` is invoked only once during startup to restore the sessions.
+/// - `save` is invoked everytime the session mapping is modified.
+pub trait SessionStore: Send + Sync {
+    /// Loads the stored session mapping.
+    fn load(&self) -> HashMap<String, constant::SessionEntry>;
+    /// Stores the session mapping.
+    fn save(&self, mapping: &HashMap<String, constant::SessionEntry>);
+}
+
+/// Session store that keeps the sessions only in memory, so they are lost on every restart.
+pub struct MemoryStore;
+
+impl SessionStore for MemoryStore {
+    fn load(&self) -> HashMap<String, constant::SessionEntry> {
+        HashMap::new()
+    }
+
+    fn save(&self, _mapping: &HashMap<String, constant::SessionEntry>) {}
+}
+
+/// Session store that writes the sessions to a JSON file, so they survive restarts.
+pub struct FileStore {
+    /// Path to the JSON file where the sessions are stored.
+    pub filepath: PathBuf,
+}
+
+impl SessionStore for FileStore {
+    fn load(&self) -> HashMap<String, constant::SessionEntry> {
+        load_json(&self.filepath)
+    }
+
+    fn save(&self, mapping: &HashMap<String, constant::SessionEntry>) {
+        save_json(&self.filepath, mapping)
+    }
+}
+
+/// Creates the session store based on the configured backend.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+///
+/// # Returns
+///
+/// Returns a boxed object that implements the `SessionStore` trait.
+pub fn session_store(config: &settings::Config) -> Box<dyn SessionStore> {
+    match config.session_backend {
+        settings::SessionBackend::Memory => Box::new(MemoryStore),
+        settings::SessionBackend::File => Box::new(FileStore {
+            filepath: config.storage.join("sessions.json")
+        }),
+    }
+}
+
+/// Loads a JSON file into the specified data-type.
+///
+/// # Arguments
+///
+/// * `filepath` - Path to the JSON file.
+///
+/// # Returns
+///
+/// Returns the deserialized object, or the default value if the file is missing or unreadable.
+pub fn load_json<T: DeserializeOwned + Default>(filepath: &Path) -> T {
+    if !filepath.exists() {
+        return T::default();
+    }
+    match fs::read_to_string(filepath) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|err| {
+            log::error!("Failed to parse {:?}: {}", filepath, err);
+            T::default()
+        }),
+        Err(err) => {
+            log::error!("Failed to read {:?}: {}", filepath, err);
+            T::default()
+        }
+    }
+}
+
+/// Writes the specified object into a JSON file.
+///
+/// # Arguments
+///
+/// * `filepath` - Path to the JSON file.
+/// * `value` - Object that has to be serialized.
+///
+/// ## See Also
+///
+/// - Content is written to a temporary file first and then renamed, so a crash doesn't leave a partial file.
+/// - Each write uses its own temporary file, so concurrent writes to the same file cannot interleave.
+pub fn save_json<T: Serialize>(filepath: &Path, value: &T) {
+    let content = match serde_json::to_string(value) {
+        Ok(content) => content,
+        Err(err) => {
+            log::error!("Failed to serialize content for {:?}: {}", filepath, err);
+            return;
+        }
+    };
+    if let Some(parent) = filepath.parent() {
+        if let Err(err) = fs::create_dir_all(parent) {
+            log::error!("Failed to create {:?}: {}", parent, err);
+            return;
+        }
+    }
+    let mut temporary = filepath.as_os_str().to_os_string();
+    temporary.push(format!(".{}.tmp", TEMPORARY_FILES.fetch_add(1, Ordering::Relaxed)));
+    let temporary = PathBuf::from(temporary);
+    if let Err(err) = fs::write(&temporary, content) {
+        log::error!("Failed to write {:?}: {}", temporary, err);
+        return;
+    }
+    if let Err(err) = fs::rename(&temporary, filepath) {
+        log::error!("Failed to store {:?}: {}", filepath, err);
+        let _ = fs::remove_file(&temporary);
+    }
+}
+
+/// Spawns a background thread that runs the task at every interval, so the frequent updates are written in batches.
+///
+/// # Arguments
+///
+/// * `interval` - Duration between the runs.
+/// * `task` - Closure that writes the pending updates, if any.
+pub fn periodically(interval: Duration, task: impl Fn() + Send + 'static) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(interval);
+        task();
+    });
+}
@@ -1,59 +1,1218 @@
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::OsStr;
-use std::fs::File;
-use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
 use std::result::Result;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-/// Converts an SRT file to VTT format.
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE};
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded;
+
+use crate::squire::{probe, settings, transcode};
+
+/// Maximum offset of the subtitles in milliseconds, in either direction.
+pub const MAX_OFFSET: i64 = 600_000;
+
+/// Subtitle formats that can be converted to WebVTT, in the order of preference.
+pub const SOURCE_FORMATS: [&str; 3] = ["srt", "ass", "ssa"];
+
+/// Counter for the temporary files, that the conversions are written to before being moved into the cache.
+static PARTIAL_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Directories next to the media files that are searched for the subtitles, compared case-insensitively.
+const SUBTITLE_DIRECTORIES: [&str; 2] = ["subs", "subtitles"];
+
+/// Languages recognized in the subtitle filenames, as the ISO 639-1 code, the ISO 639-2 codes and the English name.
+const LANGUAGES: [(&str, &[&str], &str); 28] = [
+    ("en", &["eng"], "English"),
+    ("es", &["spa"], "Spanish"),
+    ("fr", &["fre", "fra"], "French"),
+    ("de", &["ger", "deu"], "German"),
+    ("it", &["ita"], "Italian"),
+    ("pt", &["por"], "Portuguese"),
+    ("nl", &["dut", "nld"], "Dutch"),
+    ("sv", &["swe"], "Swedish"),
+    ("no", &["nor", "nob", "nno"], "Norwegian"),
+    ("da", &["dan"], "Danish"),
+    ("fi", &["fin"], "Finnish"),
+    ("pl", &["pol"], "Polish"),
+    ("ru", &["rus"], "Russian"),
+    ("uk", &["ukr"], "Ukrainian"),
+    ("cs", &["cze", "ces"], "Czech"),
+    ("hu", &["hun"], "Hungarian"),
+    ("ro", &["rum", "ron"], "Romanian"),
+    ("el", &["gre", "ell"], "Greek"),
+    ("tr", &["tur"], "Turkish"),
+    ("he", &["heb"], "Hebrew"),
+    ("ar", &["ara"], "Arabic"),
+    ("hi", &["hin"], "Hindi"),
+    ("ja", &["jpn"], "Japanese"),
+    ("ko", &["kor"], "Korean"),
+    ("zh", &["chi", "zho"], "Chinese"),
+    ("th", &["tha"], "Thai"),
+    ("vi", &["vie"], "Vietnamese"),
+    ("id", &["ind"], "Indonesian"),
+];
+
+/// Default fields of the events in ASS/SSA files, when the `Format` line is missing.
+const EVENT_FORMAT: [&str; 10] = ["layer", "start", "end", "style", "name", "marginl", "marginr", "marginv", "effect", "text"];
+
+/// Represents a cue, the text that is displayed for a time range.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Cue {
+    /// Start time in milliseconds.
+    pub start: u64,
+    /// End time in milliseconds.
+    pub end: u64,
+    /// Text of the cue in WebVTT markup, with the lines separated by `\n`
+    pub text: String,
+    /// WebVTT cue settings for the position, empty for the default position.
+    pub settings: String,
+}
+
+/// Represents the styling of the text in ASS/SSA files, that can be carried into WebVTT.
+#[derive(Debug, Clone, PartialEq)]
+struct Styling {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    /// Alignment in the numpad layout, `2` is the bottom center.
+    alignment: u8,
+}
+
+impl Default for Styling {
+    fn default() -> Self {
+        Styling { bold: false, italic: false, underline: false, alignment: 2 }
+    }
+}
+
+impl Styling {
+    /// Returns the WebVTT tags for the styling, in the order they are opened.
+    fn tags(&self) -> Vec<char> {
+        [('b', self.bold), ('i', self.italic), ('u', self.underline)].into_iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(tag, _)| tag)
+            .collect()
+    }
+}
+
+/// Removes the byte order mark and converts the line endings to `\n`
+fn normalize(content: &str) -> String {
+    content.trim_start_matches('\u{feff}')
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+}
+
+/// Escapes the characters that are reserved in the WebVTT cue text.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Parses a timestamp in the SRT (`00:01:02,500`) or ASS/SSA (`0:01:02.50`) format.
 ///
 /// # Arguments
 ///
-/// * `filename` - The path to the input SRT file.
+/// * `value` - Timestamp to be parsed, hours are optional and the fraction can be separated by `,` `.` or `:`
 ///
 /// # Returns
 ///
-/// A boolean indicating whether the conversion was successful.
-pub fn srt_to_vtt(filepath: &PathBuf) -> Result<bool, String> {
-    if filepath.extension().and_then(OsStr::to_str) != Some("srt") {
-        return Ok(false);
+/// Returns the timestamp in milliseconds, or `None` if the value is not a valid timestamp.
+fn parse_timestamp(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let (clock, fraction) = match value.rfind([',', '.']) {
+        Some(idx) => (&value[..idx], &value[idx + 1..]),
+        None => (value, "")
+    };
+    let parts = clock.split(':')
+        .map(|part| part.trim().parse::<u64>().ok())
+        .collect::<Option<Vec<u64>>>()?;
+    let (hours, minutes, seconds, fraction) = match (parts.as_slice(), fraction) {
+        ([hours, minutes, seconds], _) => (*hours, *minutes, *seconds, fraction.to_string()),
+        ([minutes, seconds], _) => (0, *minutes, *seconds, fraction.to_string()),
+        ([hours, minutes, seconds, millis], "") => (*hours, *minutes, *seconds, format!("{:03}", millis)),
+        _ => return None
+    };
+    if minutes >= 60 || seconds >= 60 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
     }
+    let digits: String = fraction.chars().take(3).collect();
+    let millis = if digits.is_empty() {
+        0
+    } else {
+        digits.parse::<u64>().ok()? * 10u64.pow(3 - digits.len() as u32)
+    };
+    Some(((hours * 60 + minutes) * 60 + seconds) * 1000 + millis)
+}
+
+/// Formats the milliseconds as a WebVTT timestamp (`00:01:02.500`).
+fn format_timestamp(millis: u64) -> String {
+    let seconds = millis / 1000;
+    format!("{:02}:{:02}:{:02}.{:03}", seconds / 3600, (seconds % 3600) / 60, seconds % 60, millis % 1000)
+}
+
+/// Parses the timing line of an SRT cue, ignoring the coordinates that may follow the end time.
+fn parse_timing(line: &str) -> Option<(u64, u64)> {
+    let (start, end) = line.split_once("-->")?;
+    let end = end.split_whitespace().next()?;
+    Some((parse_timestamp(start)?, parse_timestamp(end)?))
+}
 
-    let output_file = filepath.with_extension("vtt");
-    let mut rf = match File::open(filepath) {
-        Ok(file) => file,
-        Err(err) => return Err(format!("Error opening file: {}", err)),
+/// Converts the alignment in the numpad layout into WebVTT cue settings.
+fn position_settings(alignment: u8) -> String {
+    let line = match alignment {
+        7..=9 => Some("line:0"),
+        4..=6 => Some("line:50%"),
+        _ => None
     };
+    let align = match alignment {
+        1 | 4 | 7 => Some("align:left"),
+        3 | 6 | 9 => Some("align:right"),
+        _ => None
+    };
+    [line, align].into_iter().flatten().collect::<Vec<_>>().join(" ")
+}
 
-    let mut srt_content = String::new();
-    if let Err(err) = rf.read_to_string(&mut srt_content) {
-        return Err(format!("Error reading file: {}", err));
+/// Converts the legacy SSA alignment (1-3 bottom, 5-7 top, 9-11 middle) into the numpad layout.
+fn legacy_alignment(alignment: u8) -> u8 {
+    match alignment {
+        9..=11 => alignment - 5,
+        5..=7 => alignment + 2,
+        _ => alignment
     }
+}
+
+/// Parses a boolean field or tag value, where ASS uses `-1` and SSA uses `1` for true.
+fn flag(value: &str) -> bool {
+    value.trim().parse::<i32>().map(|value| value != 0).unwrap_or_default()
+}
 
-    let srt_content = srt_content.replace(',', ".");
-    let srt_content = srt_content.replace(" --> ", "-->");
+/// Applies the override tags of an ASS/SSA block (e.g. `\i1\an8`) to the styling.
+///
+/// # Arguments
+///
+/// * `block` - Content of the override block, without the curly braces.
+/// * `styling` - Current styling of the text, that is updated in place.
+/// * `base` - Styling of the dialogue, restored by the `\r` tag.
+/// * `styles` - Named styles, that can be applied by the `\r<name>` tag.
+///
+/// # Returns
+///
+/// Returns a boolean flag to indicate if the drawing mode (`\p1`) is enabled, `None` if it is unchanged.
+fn apply_tags(block: &str, styling: &mut Styling, base: &Styling, styles: &HashMap<String, Styling>) -> Option<bool> {
+    let mut drawing = None;
+    for tag in block.split('\\').map(str::trim).filter(|tag| !tag.is_empty()) {
+        let numeric = |prefix: &str| tag.strip_prefix(prefix)
+            .filter(|value| value.chars().all(|c| c.is_ascii_digit()));
+        if let Some(value) = numeric("an") {
+            styling.alignment = value.parse().ok().filter(|value| (1..=9).contains(value)).unwrap_or(base.alignment);
+        } else if let Some(value) = numeric("a") {
+            styling.alignment = value.parse().map(legacy_alignment).unwrap_or(base.alignment);
+        } else if let Some(value) = numeric("b") {
+            styling.bold = if value.is_empty() { base.bold } else { flag(value) };
+        } else if let Some(value) = numeric("i") {
+            styling.italic = if value.is_empty() { base.italic } else { flag(value) };
+        } else if let Some(value) = numeric("u") {
+            styling.underline = if value.is_empty() { base.underline } else { flag(value) };
+        } else if let Some(value) = numeric("p") {
+            drawing = Some(flag(value));
+        } else if let Some(name) = tag.strip_prefix('r') {
+            let alignment = styling.alignment;
+            *styling = styles.get(name.trim()).cloned().unwrap_or_else(|| base.clone());
+            // Alignment applies to the whole line, so it is not reset
+            styling.alignment = alignment;
+        }
+    }
+    drawing
+}
 
-    let mut vtt_content = String::from("WEBVTT\n\n");
-    let subtitle_blocks: Vec<&str> = srt_content.trim().split("\n\n").collect();
+/// Converts the text of an ASS/SSA dialogue into WebVTT markup.
+///
+/// # Arguments
+///
+/// * `text` - Text of the dialogue with the override blocks.
+/// * `base` - Styling of the dialogue from its style.
+/// * `styles` - Named styles, that can be applied by the `\r<name>` tag.
+///
+/// ## See Also
+///
+/// - Bold, italic and underline are converted to `<b>`, `<i>` and `<u>` tags.
+/// - `\N` is a line break, `\n` is a soft break (rendered as a space) and `\h` is a non-breaking space.
+/// - Other override tags and the drawings are removed.
+///
+/// # Returns
+///
+/// Returns a tuple of the cue text and the alignment of the dialogue.
+fn convert_ass_text(text: &str, base: &Styling, styles: &HashMap<String, Styling>) -> (String, u8) {
+    let mut styling = base.clone();
+    let mut open: Vec<char> = Vec::new();
+    let mut output = String::new();
+    let mut drawing = false;
+    let mut rest = text;
+    while !rest.is_empty() {
+        if rest.starts_with('{') {
+            if let Some(close) = rest.find('}') {
+                if let Some(enabled) = apply_tags(&rest[1..close], &mut styling, base, styles) {
+                    drawing = enabled;
+                }
+                rest = &rest[close + 1..];
+                continue;
+            }
+        }
+        let next = rest.char_indices()
+            .skip(1)
+            .find(|(_, c)| *c == '{')
+            .map(|(idx, _)| idx)
+            .unwrap_or(rest.len());
+        let segment = &rest[..next];
+        rest = &rest[next..];
+        if drawing {
+            continue;
+        }
+        let desired = styling.tags();
+        if open != desired {
+            open.iter().rev().for_each(|tag| output.push_str(&format!("</{}>", tag)));
+            desired.iter().for_each(|tag| output.push_str(&format!("<{}>", tag)));
+            open = desired;
+        }
+        output.push_str(&escape(segment)
+            .replace("\\N", "\n")
+            .replace("\\n", " ")
+            .replace("\\h", "\u{a0}"));
+    }
+    open.iter().rev().for_each(|tag| output.push_str(&format!("</{}>", tag)));
+    let text = output.split('\n')
+        .map(|line| line.trim_matches(' '))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    (text, styling.alignment)
+}
 
-    for block in subtitle_blocks {
-        let lines: Vec<&str> = block.split('\n').collect();
-        let timecode = lines[1];
-        let text = lines[2..].join("\n");
-        vtt_content.push_str(&format!("{}\n{}\n\n", timecode, text));
+/// Converts a line of SRT text into WebVTT markup.
+///
+/// # Arguments
+///
+/// * `line` - Line of the SRT cue.
+///
+/// ## See Also
+///
+/// - `<i>`, `<b>` and `<u>` tags are preserved, `<font>` tags are removed since WebVTT doesn't support them.
+/// - Any other `<`, `>` and `&` are escaped, so the text is displayed as is.
+fn convert_srt_line(line: &str) -> String {
+    let mut output = String::new();
+    let mut rest = line;
+    while let Some(open) = rest.find('<') {
+        output.push_str(&escape(&rest[..open]));
+        rest = &rest[open..];
+        let tag = rest.find('>').map(|close| rest[1..close].trim().to_lowercase());
+        match tag.as_deref() {
+            Some(tag @ ("i" | "/i" | "b" | "/b" | "u" | "/u")) => output.push_str(&format!("<{}>", tag)),
+            Some(tag) if tag == "/font" || tag.starts_with("font ") || tag == "font" => {}
+            _ => {
+                output.push_str("&lt;");
+                rest = &rest[1..];
+                continue;
+            }
+        }
+        rest = &rest[rest.find('>').unwrap() + 1..];
     }
+    output.push_str(&escape(rest));
+    output
+}
 
-    let mut wf = match File::create(output_file) {
-        Ok(file) => file,
-        Err(err) => return Err(format!("Error creating output file: {}", err)),
+/// Builds a cue from the timing and the lines of an SRT cue.
+///
+/// # Arguments
+///
+/// * `timing` - Start and end time in milliseconds.
+/// * `lines` - Lines of text in the cue.
+///
+/// # Returns
+///
+/// Returns the `Cue`, or `None` if the cue has no text.
+fn srt_cue(timing: (u64, u64), lines: &[&str]) -> Option<Cue> {
+    let text = lines.iter()
+        .map(|line| line.trim_end())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    // Position tags from ASS are commonly found at the start of SRT cues, e.g. {\an8}
+    let (text, alignment) = if text.contains("{\\") {
+        let mut styling = Styling::default();
+        let mut stripped = String::new();
+        let mut rest = text.as_str();
+        while let Some(open) = rest.find("{\\") {
+            stripped.push_str(&rest[..open]);
+            match rest[open..].find('}') {
+                Some(close) => {
+                    apply_tags(&rest[open + 1..open + close], &mut styling, &Styling::default(), &HashMap::new());
+                    rest = &rest[open + close + 1..];
+                }
+                None => {
+                    stripped.push_str(&rest[open..]);
+                    rest = "";
+                }
+            }
+        }
+        stripped.push_str(rest);
+        (stripped, styling.alignment)
+    } else {
+        (text, Styling::default().alignment)
     };
+    let text = text.split('\n')
+        .map(convert_srt_line)
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    if text.is_empty() {
+        return None;
+    }
+    Some(Cue {
+        start: timing.0,
+        end: timing.1.max(timing.0),
+        text,
+        settings: position_settings(alignment),
+    })
+}
 
-    if let Err(err) = wf.write_all(vtt_content.as_bytes()) {
-        return Err(format!("Error writing to output file: {}", err));
+/// Parses the cues from the content of an SRT file.
+///
+/// # Arguments
+///
+/// * `content` - Content of the SRT file.
+///
+/// ## See Also
+///
+/// - Tolerates the byte order mark, `CRLF` line endings, missing or repeated blank lines between the cues.
+/// - The line before a timing line is treated as the cue identifier, if it is a number.
+/// - Cues without any text are skipped.
+///
+/// # Returns
+///
+/// Returns a vector of the `Cue` in the order they appear in the file.
+pub fn parse_srt(content: &str) -> Vec<Cue> {
+    let content = normalize(content);
+    let mut cues = Vec::new();
+    let mut timing: Option<(u64, u64)> = None;
+    let mut lines: Vec<&str> = Vec::new();
+    for line in content.lines() {
+        if let Some(next) = parse_timing(line) {
+            let identifier = lines.last()
+                .map(|last| !last.trim().is_empty() && last.trim().chars().all(|c| c.is_ascii_digit()))
+                .unwrap_or_default();
+            if identifier {
+                lines.pop();
+            }
+            if let Some(cue) = timing.and_then(|timing| srt_cue(timing, &lines)) {
+                cues.push(cue);
+            }
+            lines.clear();
+            timing = Some(next);
+        } else {
+            lines.push(line);
+        }
+    }
+    if let Some(cue) = timing.and_then(|timing| srt_cue(timing, &lines)) {
+        cues.push(cue);
+    }
+    cues
+}
+
+/// Parses the cues from the content of an ASS or SSA file.
+///
+/// # Arguments
+///
+/// * `content` - Content of the ASS/SSA file.
+///
+/// ## See Also
+///
+/// - Styles are read from the `[V4+ Styles]` (ASS) or `[V4 Styles]` (SSA) sections, for the bold, italic, underline and alignment.
+/// - Dialogues are read from the `[Events]` section using the fields in its `Format` line, comments are skipped.
+/// - Cues are sorted by the start time, since WebVTT requires them to be in order.
+///
+/// # Returns
+///
+/// Returns a vector of the `Cue` sorted by the start time.
+pub fn parse_ass(content: &str) -> Vec<Cue> {
+    let content = normalize(content);
+    let mut section = String::new();
+    let mut legacy = false;
+    let mut style_format: Vec<String> = Vec::new();
+    let mut event_format: Vec<String> = EVENT_FORMAT.iter().map(|field| field.to_string()).collect();
+    let mut styles: HashMap<String, Styling> = HashMap::new();
+    let mut cues = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') && line.ends_with(']') {
+            section = line[1..line.len() - 1].trim().to_lowercase();
+            legacy = legacy || section == "v4 styles";
+            continue;
+        }
+        let (key, value) = match line.split_once(':') {
+            Some((key, value)) => (key.trim().to_lowercase(), value),
+            None => continue
+        };
+        let fields = |format: &[String]| -> Vec<String> {
+            value.splitn(format.len().max(1), ',').map(|field| field.trim().to_string()).collect()
+        };
+        let field_names = |value: &str| -> Vec<String> {
+            value.split(',').map(|field| field.trim().to_lowercase()).collect()
+        };
+        match (section.as_str(), key.as_str()) {
+            ("v4 styles" | "v4+ styles", "format") => style_format = field_names(value),
+            ("events", "format") => event_format = field_names(value),
+            ("v4 styles" | "v4+ styles", "style") => {
+                let values = fields(&style_format);
+                let get = |name: &str| style_format.iter().position(|field| field == name)
+                    .and_then(|idx| values.get(idx))
+                    .map(String::as_str);
+                let alignment = get("alignment").and_then(|value| value.parse::<u8>().ok())
+                    .map(|value| if legacy { legacy_alignment(value) } else { value })
+                    .filter(|value| (1..=9).contains(value))
+                    .unwrap_or(2);
+                // SSA marks the default style with an asterisk
+                styles.insert(get("name").unwrap_or_default().trim_start_matches('*').to_string(), Styling {
+                    bold: get("bold").map(flag).unwrap_or_default(),
+                    italic: get("italic").map(flag).unwrap_or_default(),
+                    underline: get("underline").map(flag).unwrap_or_default(),
+                    alignment,
+                });
+            }
+            ("events", "dialogue") => {
+                let values = fields(&event_format);
+                let get = |name: &str| event_format.iter().position(|field| field == name)
+                    .and_then(|idx| values.get(idx))
+                    .map(String::as_str);
+                let (start, end) = match (get("start").and_then(parse_timestamp), get("end").and_then(parse_timestamp)) {
+                    (Some(start), Some(end)) => (start, end),
+                    _ => continue
+                };
+                let base = get("style")
+                    .and_then(|name| styles.get(name.trim_start_matches('*')))
+                    .cloned()
+                    .unwrap_or_default();
+                let (text, alignment) = convert_ass_text(get("text").unwrap_or_default(), &base, &styles);
+                if text.is_empty() {
+                    continue;
+                }
+                cues.push(Cue { start, end: end.max(start), text, settings: position_settings(alignment) });
+            }
+            _ => {}
+        }
+    }
+    cues.sort_by_key(|cue| cue.start);
+    cues
+}
+
+/// Writes the cues in the WebVTT format.
+///
+/// # Arguments
+///
+/// * `cues` - Cues to be written, in the order of the start time.
+///
+/// # Returns
+///
+/// Returns the content of the WebVTT file.
+pub fn to_vtt(cues: &[Cue]) -> String {
+    let mut vtt = String::from("WEBVTT\n");
+    for cue in cues {
+        vtt.push('\n');
+        vtt.push_str(&format!("{} --> {}", format_timestamp(cue.start), format_timestamp(cue.end)));
+        if !cue.settings.is_empty() {
+            vtt.push(' ');
+            vtt.push_str(&cue.settings);
+        }
+        vtt.push('\n');
+        vtt.push_str(&cue.text);
+        vtt.push('\n');
     }
+    vtt
+}
 
-    if let Err(err) = wf.flush() {
-        return Err(format!("Error flushing output file: {}", err));
+/// Parses the cues from the content of a WebVTT file.
+///
+/// # Arguments
+///
+/// * `content` - Content of the WebVTT file.
+///
+/// ## See Also
+///
+/// - The header, `NOTE`, `STYLE` and `REGION` blocks are skipped, since they don't have a timing line.
+/// - Cue settings after the end time are retained, and the text is retained as is, since it is already escaped.
+///
+/// # Returns
+///
+/// Returns a vector of the `Cue` in the order they appear in the file.
+pub fn parse_vtt(content: &str) -> Vec<Cue> {
+    let content = normalize(content);
+    let mut cues = Vec::new();
+    for block in content.split("\n\n") {
+        let lines: Vec<&str> = block.lines().collect();
+        let position = match lines.iter().position(|line| line.contains("-->")) {
+            Some(position) => position,
+            None => continue
+        };
+        let (start, end) = match parse_timing(lines[position]) {
+            Some(timing) => timing,
+            None => continue
+        };
+        let settings = lines[position].split_once("-->")
+            .map(|(_, end)| end.split_whitespace().skip(1).collect::<Vec<_>>().join(" "))
+            .unwrap_or_default();
+        let text = lines[position + 1..].join("\n");
+        if text.trim().is_empty() {
+            continue;
+        }
+        cues.push(Cue { start, end: end.max(start), text, settings });
     }
+    cues
+}
 
+/// Shifts the cues by an offset, to fix the subtitles that are out of sync with the audio.
+///
+/// # Arguments
+///
+/// * `cues` - Cues to be shifted.
+/// * `offset` - Offset in milliseconds, positive values delay the subtitles and negative values advance them.
+///
+/// # Returns
+///
+/// Returns a vector of the shifted `Cue`, where the cues that end before the start of the video are dropped.
+pub fn shift(cues: Vec<Cue>, offset: i64) -> Vec<Cue> {
+    let shifted = |millis: u64| (millis as i64).saturating_add(offset).max(0) as u64;
+    cues.into_iter()
+        .filter(|cue| offset >= 0 || cue.end as i64 + offset > 0)
+        .map(|cue| Cue { start: shifted(cue.start), end: shifted(cue.end), ..cue })
+        .collect()
+}
+
+/// Detects the byte order of the UTF-16 content without a BOM, from the null bytes of the ASCII characters.
+///
+/// # Arguments
+///
+/// * `bytes` - Raw content of the file.
+///
+/// # Returns
+///
+/// Returns the UTF-16 encoding if the null bytes are (almost) all in either the even or the odd positions.
+fn utf16_without_bom(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(4096) & !1];
+    let pairs = sample.len() / 2;
+    if pairs < 2 {
+        return None;
+    }
+    let even = sample.iter().step_by(2).filter(|byte| **byte == 0).count();
+    let odd = sample.iter().skip(1).step_by(2).filter(|byte| **byte == 0).count();
+    // Timestamps and the punctuation are ASCII, so at least a third of the pairs have a null byte
+    match (even * 3 >= pairs, odd * 3 >= pairs) {
+        (false, true) if even * 20 < pairs => Some(UTF_16LE),
+        (true, false) if odd * 20 < pairs => Some(UTF_16BE),
+        _ => None
+    }
+}
+
+/// Decodes the content of a subtitle file into a string, regardless of the encoding.
+///
+/// # Arguments
+///
+/// * `bytes` - Raw content of the file.
+///
+/// ## See Also
+///
+/// - The encoding is detected from the BOM, then the null bytes of UTF-16, and then the validity of UTF-8.
+/// - Legacy encodings like `Latin-1` and `Windows-1252` are detected from the byte frequencies.
+///
+/// # Returns
+///
+/// Returns the decoded content, where the invalid sequences are replaced with `U+FFFD`.
+pub fn decode(bytes: &[u8]) -> String {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        return encoding.decode_without_bom_handling(&bytes[bom_length..]).0.into_owned();
+    }
+    if let Some(encoding) = utf16_without_bom(bytes) {
+        return encoding.decode_without_bom_handling(bytes).0.into_owned();
+    }
+    if let Ok(content) = std::str::from_utf8(bytes) {
+        return content.to_string();
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    let encoding = detector.guess(None, true);
+    log::debug!("Decoding subtitles as {}", encoding.name());
+    encoding.decode_without_bom_handling(bytes).0.into_owned()
+}
+
+/// Converts an SRT, ASS or SSA file to VTT format.
+///
+/// # Arguments
+///
+/// * `source` - The path to the input subtitle file.
+/// * `output` - The path to the output file in WebVTT format.
+///
+/// ## See Also
+///
+/// - The output is written to a temporary file and renamed, so a partially written file is never served.
+///
+/// # Returns
+///
+/// A boolean indicating whether the conversion was successful, `false` for the unsupported formats.
+pub fn convert(source: &Path, output: &Path) -> Result<bool, String> {
+    let extension = source.extension().and_then(OsStr::to_str).unwrap_or_default().to_lowercase();
+    let parse: fn(&str) -> Vec<Cue> = match extension.as_str() {
+        "srt" => parse_srt,
+        "ass" | "ssa" => parse_ass,
+        _ => return Ok(false)
+    };
+    let content = match fs::read(source) {
+        Ok(content) => content,
+        Err(err) => return Err(format!("Error reading file: {}", err)),
+    };
+    let cues = parse(&decode(&content));
+    if cues.is_empty() {
+        return Err(format!("No cues found in {:?}", source));
+    }
+    if let Some(parent) = output.parent() {
+        if let Err(err) = fs::create_dir_all(parent) {
+            return Err(format!("Error creating directory {:?}: {}", parent, err));
+        }
+    }
+    // Concurrent conversions of the same file must not write to the same temporary file
+    let partial = output.with_extension(format!("{}.part", PARTIAL_FILES.fetch_add(1, Ordering::Relaxed)));
+    if let Err(err) = fs::write(&partial, to_vtt(&cues)).and_then(|_| fs::rename(&partial, output)) {
+        let _ = fs::remove_file(&partial);
+        return Err(format!("Error writing to output file: {}", err));
+    }
     Ok(true)
 }
+
+/// Retrieves the WebVTT file for a subtitle file from the cache, converting it on a cache miss.
+///
+/// # Arguments
+///
+/// * `source` - True path of the subtitle file in SRT, ASS or SSA format.
+/// * `relative_path` - Path of the subtitle file, relative to `media_source`.
+/// * `config` - Configuration data for the application.
+///
+/// ## See Also
+///
+/// - Converted files are keyed by the path and the modified time, so an updated subtitle file is converted again.
+///
+/// # Returns
+///
+/// Returns the path to the cached WebVTT file, or an error message if the conversion fails.
+pub fn cached(source: &Path, relative_path: &str, config: &settings::Config) -> Result<PathBuf, String> {
+    let key = match transcode::cache_key(source, relative_path) {
+        Some(key) => key,
+        None => return Err(format!("Unable to read {:?}", source))
+    };
+    let output = config.subtitle_cache.join(format!("{}.vtt", key));
+    if output.exists() {
+        return Ok(output);
+    }
+    log::info!("Converting {:?} to vtt for subtitles", source.file_name().unwrap_or_default());
+    match convert(source, &output) {
+        Ok(true) => Ok(output),
+        Ok(false) => Err(format!("Unsupported subtitle format {:?}", source.extension().unwrap_or_default())),
+        Err(err) => Err(err)
+    }
+}
+
+/// Reads a subtitle file in WebVTT format, to be served as a track.
+///
+/// # Arguments
+///
+/// * `filepath` - True path of the subtitle file.
+/// * `relative_path` - Path of the subtitle file, relative to `media_source`.
+/// * `config` - Configuration data for the application.
+///
+/// ## See Also
+///
+/// - Absolute paths and parent directory references are rejected before the file or the cache is looked up.
+///
+/// # Returns
+///
+/// Returns the content of the WebVTT file, or the cached conversion for the SRT, ASS and SSA files.
+pub fn read(filepath: &Path, relative_path: &str, config: &settings::Config) -> Result<String, String> {
+    if !Path::new(relative_path).components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir)) {
+        return Err(format!("'{}' is not relative to the media source", relative_path));
+    }
+    let extension = filepath.extension().and_then(OsStr::to_str).unwrap_or_default().to_lowercase();
+    let vtt = if extension == "vtt" {
+        filepath.to_path_buf()
+    } else if SOURCE_FORMATS.contains(&extension.as_str()) {
+        cached(filepath, relative_path, config)?
+    } else {
+        return Err(format!("'{}' is not a subtitle file", relative_path));
+    };
+    match fs::read(&vtt) {
+        Ok(content) => Ok(decode(&content)),
+        Err(err) => Err(format!("Error reading {:?}: {}", vtt.file_name().unwrap_or_default(), err))
+    }
+}
+
+/// Represents a subtitle track of a media file, that is served in WebVTT format.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Track {
+    /// URL of the track in WebVTT format.
+    pub url: String,
+    /// Path of the subtitle file, relative to `media_source`.
+    pub path: String,
+    /// ISO 639-1 code of the language, if it is found in the filename.
+    pub language: Option<String>,
+    /// Label of the track, displayed in the player.
+    pub label: String,
+    /// Boolean flag to indicate if the track only has the forced subtitles (e.g. foreign dialogues).
+    pub forced: bool,
+    /// Boolean flag to indicate if the track has subtitles for the deaf and hard of hearing.
+    pub sdh: bool,
+    /// Boolean flag to indicate if the track is enabled by default.
+    pub default: bool,
+    /// Index of the stream within the media file, for the subtitles extracted from the container.
+    pub stream: Option<u32>,
+}
+
+/// Represents a subtitle file found for a media file.
+struct Subtitle {
+    /// True path of the subtitle file, a WebVTT file is preferred over the source formats with the same name.
+    path: PathBuf,
+    /// Path of the subtitle file, relative to `media_source`.
+    relative: String,
+    /// Tags in the filename after the name of the media file, e.g. `en.forced` in `Movie.en.forced.srt`
+    tags: String,
+}
+
+/// Looks up the language by its ISO 639-1 code, ISO 639-2 code or the English name.
+///
+/// # Returns
+///
+/// Returns a tuple of the ISO 639-1 code and the English name.
+pub fn language(token: &str) -> Option<(&'static str, &'static str)> {
+    let token = token.to_lowercase();
+    LANGUAGES.iter()
+        .find(|(code, codes, name)| *code == token || codes.contains(&token.as_str()) || name.to_lowercase() == token)
+        .map(|(code, _, name)| (*code, *name))
+}
+
+/// Parses the tags in a subtitle filename into a track.
+///
+/// # Arguments
+///
+/// * `tags` - Tags in the filename after the name of the media file, e.g. `en.forced` or `2_English`
+///
+/// ## See Also
+///
+/// - `forced`/`foreign` mark the forced subtitles, `sdh`/`cc` (and `hi` after the language) mark the SDH subtitles.
+/// - `default` marks the track to be enabled by default.
+///
+/// # Returns
+///
+/// Returns a `Track` with the language, label and flags, without the path and URL.
+fn parse_tags(tags: &str) -> Track {
+    let mut track = Track::default();
+    let mut name = None;
+    for token in tags.split(['.', '_', '-', ' ', '[', ']', '(', ')']).filter(|token| !token.is_empty()) {
+        match token.to_lowercase().as_str() {
+            "forced" | "foreign" => track.forced = true,
+            "sdh" | "cc" => track.sdh = true,
+            // `hi` is the code for Hindi, but it means hearing impaired after a language
+            "hi" if name.is_some() => track.sdh = true,
+            "default" => track.default = true,
+            _ if name.is_none() => {
+                if let Some((code, language_name)) = language(token) {
+                    track.language = Some(code.to_string());
+                    name = Some(language_name);
+                }
+            }
+            _ => {}
+        }
+    }
+    track.label = name.unwrap_or("Subtitles").to_string();
+    if track.forced {
+        track.label.push_str(" (Forced)");
+    }
+    if track.sdh {
+        track.label.push_str(" (SDH)");
+    }
+    track
+}
+
+/// Builds the track for a subtitle stream that is embedded in the media file.
+///
+/// # Arguments
+///
+/// * `stream` - Subtitle stream probed from the media file.
+/// * `relative_path` - Path of the media file, relative to `media_source`.
+///
+/// ## See Also
+///
+/// - The default disposition is ignored, since most muxers set it on the first stream regardless of the content.
+///
+/// # Returns
+///
+/// Returns a `Track` labelled by the title of the stream, or the language and the flags.
+pub fn embedded(stream: &probe::SubtitleStream, relative_path: &str) -> Track {
+    let language = stream.language.as_deref().and_then(language);
+    let mut label = language.map(|(_, name)| name).unwrap_or("Subtitles").to_string();
+    if stream.forced {
+        label.push_str(" (Forced)");
+    }
+    if stream.sdh {
+        label.push_str(" (SDH)");
+    }
+    let encoded = form_urlencoded::byte_serialize(relative_path.as_bytes()).collect::<String>();
+    Track {
+        url: format!("/track?file={}&stream={}", encoded, stream.index),
+        path: relative_path.to_string(),
+        language: language.map(|(code, _)| code.to_string()),
+        label: stream.title.clone().filter(|title| !title.trim().is_empty()).unwrap_or(label),
+        forced: stream.forced,
+        sdh: stream.sdh,
+        default: false,
+        stream: Some(stream.index),
+    }
+}
+
+/// Checks if the file is a subtitle file, that can be served or converted to WebVTT.
+fn is_subtitle(path: &Path) -> bool {
+    let extension = path.extension().and_then(OsStr::to_str).unwrap_or_default().to_lowercase();
+    extension == "vtt" || SOURCE_FORMATS.contains(&extension.as_str())
+}
+
+/// Lists the subtitle files in a directory that belong to the media file.
+///
+/// # Arguments
+///
+/// * `directory` - True path of the directory to be searched.
+/// * `relative` - Path of the directory, relative to `media_source`.
+/// * `stem` - Name of the media file without the extension, all the subtitle files are listed when `None`
+/// * `found` - Subtitle files found, keyed by the true path without the extension.
+fn scan(directory: &Path, relative: &Path, stem: Option<&str>, found: &mut BTreeMap<PathBuf, Subtitle>) {
+    let entries: Vec<PathBuf> = match fs::read_dir(directory) {
+        Ok(read_dir) => read_dir.flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .filter(|path| !path.file_name().unwrap_or_default().to_string_lossy().starts_with('.'))
+            .collect(),
+        Err(_) => return
+    };
+    let stem_of = |path: &PathBuf| path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    // Other media files that share the prefix, e.g. `Movie.Part.2.mp4` next to `Movie.mp4`
+    let others: Vec<String> = match stem {
+        Some(stem) => entries.iter()
+            .filter(|path| !is_subtitle(path))
+            .map(stem_of)
+            .filter(|other| other.starts_with(&format!("{}.", stem)))
+            .collect(),
+        None => Vec::new()
+    };
+    for path in entries.iter().filter(|path| is_subtitle(path)) {
+        let name = stem_of(path);
+        let tags = match stem {
+            None => name.clone(),
+            Some(stem) if name == stem => String::new(),
+            Some(stem) => match name.strip_prefix(&format!("{}.", stem)) {
+                Some(_) if others.iter().any(|other| name == *other || name.starts_with(&format!("{}.", other))) => continue,
+                Some(tags) => tags.to_string(),
+                None => continue
+            }
+        };
+        let subtitle = Subtitle {
+            relative: relative.join(path.file_name().unwrap_or_default()).to_string_lossy().to_string(),
+            path: path.clone(),
+            tags,
+        };
+        let is_vtt = |subtitle: &Subtitle| subtitle.path.extension().unwrap_or_default().eq_ignore_ascii_case("vtt");
+        match found.get(&path.with_extension("")) {
+            Some(existing) if is_vtt(existing) || !is_vtt(&subtitle) => {}
+            _ => { found.insert(path.with_extension(""), subtitle); }
+        }
+    }
+}
+
+/// Discovers the subtitle files for a media file.
+///
+/// # Arguments
+///
+/// * `filepath` - True path of the media file.
+/// * `relative_path` - Path of the media file, relative to `media_source`.
+///
+/// ## See Also
+///
+/// - Subtitle files next to the media file are matched by its name, e.g. `Movie.srt`, `Movie.en.srt` or `Movie.forced.en.srt`
+/// - The `Subs/` (or `Subtitles/`) directory is searched the same way, along with `Subs/<name>/` where all the files belong to the media.
+fn discover(filepath: &Path, relative_path: &str) -> Vec<Subtitle> {
+    let mut found = BTreeMap::new();
+    let (directory, stem) = match (filepath.parent(), filepath.file_stem()) {
+        (Some(directory), Some(stem)) => (directory, stem.to_string_lossy().to_string()),
+        _ => return Vec::new()
+    };
+    let relative = Path::new(relative_path).parent().unwrap_or(Path::new(""));
+    scan(directory, relative, Some(&stem), &mut found);
+    let subdirectories = fs::read_dir(directory).into_iter()
+        .flat_map(|read_dir| read_dir.flatten())
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| SUBTITLE_DIRECTORIES.contains(&name.to_lowercase().as_str()));
+    for name in subdirectories {
+        scan(&directory.join(&name), &relative.join(&name), Some(&stem), &mut found);
+        scan(&directory.join(&name).join(&stem), &relative.join(&name).join(&stem), None, &mut found);
+    }
+    found.into_values().collect()
+}
+
+/// Checks if there are any subtitle files for the media file.
+///
+/// # Arguments
+///
+/// * `filepath` - True path of the media file.
+pub fn available(filepath: &Path) -> bool {
+    !discover(filepath, "").is_empty()
+}
+
+/// Parses the `Accept-Language` header into the ISO 639-1 codes, in the order of preference.
+///
+/// # Arguments
+///
+/// * `header` - Value of the `Accept-Language` header, e.g. `es-ES,es;q=0.9,en;q=0.8`
+pub fn preferred_languages(header: &str) -> Vec<String> {
+    let mut languages: Vec<String> = Vec::new();
+    for language in header.split(',') {
+        let code = language.split(';').next().unwrap_or_default()
+            .split('-').next().unwrap_or_default()
+            .trim().to_lowercase();
+        if !code.is_empty() && code != "*" && !languages.contains(&code) {
+            languages.push(code);
+        }
+    }
+    languages
+}
+
+/// Lists the subtitle tracks for a media file, converting the subtitle files to WebVTT when required.
+///
+/// # Arguments
+///
+/// * `filepath` - True path of the media file.
+/// * `relative_path` - Path of the media file, relative to `media_source`.
+/// * `preferred` - ISO 639-1 codes of the languages preferred by the user.
+/// * `config` - Configuration data for the application.
+/// * `embedded` - Tracks for the subtitle streams that were extracted from the media file.
+///
+/// ## See Also
+///
+/// - A track tagged as `default` is enabled by default, otherwise the forced subtitles in the preferred language.
+/// - Full subtitles are not enabled by default, since the audio is likely in the preferred language as well.
+/// - Subtitle files are converted into the `subtitle_cache`, so the ones that fail to convert are not listed.
+///
+/// # Returns
+///
+/// Returns a vector of the `Track` sorted by the label.
+pub fn tracks(filepath: &Path,
+              relative_path: &str,
+              preferred: &[String],
+              config: &settings::Config,
+              embedded: Vec<Track>) -> Vec<Track> {
+    let mut tracks = embedded;
+    for subtitle in discover(filepath, relative_path) {
+        let extension = subtitle.path.extension().and_then(OsStr::to_str).unwrap_or_default().to_lowercase();
+        if extension != "vtt" {
+            if let Err(err) = cached(&subtitle.path, &subtitle.relative, config) {
+                log::error!("Failed to convert subtitles to vtt: {}", err);
+                continue;
+            }
+        }
+        let encoded = form_urlencoded::byte_serialize(subtitle.relative.as_bytes()).collect::<String>();
+        tracks.push(Track {
+            url: format!("/track?file={}", encoded),
+            path: subtitle.relative,
+            ..parse_tags(&subtitle.tags)
+        });
+    }
+    tracks.sort_by(|a, b| a.label.cmp(&b.label).then_with(|| a.path.cmp(&b.path)));
+    // Labels have to be unique to be distinguished in the player
+    let mut labels: HashMap<String, usize> = HashMap::new();
+    for track in tracks.iter_mut() {
+        let count = labels.entry(track.label.clone()).or_default();
+        *count += 1;
+        if *count > 1 {
+            track.label = format!("{} {}", track.label, count);
+        }
+    }
+    let default = tracks.iter().position(|track| track.default)
+        .or_else(|| preferred.iter().find_map(|language| tracks.iter()
+            .position(|track| track.forced && track.language.as_deref() == Some(language.as_str()))));
+    for (idx, track) in tracks.iter_mut().enumerate() {
+        track.default = Some(idx) == default;
+    }
+    tracks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture_path(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join("subtitles").join(name)
+    }
+
+    fn fixture(name: &str) -> String {
+        String::from_utf8(fs::read(fixture_path(name)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(parse_timestamp("00:01:02,500"), Some(62_500));
+        assert_eq!(parse_timestamp("1:02:03.45"), Some(3_723_450));
+        assert_eq!(parse_timestamp("01:02.5"), Some(62_500));
+        assert_eq!(parse_timestamp("00:00:01:200"), Some(1_200));
+        assert_eq!(parse_timestamp("100:00:00,000"), Some(360_000_000));
+        assert_eq!(parse_timestamp("00:61:00,000"), None);
+        assert_eq!(parse_timestamp("-00:00:01,000"), None);
+        assert_eq!(parse_timestamp("Hello, world"), None);
+        assert_eq!(format_timestamp(3_723_450), "01:02:03.450");
+    }
+
+    #[test]
+    fn srt_with_bom_crlf_and_commas() {
+        let content = fixture("crlf_bom.srt");
+        assert!(content.starts_with('\u{feff}') && content.contains("\r\n"));
+        let cues = parse_srt(&content);
+        assert_eq!(cues.len(), 3);
+        assert_eq!(cues[0], Cue {
+            start: 1_000,
+            end: 4_074,
+            text: "Well, well, well...\nLook who's here, 1,000 miles from home.".to_string(),
+            settings: String::new(),
+        });
+        assert_eq!(cues[1].text, "<i>Yes, I know.</i>\nI'm late.");
+        assert_eq!(cues[2].text, "Tom &amp; Jerry &lt;3 -&gt; friends");
+    }
+
+    #[test]
+    fn srt_skips_empty_trailing_cue() {
+        let cues = parse_srt(&fixture("crlf_bom.srt"));
+        assert_eq!(cues.last().unwrap().start, 9_000);
+        assert!(cues.iter().all(|cue| !cue.text.is_empty()));
+    }
+
+    #[test]
+    fn srt_without_blank_lines_and_with_positions() {
+        let cues = parse_srt(&fixture("malformed.srt"));
+        assert_eq!(cues.len(), 4);
+        assert_eq!((cues[0].start, cues[0].end), (500, 2_000));
+        assert_eq!(cues[0].text, "No blank line after this one");
+        assert_eq!(cues[1].text, "Coordinates after the end time");
+        assert_eq!(cues[1].end, 5_250);
+        assert_eq!(cues[2].text, "Top of the screen");
+        assert_eq!(cues[2].settings, "line:0");
+        assert_eq!(cues[3].text, "<b>Red text</b>\nspans two blocks");
+    }
+
+    #[test]
+    fn ass_with_styles_and_overrides() {
+        let cues = parse_ass(&fixture("styled.ass"));
+        assert_eq!(cues.len(), 5);
+        assert_eq!(cues[0], Cue {
+            start: 1_000,
+            end: 3_500,
+            text: "Hello, world. Commas, in, dialogue.\nSecond line".to_string(),
+            settings: String::new(),
+        });
+        assert_eq!(cues[1].text, "<i>Thinking out loud</i>");
+        assert_eq!(cues[2].text, "Plain <i>italic</i> and <b>bold</b>");
+        assert_eq!(cues[3].text, "Sign on top");
+        assert_eq!(cues[3].settings, "line:0");
+        assert_eq!(cues[4].text, "Soft break\u{a0}here &amp; there");
+        assert_eq!(cues[4].start, 10_120);
+    }
+
+    #[test]
+    fn ssa_with_legacy_alignment() {
+        let cues = parse_ass(&fixture("legacy.ssa"));
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].text, "<b>Top centered title</b>");
+        assert_eq!(cues[0].settings, "line:0");
+        assert_eq!(cues[1].text, "Bottom left, with a comma");
+        assert_eq!(cues[1].settings, "align:left");
+    }
+
+    #[test]
+    fn parses_filename_tags() {
+        let track = parse_tags("forced.en");
+        assert_eq!((track.language.as_deref(), track.label.as_str(), track.forced, track.sdh), (Some("en"), "English (Forced)", true, false));
+        let track = parse_tags("2_Spanish");
+        assert_eq!((track.language.as_deref(), track.label.as_str()), (Some("es"), "Spanish"));
+        let track = parse_tags("eng.hi");
+        assert_eq!((track.language.as_deref(), track.sdh), (Some("en"), true));
+        let track = parse_tags("hi");
+        assert_eq!((track.language.as_deref(), track.label.as_str()), (Some("hi"), "Hindi"));
+        let track = parse_tags("pt-BR.default");
+        assert_eq!((track.language.as_deref(), track.default), (Some("pt"), true));
+        assert_eq!(parse_tags("").label, "Subtitles");
+        assert_eq!(preferred_languages("es-ES,es;q=0.9,en-US;q=0.8,*;q=0.5"), vec!["es", "en"]);
+    }
+
+    #[test]
+    fn writes_vtt() {
+        let cues = vec![
+            Cue { start: 0, end: 1_500, text: "First".to_string(), settings: String::new() },
+            Cue { start: 3_600_000, end: 3_601_000, text: "Second\nline".to_string(), settings: "line:0".to_string() },
+        ];
+        assert_eq!(to_vtt(&cues), "WEBVTT\n\n00:00:00.000 --> 00:00:01.500\nFirst\n\n01:00:00.000 --> 01:00:01.000 line:0\nSecond\nline\n");
+        assert_eq!(to_vtt(&[]), "WEBVTT\n");
+    }
+
+    #[test]
+    fn decodes_windows_1252() {
+        let bytes = fs::read(fixture_path("windows1252.srt")).unwrap();
+        assert!(std::str::from_utf8(&bytes).is_err());
+        let cues = parse_srt(&decode(&bytes));
+        assert_eq!(cues.len(), 3);
+        assert_eq!(cues[0].text, "Café crème, s’il vous plaît.");
+        assert_eq!(cues[1].text, "¿Dónde está el niño? — ¡Aquí!");
+        assert_eq!(cues[2].text, "Das Mädchen aß große Äpfel für 5 €.");
+    }
+
+    #[test]
+    fn decodes_utf16_with_and_without_bom() {
+        let content = fixture("crlf_bom.srt");
+        let little_endian: Vec<u8> = content.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let big_endian: Vec<u8> = content.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        let without_bom = content.trim_start_matches('\u{feff}');
+        let unmarked: Vec<u8> = without_bom.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert_eq!(decode(&little_endian), without_bom);
+        assert_eq!(decode(&big_endian), without_bom);
+        assert_eq!(decode(&unmarked), without_bom);
+        assert_eq!(parse_srt(&decode(&unmarked)), parse_srt(&content));
+        assert_eq!(decode("Plain ASCII and UTF-8 ✓".as_bytes()), "Plain ASCII and UTF-8 ✓");
+    }
+
+    #[test]
+    fn vtt_skips_blocks_without_timing() {
+        let cues = parse_vtt(&fixture("notes.vtt"));
+        assert_eq!(cues.len(), 3);
+        assert_eq!((cues[0].start, cues[0].end), (500, 1_500));
+        assert_eq!(cues[0].text, "Opening line");
+        assert_eq!(cues[1].settings, "align:start line:0");
+        assert_eq!(cues[1].text, "<i>Hello</i> &amp; welcome\nSecond line");
+        assert_eq!(cues[2].text, "Last cue");
+        let converted = parse_srt(&fixture("crlf_bom.srt"));
+        assert_eq!(parse_vtt(&to_vtt(&converted)), converted);
+    }
+
+    #[test]
+    fn shifts_cues_by_offset() {
+        let cues = parse_vtt(&fixture("notes.vtt"));
+        let delayed = shift(cues.clone(), 1_250);
+        assert_eq!((delayed[0].start, delayed[0].end), (1_750, 2_750));
+        assert_eq!(delayed[1].settings, cues[1].settings);
+        let advanced = shift(cues.clone(), -1_500);
+        assert_eq!(advanced.len(), 2);
+        assert_eq!((advanced[0].start, advanced[0].end), (0, 2_500));
+        assert_eq!((advanced[1].start, advanced[1].end), (5_500, 7_750));
+        assert_eq!(shift(cues.clone(), 0), cues);
+    }
+}
@@ -0,0 +1,347 @@
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use url::form_urlencoded;
+
+use crate::constant;
+use crate::squire::{probe, settings, transcode};
+
+/// Width of the thumbnails in pixels, the height is scaled to retain the aspect ratio.
+const THUMBNAIL_WIDTH: u32 = 320;
+
+/// Position of the poster frame, as a fraction of the video's duration.
+const POSTER_POSITION: f64 = 0.1;
+
+/// Number of seconds between the frames in the seek-preview sprite sheets.
+const PREVIEW_INTERVAL: u64 = 10;
+
+/// Width and height of each frame in the sprite sheets.
+const TILE_SIZE: (u32, u32) = (160, 90);
+
+/// Number of columns and rows of frames in each sprite sheet.
+const TILE_GRID: (u32, u32) = (10, 10);
+
+/// Name of the WebVTT thumbnails track, written only after all the sprite sheets are generated.
+const PREVIEW_TRACK: &str = "thumbnails.vtt";
+
+/// Maximum number of videos waiting for a worker, further videos are dropped until they are requested again.
+const MAX_QUEUE: usize = 64;
+
+/// Counter to generate unique names for the thumbnails being written, so concurrent requests don't collide.
+static PARTIAL_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Struct to generate the thumbnails using `ffmpeg` and cache them outside the media tree.
+///
+/// ## Fields
+///
+/// * `enabled` - Boolean flag to indicate if `ffmpeg` is available.
+/// * `ffmpeg` - Path to the `ffmpeg` binary.
+/// * `directory` - Directory where the thumbnails are cached.
+/// * `previews` - Directory where the seek-preview sprite sheets and tracks are cached.
+/// * `pending` - Cache keys of the videos, for which the sprite sheets are queued or being generated.
+/// * `queue` - Videos waiting for a worker to generate the sprite sheets.
+/// * `queued` - Condition variable to wake up the workers, when a video is queued.
+pub struct Thumbnailer {
+    pub enabled: bool,
+    ffmpeg: PathBuf,
+    directory: PathBuf,
+    previews: PathBuf,
+    pending: Mutex<HashSet<String>>,
+    queue: Mutex<VecDeque<Preview>>,
+    queued: Condvar,
+}
+
+/// Represents a video that is queued for the seek-preview sprite sheets.
+struct Preview {
+    key: String,
+    filepath: PathBuf,
+    relative_path: String,
+    prober: Arc<probe::Prober>,
+}
+
+/// Instantiates the `Thumbnailer` struct, after verifying that `ffmpeg` can be executed.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+///
+/// ## See Also
+///
+/// - Sprite sheets are generated by `max_extractions` worker threads, so the number of `ffmpeg` processes is bounded.
+///
+/// # Returns
+///
+/// Returns the constructed `Arc` for the `Thumbnailer` struct.
+pub fn thumbnailer_info(config: &settings::Config) -> Arc<Thumbnailer> {
+    let enabled = transcode::available(&config.ffmpeg, "thumbnails will not be available", log::Level::Info);
+    let thumbnailer = Arc::new(Thumbnailer {
+        enabled,
+        ffmpeg: config.ffmpeg.clone(),
+        directory: config.cache_dir.join("thumbnails"),
+        previews: config.cache_dir.join("previews"),
+        pending: Mutex::new(HashSet::new()),
+        queue: Mutex::new(VecDeque::new()),
+        queued: Condvar::new(),
+    });
+    if enabled {
+        for _ in 0..config.max_extractions {
+            let worker = thumbnailer.clone();
+            std::thread::spawn(move || worker.work());
+        }
+    }
+    thumbnailer
+}
+
+/// Formats the number of seconds as a WebVTT timestamp.
+///
+/// # Arguments
+///
+/// * `seconds` - Number of seconds from the start of the video.
+///
+/// # Returns
+///
+/// Returns the timestamp formatted as `HH:MM:SS.mmm`.
+fn timestamp(seconds: f64) -> String {
+    let millis = (seconds * 1000.0).round() as u64;
+    format!("{:02}:{:02}:{:02}.{:03}",
+            millis / 3_600_000, (millis % 3_600_000) / 60_000, (millis % 60_000) / 1000, millis % 1000)
+}
+
+/// Builds the WebVTT thumbnails track, with a cue for each frame in the sprite sheets.
+///
+/// # Arguments
+///
+/// * `duration` - Duration of the video in seconds.
+/// * `sheets` - Number of sprite sheets that were generated.
+/// * `url` - URL of the preview endpoint for the video, to which the sheet number is appended.
+///
+/// # Returns
+///
+/// Returns the content of the WebVTT track as a `String`.
+fn preview_track(duration: f64, sheets: u32, url: &str) -> String {
+    let per_sheet = TILE_GRID.0 * TILE_GRID.1;
+    let frames = ((duration / PREVIEW_INTERVAL as f64).ceil() as u32).min(sheets * per_sheet);
+    let mut track = String::from("WEBVTT\n");
+    for frame in 0..frames {
+        let start = (frame as u64 * PREVIEW_INTERVAL) as f64;
+        let end = ((frame as u64 + 1) * PREVIEW_INTERVAL) as f64;
+        let position = frame % per_sheet;
+        track.push_str(&format!(
+            "\n{} --> {}\n{}&sheet={}#xywh={},{},{},{}\n",
+            timestamp(start), timestamp(end.min(duration)), url, frame / per_sheet + 1,
+            (position % TILE_GRID.0) * TILE_SIZE.0, (position / TILE_GRID.0) * TILE_SIZE.1, TILE_SIZE.0, TILE_SIZE.1
+        ));
+    }
+    track
+}
+
+impl Thumbnailer {
+    /// Generates the thumbnail for the media file, unless it is already cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepath` - True path of the media file.
+    /// * `relative_path` - Path of the media file, relative to `media_source`.
+    /// * `prober` - Prober struct to get the duration of the video, for the poster frame's position.
+    ///
+    /// ## See Also
+    ///
+    /// - Videos use a frame at 10% of the duration as the poster, to skip the intro/black frames.
+    /// - Images are downscaled, but never upscaled.
+    /// - SVGs cannot be decoded by `ffmpeg` and scale without any loss, so the original file is used as-is.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the path to the thumbnail, or an error message.
+    pub fn generate(&self,
+                    filepath: &Path,
+                    relative_path: &str,
+                    prober: &probe::Prober) -> Result<PathBuf, String> {
+        if !self.enabled {
+            return Err("Thumbnails are not available".to_string());
+        }
+        if filepath.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("svg")) {
+            return Ok(filepath.to_path_buf());
+        }
+        let key = transcode::cache_key(filepath, relative_path).ok_or("Unable to read the file's metadata")?;
+        let thumbnail = self.directory.join(format!("{}.jpg", key));
+        if thumbnail.exists() {
+            return Ok(thumbnail);
+        }
+        std::fs::create_dir_all(&self.directory)
+            .map_err(|err| format!("Failed to create {:?}: {}", self.directory, err))?;
+        let extension = filepath.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+        let mut command = Command::new(&self.ffmpeg);
+        command.args(["-hide_banner", "-loglevel", "error", "-nostdin", "-y"]);
+        if !constant::IMAGE_FORMATS.contains(&extension.as_str()) {
+            let position = prober.probe(filepath, relative_path)
+                .map(|info| info.duration * POSTER_POSITION)
+                .unwrap_or_default();
+            command.args(["-ss", &format!("{:.2}", position)]);
+        }
+        // Thumbnail is written to a temporary file first, so a partial thumbnail is never served
+        let temporary = thumbnail.with_extension(format!("{}.tmp.jpg", PARTIAL_FILES.fetch_add(1, Ordering::Relaxed)));
+        let output = command
+            .arg("-i")
+            .arg(filepath)
+            .args(["-frames:v", "1", "-vf", &format!("scale='min({},iw)':-2", THUMBNAIL_WIDTH), "-q:v", "4"])
+            .arg(&temporary)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|err| format!("Failed to run {:?}: {}", self.ffmpeg, err))?;
+        if !output.status.success() || !temporary.exists() {
+            let _ = std::fs::remove_file(&temporary);
+            return Err(format!("Failed to generate a thumbnail for {:?}: {}",
+                               filepath, String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        std::fs::rename(&temporary, &thumbnail)
+            .map_err(|err| format!("Failed to store {:?}: {}", thumbnail, err))?;
+        Ok(thumbnail)
+    }
+
+    /// Looks up the seek-preview track for the video, without generating it.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepath` - True path of the media file.
+    /// * `relative_path` - Path of the media file, relative to `media_source`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the path to the WebVTT thumbnails track, if the sprite sheets are generated for the current version of the file.
+    pub fn preview(&self, filepath: &Path, relative_path: &str) -> Option<PathBuf> {
+        let key = transcode::cache_key(filepath, relative_path)?;
+        let track = self.previews.join(key).join(PREVIEW_TRACK);
+        if track.exists() { Some(track) } else { None }
+    }
+
+    /// Looks up a sprite sheet for the video.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepath` - True path of the media file.
+    /// * `relative_path` - Path of the media file, relative to `media_source`.
+    /// * `sheet` - Number of the sprite sheet, starting from 1.
+    ///
+    /// # Returns
+    ///
+    /// Returns the path to the sprite sheet, if it exists.
+    pub fn sprite_sheet(&self, filepath: &Path, relative_path: &str, sheet: u32) -> Option<PathBuf> {
+        let key = transcode::cache_key(filepath, relative_path)?;
+        let sprite = self.previews.join(key).join(format!("sheet_{:03}.jpg", sheet));
+        if sprite.exists() { Some(sprite) } else { None }
+    }
+
+    /// Generates the sprite sheets and the WebVTT thumbnails track for the video.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepath` - True path of the media file.
+    /// * `relative_path` - Path of the media file, relative to `media_source`.
+    /// * `prober` - Prober struct to get the duration of the video, for the timestamps in the track.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the path to the WebVTT track, or an error message.
+    fn generate_preview(&self,
+                        filepath: &Path,
+                        relative_path: &str,
+                        prober: &probe::Prober) -> Result<PathBuf, String> {
+        let key = transcode::cache_key(filepath, relative_path).ok_or("Unable to read the file's metadata")?;
+        let duration = prober.probe(filepath, relative_path)
+            .map(|info| info.duration)
+            .filter(|duration| *duration > 0.0)
+            .ok_or(format!("Unable to get the duration of {:?}", filepath))?;
+        let output = self.previews.join(&key);
+        // Sheets from an incomplete run are discarded, since the track is written only on success
+        let _ = std::fs::remove_dir_all(&output);
+        std::fs::create_dir_all(&output)
+            .map_err(|err| format!("Failed to create {:?}: {}", output, err))?;
+        let filter = format!(
+            "fps=1/{interval},scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,tile={c}x{r}",
+            interval = PREVIEW_INTERVAL, w = TILE_SIZE.0, h = TILE_SIZE.1, c = TILE_GRID.0, r = TILE_GRID.1
+        );
+        let result = Command::new(&self.ffmpeg)
+            .args(["-hide_banner", "-loglevel", "error", "-nostdin", "-y", "-i"])
+            .arg(filepath)
+            .args(["-an", "-sn", "-vf", &filter, "-q:v", "5"])
+            .arg(output.join("sheet_%03d.jpg"))
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|err| format!("Failed to run {:?}: {}", self.ffmpeg, err))?;
+        let sheets = std::fs::read_dir(&output)
+            .map(|entries| entries.flatten()
+                .filter(|entry| entry.file_name().to_string_lossy().starts_with("sheet_"))
+                .count() as u32)
+            .unwrap_or_default();
+        if !result.status.success() || sheets == 0 {
+            return Err(format!("Failed to generate the sprite sheets for {:?}: {}",
+                               filepath, String::from_utf8_lossy(&result.stderr).trim()));
+        }
+        let url = format!("/preview?file={}", form_urlencoded::byte_serialize(relative_path.as_bytes()).collect::<String>());
+        let track = output.join(PREVIEW_TRACK);
+        std::fs::write(&track, preview_track(duration, sheets, &url))
+            .map_err(|err| format!("Failed to store {:?}: {}", track, err))?;
+        Ok(track)
+    }
+
+    /// Queues the video for the seek-preview sprite sheets, since it requires decoding the whole video.
+    ///
+    /// # Arguments
+    ///
+    /// * `prober` - Reference counted `Prober` to get the duration of the video.
+    /// * `filepath` - True path of the media file.
+    /// * `relative_path` - Path of the media file, relative to `media_source`.
+    pub fn schedule_preview(&self,
+                            prober: &Arc<probe::Prober>,
+                            filepath: &Path,
+                            relative_path: &str) {
+        if !self.enabled || !prober.enabled {
+            return;
+        }
+        let key = match transcode::cache_key(filepath, relative_path) {
+            Some(key) => key,
+            None => return
+        };
+        if !self.pending.lock().unwrap().insert(key.clone()) {
+            return;
+        }
+        let mut queue = self.queue.lock().unwrap();
+        if queue.len() >= MAX_QUEUE {
+            log::warn!("Unable to queue the seek-preview for {:?}, {} videos are already waiting", filepath, queue.len());
+            drop(queue);
+            self.pending.lock().unwrap().remove(&key);
+            return;
+        }
+        queue.push_back(Preview {
+            key,
+            filepath: filepath.to_path_buf(),
+            relative_path: relative_path.to_string(),
+            prober: Arc::clone(prober),
+        });
+        self.queued.notify_one();
+    }
+
+    /// Generates the queued seek-previews one after the other, waiting for a video when the queue is empty.
+    fn work(&self) {
+        loop {
+            let preview = {
+                let mut queue = self.queue.lock().unwrap();
+                loop {
+                    match queue.pop_front() {
+                        Some(next) => break next,
+                        None => queue = self.queued.wait(queue).unwrap()
+                    }
+                }
+            };
+            match self.generate_preview(&preview.filepath, &preview.relative_path, &preview.prober) {
+                Ok(track) => log::info!("Generated seek-preview track {:?}", track),
+                Err(err) => log::warn!("{}", err)
+            }
+            self.pending.lock().unwrap().remove(&preview.key);
+        }
+    }
+}
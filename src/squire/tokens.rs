@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::squire::{secure, settings, storage};
+
+/// Prefix for the API tokens, to make them easily identifiable.
+const PREFIX: &str = "rst";
+
+/// Resolution (1 minute) of the `last_used` time, so a busy client doesn't rewrite the tokens on every request.
+const LAST_USED_RESOLUTION: i64 = 60;
+
+/// Struct to store the information of an API token.
+///
+/// ## See Also
+///
+/// - Only the SHA-512 digest of the secret is stored, so the token is shown only once during creation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    /// Unique identifier of the token, which is also the first part of the token.
+    pub id: String,
+    /// Username that the token belongs to.
+    pub username: String,
+    /// Name of the token to identify the client it was created for.
+    pub name: String,
+    /// Highest role the token is allowed to act as, capped by the user's role.
+    pub scope: settings::Role,
+    /// Epoch time when the token was created.
+    pub created: i64,
+    /// Epoch time when the token expires, never expires if not set.
+    pub expiry: Option<i64>,
+    /// Epoch time when the token was last used, accurate up to a minute.
+    pub last_used: i64,
+    /// SHA-512 digest of the token's secret.
+    #[serde(skip_serializing_if = "String::is_empty", default)]
+    digest: String,
+}
+
+/// Struct to store the API tokens, persisted in the `storage` directory.
+///
+/// ## Fields
+///
+/// * `tokens` - API tokens keyed by their ID.
+/// * `filepath` - JSON file where the API tokens are stored.
+pub struct Tokens {
+    pub tokens: Mutex<HashMap<String, ApiToken>>,
+    filepath: PathBuf,
+}
+
+/// Instantiates the `Tokens` struct with the API tokens restored from the `storage` directory.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+///
+/// # Returns
+///
+/// Returns the constructed `Tokens` struct.
+pub fn tokens_info(config: &settings::Config) -> Tokens {
+    let filepath = config.storage.join("tokens.json");
+    let tokens: HashMap<String, ApiToken> = storage::load_json(&filepath);
+    Tokens {
+        tokens: Mutex::new(tokens),
+        filepath,
+    }
+}
+
+impl ApiToken {
+    /// Returns a copy of the token without the digest, so it can be shared with the client.
+    pub fn public(&self) -> ApiToken {
+        ApiToken { digest: String::new(), ..self.clone() }
+    }
+}
+
+impl Tokens {
+    /// Writes the current API tokens to the `storage` directory.
+    fn persist(&self, tokens: &HashMap<String, ApiToken>) {
+        storage::save_json(&self.filepath, tokens);
+    }
+
+    /// Creates a new API token for the user.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username of the user.
+    /// * `name` - Name of the token.
+    /// * `scope` - Highest role the token is allowed to act as.
+    /// * `duration` - Number of seconds the token should be valid for, never expires if not set.
+    ///
+    /// # Returns
+    ///
+    /// Returns a tuple of the token (in plain text) and the created `ApiToken`.
+    pub fn create(&self,
+                  username: &str,
+                  name: &str,
+                  scope: settings::Role,
+                  duration: Option<i64>) -> (String, ApiToken) {
+        let current_time = Utc::now().timestamp();
+        let id = secure::keygen()[..16].to_string();
+        let secret = secure::keygen();
+        let api_token = ApiToken {
+            id: id.to_string(),
+            username: username.to_string(),
+            name: name.to_string(),
+            scope,
+            created: current_time,
+            expiry: duration.map(|duration| current_time + duration),
+            last_used: 0,
+            digest: secure::calculate_hash(secret.to_string()),
+        };
+        let mut tokens = self.tokens.lock().unwrap();
+        tokens.insert(id.to_string(), api_token.clone());
+        self.persist(&tokens);
+        (format!("{}.{}.{}", PREFIX, id, secret), api_token.public())
+    }
+
+    /// Verifies the API token received in the `Authorization` header.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - API token in plain text.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the `ApiToken` if valid, or an error message.
+    pub fn verify(&self, token: &str) -> Result<ApiToken, &'static str> {
+        let parts: Vec<&str> = token.splitn(3, '.').collect();
+        if parts.len() != 3 || parts[0] != PREFIX {
+            return Err("Invalid API token format");
+        }
+        let mut tokens = self.tokens.lock().unwrap();
+        let api_token = match tokens.get_mut(parts[1]) {
+            Some(api_token) if api_token.digest == secure::calculate_hash(parts[2].to_string()) => api_token,
+            _ => return Err("Invalid API token")
+        };
+        let current_time = Utc::now().timestamp();
+        if api_token.expiry.is_some_and(|expiry| expiry <= current_time) {
+            return Err("API token has expired");
+        }
+        if current_time - api_token.last_used < LAST_USED_RESOLUTION {
+            return Ok(api_token.public());
+        }
+        api_token.last_used = current_time;
+        let api_token = api_token.public();
+        self.persist(&tokens);
+        Ok(api_token)
+    }
+
+    /// Lists the API tokens created by a user.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username of the user.
+    ///
+    /// # Returns
+    ///
+    /// Returns a vector of `ApiToken` without the digest, sorted by the creation time.
+    pub fn list(&self, username: &str) -> Vec<ApiToken> {
+        let mut tokens: Vec<ApiToken> = self.tokens.lock().unwrap().values()
+            .filter(|api_token| api_token.username == username)
+            .map(ApiToken::public)
+            .collect();
+        tokens.sort_by_key(|api_token| api_token.created);
+        tokens
+    }
+
+    /// Revokes an API token created by a user.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ID of the API token.
+    /// * `username` - Username of the user.
+    ///
+    /// # Returns
+    ///
+    /// Returns a boolean flag to indicate whether the API token was revoked.
+    pub fn revoke(&self, id: &str, username: &str) -> bool {
+        let mut tokens = self.tokens.lock().unwrap();
+        if tokens.get(id).is_some_and(|api_token| api_token.username == username) {
+            tokens.remove(id);
+            self.persist(&tokens);
+            return true;
+        }
+        false
+    }
+}
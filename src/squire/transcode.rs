@@ -0,0 +1,681 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use actix_web::web;
+
+use crate::squire::{probe, secure, settings};
+
+/// Represents a bitrate rendition that the videos are transcoded into.
+pub struct Rendition {
+    /// Name of the rendition, used as the playlist and segment prefix.
+    pub name: &'static str,
+    /// Maximum height of the video, the source is never upscaled.
+    pub height: u32,
+    /// Video bitrate in kilobits per second.
+    pub bitrate: u32,
+}
+
+/// Renditions generated for the adaptive streams, in the decreasing order of quality.
+pub const RENDITIONS: [Rendition; 4] = [
+    Rendition { name: "1080p", height: 1080, bitrate: 5000 },
+    Rendition { name: "720p", height: 720, bitrate: 2800 },
+    Rendition { name: "480p", height: 480, bitrate: 1400 },
+    Rendition { name: "360p", height: 360, bitrate: 800 },
+];
+
+/// Audio bitrate in kilobits per second, for all the renditions.
+const AUDIO_BITRATE: u32 = 128;
+
+/// Duration of each segment in seconds.
+const SEGMENT_DURATION: u32 = 6;
+
+/// Number of bytes read from the head of a playlist, to check if the first segment is listed.
+const PLAYLIST_HEAD: u64 = 4096;
+
+/// Interval (in seconds) at which the idle jobs are killed and the cache size is enforced.
+const REAPER_INTERVAL: u64 = 10;
+
+/// Represents a running `ffmpeg` process and the last time its output was requested.
+struct Job {
+    child: Child,
+    last_access: Instant,
+}
+
+/// Represents the reasons for which a transcoding job couldn't be started.
+pub enum JobError {
+    /// Maximum number of transcoding jobs are already running.
+    Busy,
+    /// Output directory couldn't be prepared or the process couldn't be started.
+    Failed(String),
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JobError::Busy => write!(f, "Maximum number of transcoding jobs are running"),
+            JobError::Failed(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Struct to keep track of the media that can be transcoded and the `ffmpeg` processes that are running.
+///
+/// ## Fields
+///
+/// * `hls` - Boolean flag to indicate if `ffmpeg` is available and HLS is enabled.
+/// * `dash` - Boolean flag to indicate if `ffmpeg` is available and MPEG-DASH is enabled.
+/// * `media` - Cache keys and the path of the media file (relative to `media_source`) they were generated for.
+/// * `jobs` - Running `ffmpeg` processes keyed by the cache key and the output (rendition name or `dash`).
+/// * `accessed` - Time when the output of each cache key was last requested, to evict the least recently used.
+/// * `max_jobs` - Maximum number of `ffmpeg` processes that can run at the same time.
+/// * `idle_timeout` - Duration after which a process is killed, if its output is not requested.
+/// * `max_cache_size` - Maximum size of the transcoded output in bytes.
+/// * `outputs` - Directories where the transcoded output is cached.
+pub struct Transcoder {
+    pub hls: bool,
+    pub dash: bool,
+    media: Mutex<HashMap<String, String>>,
+    jobs: Mutex<HashMap<String, Job>>,
+    accessed: Mutex<HashMap<String, SystemTime>>,
+    max_jobs: usize,
+    idle_timeout: Duration,
+    max_cache_size: u64,
+    outputs: Vec<PathBuf>,
+}
+
+/// Instantiates the `Transcoder` struct, after verifying that the `ffmpeg` binary can be executed.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+///
+/// ## See Also
+///
+/// - A background thread kills the idle jobs and removes the least recently used output beyond the cache size.
+///
+/// # Returns
+///
+/// Returns the constructed `Arc` for the `Transcoder` struct.
+pub fn transcoder_info(config: &settings::Config) -> Arc<Transcoder> {
+    let runnable = (config.hls || config.dash)
+        && available(&config.ffmpeg, "adaptive streaming is disabled", log::Level::Error);
+    let transcoder = Arc::new(Transcoder {
+        hls: runnable && config.hls,
+        dash: runnable && config.dash,
+        media: Mutex::new(HashMap::new()),
+        jobs: Mutex::new(HashMap::new()),
+        accessed: Mutex::new(HashMap::new()),
+        max_jobs: config.max_transcodes,
+        idle_timeout: Duration::from_secs(config.transcode_timeout.max(1) as u64),
+        max_cache_size: config.transcode_cache_size as u64,
+        outputs: vec![config.cache_dir.join("hls"), config.cache_dir.join("dash")],
+    });
+    if runnable {
+        let reaper = transcoder.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(Duration::from_secs(REAPER_INTERVAL));
+            reaper.kill_idle();
+            reaper.evict();
+        });
+    }
+    transcoder
+}
+
+/// Verifies that the `ffmpeg` or `ffprobe` binary can be executed, by running it with `-version`
+///
+/// # Arguments
+///
+/// * `binary` - Path to the binary.
+/// * `fallback` - Description of what is disabled without the binary, to be logged.
+/// * `level` - Log level for the failure, since some features are optional.
+///
+/// # Returns
+///
+/// Returns a boolean flag to indicate if the binary is available.
+pub fn available(binary: &Path, fallback: &str, level: log::Level) -> bool {
+    match Command::new(binary).arg("-version").stdout(Stdio::null()).stderr(Stdio::null()).status() {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            log::log!(level, "{:?} exited with {}, {}", binary, status, fallback);
+            false
+        }
+        Err(err) => {
+            log::log!(level, "Unable to run {:?}: {}, {}", binary, err, fallback);
+            false
+        }
+    }
+}
+
+/// Generates a cache key for the media file, which changes whenever the file is modified.
+///
+/// # Arguments
+///
+/// * `filepath` - True path of the media file.
+/// * `relative_path` - Path of the media file, relative to `media_source`.
+///
+/// # Returns
+///
+/// Returns the cache key if the file's metadata could be read.
+pub fn cache_key(filepath: &Path, relative_path: &str) -> Option<String> {
+    let modified = fs::metadata(filepath).ok()?.modified().ok()?;
+    let epoch = modified.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or_default();
+    let digest = secure::calculate_hash(format!("{}:{}", relative_path, epoch));
+    Some(digest[..32].to_string())
+}
+
+/// Looks up the rendition by its name.
+///
+/// # Arguments
+///
+/// * `name` - Name of the rendition.
+///
+/// # Returns
+///
+/// Returns a reference to the `Rendition` if it exists.
+pub fn rendition(name: &str) -> Option<&'static Rendition> {
+    RENDITIONS.iter().find(|rendition| rendition.name == name)
+}
+
+/// Registers the media file for adaptive streaming and generates the URLs for the enabled formats.
+///
+/// # Arguments
+///
+/// * `transcoder` - Transcoder struct that keeps track of the media available for adaptive streaming.
+/// * `filepath` - True path of the media file.
+/// * `relative_path` - Path of the media file, relative to `media_source`.
+///
+/// # Returns
+///
+/// Returns a tuple of the optional URLs for the HLS master playlist and the DASH manifest.
+pub fn sources(transcoder: &Transcoder,
+               filepath: &Path,
+               relative_path: &str) -> (Option<String>, Option<String>) {
+    if !transcoder.hls && !transcoder.dash {
+        return (None, None);
+    }
+    let key = match cache_key(filepath, relative_path) {
+        Some(key) => key,
+        None => return (None, None),
+    };
+    transcoder.register(&key, relative_path);
+    let hls = transcoder.hls.then(|| format!("/hls/{}/master.m3u8", key));
+    let dash = transcoder.dash.then(|| format!("/dash/{}/manifest.mpd", key));
+    (hls, dash)
+}
+
+impl Transcoder {
+    /// Registers the media file with its cache key, so the playlists and segments can be served by the key.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Cache key of the media file.
+    /// * `relative_path` - Path of the media file, relative to `media_source`.
+    pub fn register(&self, key: &str, relative_path: &str) {
+        self.media.lock().unwrap().insert(key.to_string(), relative_path.to_string());
+    }
+
+    /// Looks up the media file registered for the cache key.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Cache key of the media file.
+    ///
+    /// # Returns
+    ///
+    /// Returns the path of the media file relative to `media_source`, if registered.
+    pub fn lookup(&self, key: &str) -> Option<String> {
+        self.media.lock().unwrap().get(key).cloned()
+    }
+
+    /// Checks if an `ffmpeg` process is still running for the cache key and output.
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - Job identifier, which is the cache key and the rendition name or `dash`.
+    ///
+    /// # Returns
+    ///
+    /// Returns a boolean flag to indicate if the process is running, reaping it if it has exited.
+    pub fn is_running(&self, job: &str) -> bool {
+        let mut jobs = self.jobs.lock().unwrap();
+        let running = match jobs.get_mut(job) {
+            Some(running) => match running.child.try_wait() {
+                Ok(None) => true,
+                Ok(Some(status)) => {
+                    if !status.success() {
+                        log::error!("Transcoding job '{}' exited with {}", job, status);
+                    }
+                    false
+                }
+                Err(err) => {
+                    log::error!("Failed to check the transcoding job '{}': {}", job, err);
+                    false
+                }
+            },
+            None => return false,
+        };
+        if !running {
+            jobs.remove(job);
+        }
+        running
+    }
+
+    /// Records that the output of the job was requested, so it is neither killed nor evicted.
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - Job identifier, which is the cache key and the rendition name or `dash`.
+    pub fn touch(&self, job: &str) {
+        if let Some(running) = self.jobs.lock().unwrap().get_mut(job) {
+            running.last_access = Instant::now();
+        }
+        let key = job.split('/').next().unwrap_or_default();
+        self.accessed.lock().unwrap().insert(key.to_string(), SystemTime::now());
+    }
+
+    /// Checks if the maximum number of jobs are running, reaping the ones that have exited.
+    ///
+    /// # Returns
+    ///
+    /// Returns a boolean flag to indicate if a new job can be started.
+    fn has_capacity(&self) -> bool {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|_, running| matches!(running.child.try_wait(), Ok(None)));
+        jobs.len() < self.max_jobs
+    }
+
+    /// Spawns the `ffmpeg` process for the job and keeps track of it.
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - Job identifier, which is the cache key and the rendition name or `dash`.
+    /// * `command` - `ffmpeg` command with all the arguments.
+    /// * `description` - Description of the job to be logged.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with a `JobError` if the process couldn't be started.
+    fn start(&self, job: String, mut command: Command, description: String) -> Result<(), JobError> {
+        let mut jobs = self.jobs.lock().unwrap();
+        if jobs.len() >= self.max_jobs {
+            log::warn!("Unable to transcode {}, {} jobs are already running", description, jobs.len());
+            return Err(JobError::Busy);
+        }
+        let child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|err| JobError::Failed(format!("Failed to start {:?}: {}", command.get_program(), err)))?;
+        log::info!("Transcoding {}", description);
+        jobs.insert(job.to_string(), Job { child, last_access: Instant::now() });
+        drop(jobs);
+        self.touch(&job);
+        Ok(())
+    }
+
+    /// Kills the jobs whose output has not been requested within the idle timeout.
+    ///
+    /// ## See Also
+    ///
+    /// - The output of a killed job is incomplete, so it is discarded and regenerated on the next request.
+    pub fn kill_idle(&self) {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|job, running| {
+            if running.last_access.elapsed() < self.idle_timeout {
+                return true;
+            }
+            log::info!("Killing the transcoding job '{}', since it has been idle for {:?}", job, self.idle_timeout);
+            let _ = running.child.kill();
+            let _ = running.child.wait();
+            false
+        });
+    }
+
+    /// Removes the output of the least recently used media, until the cache fits within its maximum size.
+    ///
+    /// ## See Also
+    ///
+    /// - Output of the media that are being transcoded is never removed.
+    /// - Output that was not requested since the server started falls back to its modified time.
+    pub fn evict(&self) {
+        let running: Vec<String> = self.jobs.lock().unwrap().keys()
+            .filter_map(|job| job.split('/').next().map(str::to_string))
+            .collect();
+        let accessed = self.accessed.lock().unwrap().clone();
+        let mut entries = Vec::new();
+        let mut total = 0;
+        for output in &self.outputs {
+            for entry in fs::read_dir(output).into_iter().flatten().flatten() {
+                let key = entry.file_name().to_string_lossy().to_string();
+                let size = directory_size(&entry.path());
+                total += size;
+                if running.contains(&key) {
+                    continue;
+                }
+                let last_used = accessed.get(&key).copied()
+                    .or_else(|| entry.metadata().and_then(|metadata| metadata.modified()).ok())
+                    .unwrap_or(UNIX_EPOCH);
+                entries.push((last_used, size, entry.path(), key));
+            }
+        }
+        if total <= self.max_cache_size {
+            return;
+        }
+        entries.sort_by_key(|(last_used, ..)| *last_used);
+        for (_, size, path, key) in entries {
+            if total <= self.max_cache_size {
+                break;
+            }
+            match fs::remove_dir_all(&path) {
+                Ok(_) => {
+                    log::info!("Removed {:?} to keep the transcoded cache within its size limit", path);
+                    total = total.saturating_sub(size);
+                    self.accessed.lock().unwrap().remove(&key);
+                    // Key is forgotten once neither of the outputs is cached, the page has to be reloaded to stream it
+                    if !self.outputs.iter().any(|output| output.join(&key).exists()) {
+                        self.media.lock().unwrap().remove(&key);
+                    }
+                }
+                Err(err) => log::error!("Failed to remove {:?}: {}", path, err),
+            }
+        }
+    }
+
+    /// Starts transcoding the media file into the HLS rendition, unless it is running or already complete.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Configuration data for the application.
+    /// * `key` - Cache key of the media file.
+    /// * `rendition` - Rendition that has to be generated.
+    /// * `output` - Directory where the playlist and the segments are written.
+    ///
+    /// ## See Also
+    ///
+    /// - The playlist is generated as an `event` playlist, so the player can start before transcoding completes.
+    /// - Incomplete output from an earlier run (without `#EXT-X-ENDLIST`) is discarded and regenerated.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the path to the rendition's playlist, or a `JobError`.
+    pub fn ensure_hls(&self,
+                      config: &settings::Config,
+                      key: &str,
+                      rendition: &Rendition,
+                      output: &Path) -> Result<PathBuf, JobError> {
+        let playlist = output.join(format!("{}.m3u8", rendition.name));
+        let job = format!("{}/{}", key, rendition.name);
+        if self.is_running(&job) || is_complete(&playlist) {
+            return Ok(playlist);
+        }
+        if !self.has_capacity() {
+            return Err(JobError::Busy);
+        }
+        let relative_path = self.lookup(key)
+            .ok_or(JobError::Failed("Media is not registered for transcoding".to_string()))?;
+        clear_output(output, &format!("{}_", rendition.name));
+        let _ = fs::remove_file(&playlist);
+        fs::create_dir_all(output)
+            .map_err(|err| JobError::Failed(format!("Failed to create {:?}: {}", output, err)))?;
+        let mut command = Command::new(&config.ffmpeg);
+        command
+            .args(["-hide_banner", "-loglevel", "error", "-nostdin", "-i"])
+            .arg(config.media_source.join(&relative_path))
+            .args(["-map", "0:v:0", "-map", "0:a:0?"])
+            .args(["-vf", &scale_filter(rendition)])
+            .args(["-c:v", "libx264", "-preset", "veryfast", "-profile:v", "main"])
+            .args(["-b:v", &format!("{}k", rendition.bitrate)])
+            .args(["-maxrate", &format!("{}k", rendition.bitrate)])
+            .args(["-bufsize", &format!("{}k", rendition.bitrate * 2)])
+            .args(["-sc_threshold", "0", "-force_key_frames", &format!("expr:gte(t,n_forced*{})", SEGMENT_DURATION)])
+            .args(["-c:a", "aac", "-ac", "2", "-b:a", &format!("{}k", AUDIO_BITRATE)])
+            .args(["-f", "hls", "-hls_time", &SEGMENT_DURATION.to_string(), "-hls_playlist_type", "event"])
+            .arg("-hls_segment_filename")
+            .arg(output.join(format!("{}_%05d.ts", rendition.name)))
+            .arg(&playlist);
+        self.start(job, command, format!("'{}' into {} HLS", relative_path, rendition.name))?;
+        Ok(playlist)
+    }
+
+    /// Starts transcoding the media file into MPEG-DASH with all the renditions, unless it is running or complete.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Configuration data for the application.
+    /// * `key` - Cache key of the media file.
+    /// * `output` - Directory where the manifest and the segments are written.
+    ///
+    /// ## See Also
+    ///
+    /// - All renditions are generated by a single process, since they are listed in the same manifest.
+    /// - Incomplete output from an earlier run (manifest that is not `static`) is discarded and regenerated.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the path to the manifest, or a `JobError`.
+    pub fn ensure_dash(&self,
+                       config: &settings::Config,
+                       key: &str,
+                       output: &Path) -> Result<PathBuf, JobError> {
+        let manifest = output.join("manifest.mpd");
+        let job = format!("{}/dash", key);
+        if self.is_running(&job) || is_static(&manifest) {
+            return Ok(manifest);
+        }
+        if !self.has_capacity() {
+            return Err(JobError::Busy);
+        }
+        let relative_path = self.lookup(key)
+            .ok_or(JobError::Failed("Media is not registered for transcoding".to_string()))?;
+        clear_output(output, "");
+        fs::create_dir_all(output)
+            .map_err(|err| JobError::Failed(format!("Failed to create {:?}: {}", output, err)))?;
+        let mut command = Command::new(&config.ffmpeg);
+        command
+            .args(["-hide_banner", "-loglevel", "error", "-nostdin", "-i"])
+            .arg(config.media_source.join(&relative_path));
+        for _ in RENDITIONS.iter() {
+            command.args(["-map", "0:v:0"]);
+        }
+        command.args(["-map", "0:a:0?"]);
+        command.args(["-c:v", "libx264", "-preset", "veryfast", "-profile:v", "main"]);
+        for (index, rendition) in RENDITIONS.iter().enumerate() {
+            command
+                .args([format!("-filter:v:{}", index), scale_filter(rendition)])
+                .args([format!("-b:v:{}", index), format!("{}k", rendition.bitrate)])
+                .args([format!("-maxrate:v:{}", index), format!("{}k", rendition.bitrate)])
+                .args([format!("-bufsize:v:{}", index), format!("{}k", rendition.bitrate * 2)]);
+        }
+        command
+            .args(["-sc_threshold", "0", "-force_key_frames", &format!("expr:gte(t,n_forced*{})", SEGMENT_DURATION)])
+            .args(["-c:a", "aac", "-ac", "2", "-b:a", &format!("{}k", AUDIO_BITRATE)])
+            .args(["-f", "dash", "-seg_duration", &SEGMENT_DURATION.to_string()])
+            .args(["-use_template", "1", "-use_timeline", "1"])
+            .args(["-adaptation_sets", "id=0,streams=v id=1,streams=a"])
+            .args(["-init_seg_name", "init-$RepresentationID$.m4s"])
+            .args(["-media_seg_name", "chunk-$RepresentationID$-$Number%05d$.m4s"])
+            .arg(&manifest);
+        self.start(job, command, format!("'{}' into DASH", relative_path))?;
+        Ok(manifest)
+    }
+}
+
+/// Removes the files generated by an earlier run from the output directory.
+///
+/// # Arguments
+///
+/// * `output` - Directory where the generated files are stored.
+/// * `prefix` - Prefix of the files that have to be removed, all files are removed if empty.
+fn clear_output(output: &Path, prefix: &str) {
+    for entry in fs::read_dir(output).into_iter().flatten().flatten() {
+        if entry.file_name().to_string_lossy().starts_with(prefix) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Calculates the total size of the files in a directory.
+///
+/// # Arguments
+///
+/// * `directory` - Directory with the transcoded output.
+///
+/// # Returns
+///
+/// Returns the size in bytes.
+fn directory_size(directory: &Path) -> u64 {
+    fs::read_dir(directory).into_iter().flatten().flatten()
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Generates the scale filter for the rendition, which retains the aspect ratio and never upscales the source.
+///
+/// # Arguments
+///
+/// * `rendition` - Rendition that has to be generated.
+///
+/// # Returns
+///
+/// Returns the `ffmpeg` filter as a `String`.
+fn scale_filter(rendition: &Rendition) -> String {
+    format!("scale=-2:'trunc(min({},ih)/2)*2'", rendition.height)
+}
+
+/// Checks if the DASH manifest has been completely generated.
+///
+/// # Arguments
+///
+/// * `manifest` - Path to the manifest.
+///
+/// # Returns
+///
+/// Returns a boolean flag to indicate if the manifest is `static`, which is written once all segments are ready.
+pub fn is_static(manifest: &Path) -> bool {
+    fs::read_to_string(manifest).is_ok_and(|content| content.contains("type=\"static\""))
+}
+
+/// Checks if the playlist has been completely generated.
+///
+/// # Arguments
+///
+/// * `playlist` - Path to the playlist.
+///
+/// # Returns
+///
+/// Returns a boolean flag to indicate if the playlist contains the end tag.
+pub fn is_complete(playlist: &Path) -> bool {
+    fs::read_to_string(playlist).is_ok_and(|content| content.contains("#EXT-X-ENDLIST"))
+}
+
+/// Checks if the playlist has at least one segment, so it can be served to the player.
+///
+/// # Arguments
+///
+/// * `playlist` - Path to the playlist.
+///
+/// ## See Also
+///
+/// - Only the head of the playlist is read, since the first segment is listed right after the header.
+///
+/// # Returns
+///
+/// Returns a boolean flag to indicate if the playlist is playable.
+pub fn is_playable(playlist: &Path) -> bool {
+    let mut head = String::new();
+    fs::File::open(playlist)
+        .and_then(|file| file.take(PLAYLIST_HEAD).read_to_string(&mut head))
+        .is_ok_and(|_| head.contains("#EXTINF"))
+}
+
+/// Lists the renditions that are not taller than the source, since the source is never upscaled.
+///
+/// # Arguments
+///
+/// * `height` - Height of the source video, `0` if unknown.
+///
+/// # Returns
+///
+/// Returns a vector of the renditions, with at least the lowest rendition for the sources shorter than all of them.
+fn renditions(height: u32) -> Vec<&'static Rendition> {
+    let fitting: Vec<&Rendition> = RENDITIONS.iter()
+        .filter(|rendition| height == 0 || rendition.height <= height)
+        .collect();
+    if fitting.is_empty() {
+        return RENDITIONS.last().into_iter().collect();
+    }
+    fitting
+}
+
+/// Gets the height of the source video from the probed metadata, without blocking the worker.
+///
+/// # Arguments
+///
+/// * `prober` - Prober struct that extracts and caches the metadata of the media files.
+/// * `filepath` - True path of the media file.
+/// * `relative_path` - Path of the media file, relative to `media_source`.
+///
+/// # Returns
+///
+/// Returns the height of the video, `0` if the file couldn't be probed.
+pub async fn source_height(prober: &Arc<probe::Prober>, filepath: PathBuf, relative_path: String) -> u32 {
+    let prober = prober.clone();
+    web::block(move || prober.probe(&filepath, &relative_path)).await.ok().flatten()
+        .map(|info| info.height)
+        .unwrap_or_default()
+}
+
+/// Generates the master playlist with the renditions that fit the source.
+///
+/// # Arguments
+///
+/// * `height` - Height of the source video, `0` if unknown.
+///
+/// # Returns
+///
+/// Returns the master playlist as a `String`, with the rendition playlists relative to the master playlist.
+pub fn master_playlist(height: u32) -> String {
+    let mut playlist = String::from("#EXTM3U\n#EXT-X-VERSION:3\n");
+    for rendition in renditions(height) {
+        let bandwidth = (rendition.bitrate + AUDIO_BITRATE) * 1000;
+        playlist.push_str(&format!(
+            "#EXT-X-STREAM-INF:BANDWIDTH={},NAME=\"{}\"\n{}.m3u8\n", bandwidth, rendition.name, rendition.name
+        ));
+    }
+    playlist
+}
+
+/// Waits until the condition is met or the timeout is reached.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum number of seconds to wait.
+/// * `condition` - Closure that returns `true` when the wait is over.
+///
+/// ## See Also
+///
+/// - The condition reads the filesystem, so it is checked on the blocking thread pool instead of the worker.
+///
+/// # Returns
+///
+/// Returns a boolean flag to indicate if the condition was met.
+pub async fn wait_for<F>(timeout: u64, condition: F) -> bool
+where
+    F: Fn() -> bool + Clone + Send + 'static,
+{
+    for _ in 0..(timeout * 2) {
+        if web::block(condition.clone()).await.unwrap_or_default() {
+            return true;
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(500)).await;
+    }
+    web::block(condition).await.unwrap_or_default()
+}
@@ -3,7 +3,7 @@
 /// # See Also
 ///
 /// - This page is served as a response for all the entry points,
-/// when the user tries to access a page without valid authentication.
+///   when the user tries to access a page without valid authentication.
 ///
 /// # Returns
 ///
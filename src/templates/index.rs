@@ -180,9 +180,14 @@ pub fn get_content() -> String {
             }
             return "\\u" + arr.join("\\u");
         }
-        async function CalculateHash(username, password, timestamp) {
-            const message = username + password + timestamp;
-            const encoder = new TextEncoder();
+        const encoder = new TextEncoder();
+        function BytesToHex(bytes) {
+            return Array.from(bytes).map(byte => byte.toString(16).padStart(2, '0')).join('');
+        }
+        function HexToBytes(hex) {
+            return new Uint8Array(hex.match(/.{2}/g).map(byte => parseInt(byte, 16)));
+        }
+        async function CalculateHash(message) {
             const data = encoder.encode(message);
             if (crypto.subtle === undefined) {
                 const wordArray = CryptoJS.lib.WordArray.create(data);
@@ -191,17 +196,63 @@ pub fn get_content() -> String {
                 return hash.toString(CryptoJS.enc.Hex);
             } else {
                 const hashBuffer = await crypto.subtle.digest('SHA-512', data);
-                const hashArray = Array.from(new Uint8Array(hashBuffer));
-                // Convert each byte to a hexadecimal string, pad with zeros, and join them to form the final hash
-                return hashArray.map(byte => byte.toString(16).padStart(2, '0')).join('');
+                return BytesToHex(new Uint8Array(hashBuffer));
+            }
+        }
+        async function HashBytes(hex) {
+            if (crypto.subtle === undefined) {
+                return CryptoJS.SHA512(CryptoJS.enc.Hex.parse(hex)).toString(CryptoJS.enc.Hex);
+            }
+            const hashBuffer = await crypto.subtle.digest('SHA-512', HexToBytes(hex));
+            return BytesToHex(new Uint8Array(hashBuffer));
+        }
+        async function CalculateHmac(keyHex, message) {
+            if (crypto.subtle === undefined) {
+                return CryptoJS.HmacSHA512(message, CryptoJS.enc.Hex.parse(keyHex)).toString(CryptoJS.enc.Hex);
             }
+            const key = await crypto.subtle.importKey(
+                'raw', HexToBytes(keyHex), {name: 'HMAC', hash: 'SHA-512'}, false, ['sign']
+            );
+            const signature = await crypto.subtle.sign('HMAC', key, encoder.encode(message));
+            return BytesToHex(new Uint8Array(signature));
+        }
+        async function DeriveKey(password, salt, iterations) {
+            if (crypto.subtle === undefined) {
+                return CryptoJS.PBKDF2(password, CryptoJS.enc.Hex.parse(salt), {
+                    keySize: 512 / 32, iterations: iterations, hasher: CryptoJS.algo.SHA512
+                }).toString(CryptoJS.enc.Hex);
+            }
+            const key = await crypto.subtle.importKey('raw', encoder.encode(password), 'PBKDF2', false, ['deriveBits']);
+            const bits = await crypto.subtle.deriveBits(
+                {name: 'PBKDF2', hash: 'SHA-512', salt: HexToBytes(salt), iterations: iterations}, key, 512
+            );
+            return BytesToHex(new Uint8Array(bits));
         }
         let hex_user = await ConvertStringToHex(username);
         let hex_pass = await ConvertStringToHex(password);
         let timestamp = Math.round(new Date().getTime() / 1000);
-        let hash = await CalculateHash(hex_user, hex_pass, timestamp)
-        let authHeaderValue = hex_user + ',' + hash + ',' + timestamp;
         let origin = window.location.origin
+        let challenge;
+        try {
+            // One-time nonce issued by the server, so the authorization header cannot be replayed
+            // Salt and iterations of the stored hash are returned along with the nonce
+            challenge = await $.ajax({method: "GET", url: origin.concat("/challenge"), data: {username: username}});
+        } catch (error) {
+            if (error.hasOwnProperty("responseJSON")) {
+                alert(error.responseJSON.detail);
+            } else {
+                alert("ERROR: Unable to reach the server, please try again!");
+            }
+            return false;
+        }
+        // SCRAM style proof, the server only stores the hash of the client key and never receives it in the clear
+        let salted = await DeriveKey(await CalculateHash(hex_user + hex_pass), challenge.salt, challenge.iterations);
+        let clientKey = await CalculateHmac(salted, "Client Key");
+        let serverKey = await HashBytes(clientKey);
+        let signature = await CalculateHmac(serverKey, hex_user + ',' + timestamp + ',' + challenge.nonce);
+        let clientBytes = HexToBytes(clientKey);
+        let proof = BytesToHex(HexToBytes(signature).map((byte, idx) => byte ^ clientBytes[idx]));
+        let authHeaderValue = hex_user + ',' + proof + ',' + timestamp + ',' + challenge.nonce;
         $.ajax({
             method: "POST",
             url: origin.concat("/login"),
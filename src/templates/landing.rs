@@ -3,6 +3,7 @@
 /// # See Also
 ///
 /// - This page is served as a response for the `/stream` entry point.
+/// - Navigation and user controls are hidden when served for a share link via `/shared`.
 ///
 /// # Returns
 ///
@@ -55,6 +56,15 @@ pub fn get_content() -> String {
             font-size: 16px;
             cursor: pointer;
         }
+        .share {
+            position: absolute;
+            top: 3.8%;
+            left: 30px;
+            border: none;
+            padding: 10px 14px;
+            font-size: 16px;
+            cursor: pointer;
+        }
         .home {
             position: absolute;
             top: 3.8%;
@@ -94,6 +104,16 @@ pub fn get_content() -> String {
         h1 {
             text-align: center;
         }
+        .media-info {
+            text-align: center;
+            font-size: 14px;
+            opacity: 0.6;
+        }
+        .player-settings {
+            text-align: center;
+            font-size: 14px;
+            margin: 10px auto;
+        }
     </style>
     <!-- Size of the container and the player -->
     <style>
@@ -130,6 +150,15 @@ pub fn get_content() -> String {
             width: 100%;
             display: block;
         }
+        .seek-preview {
+            display: none;
+            position: absolute;
+            bottom: 3em;
+            border: 1px solid #fff;
+            background-repeat: no-repeat;
+            pointer-events: none;
+            z-index: 2;
+        }
         @media (max-width: 768px) {
             #image-source {
                 height: auto;
@@ -201,18 +230,26 @@ pub fn get_content() -> String {
     </noscript>
 </head>
 <body>
-    <button class="upload" onclick="upload()"><i class="fa-solid fa-cloud-arrow-up"></i> Upload</button>
-    <button class="home" onclick="goHome()"><i class="fa fa-home"></i> Home</button>
-    <button class="back" onclick="goBack()"><i class="fa fa-backward"></i> Back</button>
-    <div class="dropdown">
-        <button class="dropbtn"><i class="fa fa-user"></i></button>
-        <div class="dropdown-content">
-            <a onclick="goProfile()" style="cursor: pointer;"><i class="fa-solid fa-user-lock"></i> {{ user }}</a>
-            <a onclick="logOut()" style="cursor: pointer"><i class="fa fa-sign-out"></i> logout</a>
+    {% if not shared %}
+        <button class="share" onclick="share()"><i class="fa-solid fa-share-nodes"></i> Share</button>
+        {% if role != 'viewer' %}
+            <button class="upload" onclick="upload()"><i class="fa-solid fa-cloud-arrow-up"></i> Upload</button>
+        {% endif %}
+        <button class="home" onclick="goHome()"><i class="fa fa-home"></i> Home</button>
+        <button class="back" onclick="goBack()"><i class="fa fa-backward"></i> Back</button>
+        <div class="dropdown">
+            <button class="dropbtn"><i class="fa fa-user"></i></button>
+            <div class="dropdown-content">
+                <a onclick="goProfile()" style="cursor: pointer;"><i class="fa-solid fa-user-lock"></i> {{ user }}</a>
+                <a onclick="logOut()" style="cursor: pointer"><i class="fa fa-sign-out"></i> logout</a>
+            </div>
         </div>
-    </div>
+    {% endif %}
     <br><br><br>
     <h1>{{ media_title }}</h1>
+    {% if media_info %}
+        <p class="media-info">{{ media_info }}</p>
+    {% endif %}
     {% if render_image %}
         <img id="image-source" src="" onclick="fullScreen()">
     {% else %}
@@ -231,8 +268,20 @@ pub fn get_content() -> String {
                    }
                  }
                }'>
+                <!-- Adaptive streams are preferred, raw file is used as a fallback -->
+                {% if hls %}
+                    <source id="hls-source" type="application/x-mpegURL" src="{{ hls }}"/>
+                {% endif %}
+                {% if dash %}
+                    <source id="dash-source" type="application/dash+xml" src="{{ dash }}"/>
+                {% endif %}
                 <source id="video-source" type="video/mp4" src=""/>
-                <track id="subtitles" kind="subtitles" src="" srclang="en"/>
+                {% for track in tracks %}
+                    <track kind="{% if track.sdh %}captions{% else %}subtitles{% endif %}" src="{{ track.url }}{% if subtitles.offset %}&offset={{ subtitles.offset }}{% endif %}"{% if track.language %} srclang="{{ track.language }}"{% endif %} label="{{ track.label|e }}"{% if track.default %} default{% endif %}/>
+                {% endfor %}
+                {% if previews %}
+                    <track id="previews" kind="metadata" label="thumbnails" src="{{ previews }}"/>
+                {% endif %}
                 <p class="vjs-no-js">
                     To view this video please enable JavaScript, and consider upgrading to a
                     web browser that
@@ -240,15 +289,39 @@ pub fn get_content() -> String {
                 </p>
             </video>
         </div>
+        {% if resume %}
+            <button class="iter" id="resume" onclick="resumePlayback()"><i class="fa-solid fa-clock-rotate-left"></i> Resume from {{ resume_str }}</button>
+        {% endif %}
+        {% if tracks %}
+            <div class="player-settings">
+                <i class="fa-solid fa-closed-captioning"></i> Subtitle delay
+                <button onclick="shiftSubtitles(-250)" title="Show the subtitles earlier">-</button>
+                <span id="subtitle-offset"></span>
+                <button onclick="shiftSubtitles(250)" title="Show the subtitles later">+</button>
+                <button onclick="saveSubtitleOffset()">Save as default</button>
+                <span id="subtitle-status"></span>
+            </div>
+        {% endif %}
+        {% if audio_tracks %}
+            <div class="player-settings">
+                <label for="audio-track"><i class="fa-solid fa-headphones"></i> Audio</label>
+                <select id="audio-track" onchange="switchAudio(this.value)">
+                    {% for audio in audio_tracks %}
+                        <option value="{{ audio.url }}">{{ audio.label|e }}</option>
+                    {% endfor %}
+                </select>
+                <span id="audio-status"></span>
+            </div>
+        {% endif %}
     {% endif %}
     <div id="nav-container">
         {% if previous %}
-            <button class="iter" style="float: left" onclick="window.location='{{ previous }}'" title="{{ previous }}">
+            <button class="iter" style="float: left" onclick="window.location='{{ previous }}{{ sort_query }}'" title="{{ previous }}">
                 <i class="fa fa-backward"></i> Previous
             </button>
         {% endif %}
         {% if next %}
-            <button class="iter" style="float: right" onclick="window.location='{{ next }}'" title="{{ next }}">
+            <button class="iter" style="float: right" onclick="window.location='{{ next }}{{ sort_query }}'" title="{{ next }}">
                 Next <i class="fa fa-forward"></i>
             </button>
         {% endif %}
@@ -265,8 +338,6 @@ pub fn get_content() -> String {
             let imageElement = document.getElementById("image-source");
             imageElement.setAttribute("src", imageSource);
         {% else %}
-            let track = "{{ track }}";
-
             // Construct the source URL for video by combining origin and path
             let videoSource = origin + path;
 
@@ -274,15 +345,185 @@ pub fn get_content() -> String {
             let videoElement = document.getElementById("video-source");
             videoElement.setAttribute("src", videoSource);
 
-            // Set the subtitles URL for the video
-            let trackElement = document.getElementById("subtitles");
-            trackElement.setAttribute("src", track);
-
             let videoPlayer = document.getElementById("video-player");
             videoPlayer.load(); // Load the video
             // videoPlayer.play(); // Play the video
         {% endif %}
     </script>
+    {% if not render_image and not shared %}
+    <script>
+        // Reports the playback position periodically, so the video can be resumed later
+        let lastReported = 0;
+        function reportProgress(player) {
+            let duration = player.duration();
+            if (!duration || !isFinite(duration)) {
+                return;
+            }
+            lastReported = Date.now();
+            fetch("/progress", {
+                method: "POST",
+                headers: {"Content-Type": "application/json"},
+                body: JSON.stringify({
+                    file: decodeURIComponent(window.location.pathname.replace(/^\/stream\//, "")),
+                    position: player.currentTime(),
+                    duration: duration
+                }),
+                keepalive: true
+            });
+        }
+        {% if resume %}
+        function resumePlayback() {
+            let player = videojs("video-player");
+            player.currentTime({{ resume }});
+            player.play();
+            document.getElementById("resume").style.display = "none";
+        }
+        {% endif %}
+        window.addEventListener("load", function () {
+            let player = videojs("video-player");
+            player.on("timeupdate", function () {
+                if (!player.paused() && Date.now() - lastReported > 10000) {
+                    reportProgress(player);
+                }
+            });
+            player.on("pause", function () { reportProgress(player); });
+            player.on("ended", function () { reportProgress(player); });
+        });
+    </script>
+    {% endif %}
+    {% if tracks %}
+    {% if subtitles.font_size != 100 %}
+    <style>
+        ::cue {
+            font-size: {{ subtitles.font_size }}%;
+        }
+    </style>
+    {% endif %}
+    <script>
+        // Shifts the subtitles by replacing the tracks with the ones shifted on the server
+        let subtitleOffset = {{ subtitles.offset }};
+        function displayOffset() {
+            document.getElementById("subtitle-offset").innerText = (subtitleOffset / 1000).toFixed(2) + "s";
+        }
+        function shiftSubtitles(delta) {
+            subtitleOffset = Math.max(-{{ max_offset }}, Math.min({{ max_offset }}, subtitleOffset + delta));
+            displayOffset();
+            let player = videojs("video-player");
+            Array.from(player.remoteTextTracks())
+                .filter(track => track.kind === "subtitles" || track.kind === "captions")
+                .forEach(function (track) {
+                    let url = new URL(track.src, origin);
+                    url.searchParams.set("offset", subtitleOffset);
+                    let mode = track.mode;
+                    player.removeRemoteTextTrack(track);
+                    player.addRemoteTextTrack({
+                        kind: track.kind, src: url.pathname + url.search, srclang: track.language, label: track.label, mode: mode
+                    }, false);
+                });
+        }
+        function saveSubtitleOffset() {
+            let status = document.getElementById("subtitle-status");
+            fetch("/preferences", {
+                method: "POST",
+                headers: {"Content-Type": "application/json"},
+                body: JSON.stringify({
+                    offset: subtitleOffset,
+                    font_size: {{ subtitles.font_size }},
+                    language: {% if subtitles.language %}"{{ subtitles.language }}"{% else %}null{% endif %}
+                })
+            }).then(function (response) {
+                status.innerText = response.ok ? "Saved" : "Failed to save";
+            });
+        }
+        window.addEventListener("load", function () {
+            displayOffset();
+            let player = videojs("video-player");
+            // Emulated text tracks are styled by the player, instead of the ::cue pseudo-element
+            if (player.textTrackSettings && {{ subtitles.font_size }} !== 100) {
+                player.textTrackSettings.setValues({fontPercent: {{ subtitles.font_size }} / 100});
+                player.textTrackSettings.updateDisplay();
+            }
+        });
+    </script>
+    {% endif %}
+    {% if audio_tracks %}
+    <script>
+        // Switches to the variant with the chosen audio track, which is remuxed on the server when requested first
+        let audioRequest = 0;
+        function switchAudio(url) {
+            let request = ++audioRequest;
+            let status = document.getElementById("audio-status");
+            let poll = function () {
+                fetch(url, {headers: {"Range": "bytes=0-0"}}).then(function (response) {
+                    if (request !== audioRequest) {
+                        return;  // Another audio track was chosen meanwhile
+                    }
+                    if (response.status === 503 && response.headers.has("Retry-After")) {
+                        status.innerText = "Preparing the audio track...";
+                        setTimeout(poll, parseInt(response.headers.get("Retry-After")) * 1000);
+                        return;
+                    }
+                    if (!response.ok) {
+                        status.innerText = "Audio track is not available";
+                        return;
+                    }
+                    status.innerText = "";
+                    let player = videojs("video-player");
+                    let position = player.currentTime();
+                    let paused = player.paused();
+                    // Remuxed variant keeps the container of the media file, so the type is taken from the response
+                    let type = (response.headers.get("Content-Type") || "video/mp4").split(";")[0];
+                    player.src({src: origin + url, type: type});
+                    player.one("loadedmetadata", function () {
+                        player.currentTime(position);
+                        if (!paused) {
+                            player.play();
+                        }
+                    });
+                });
+            };
+            poll();
+        }
+    </script>
+    {% endif %}
+    {% if previews %}
+    <script>
+        // Shows the frame from the sprite sheets, that matches the position of the cursor on the progress bar
+        window.addEventListener("load", function () {
+            let player = videojs("video-player");
+            let track = Array.from(player.textTracks()).find(track => track.kind === "metadata" && track.label === "thumbnails");
+            if (!track) {
+                return;
+            }
+            track.mode = "hidden";  // Loads the cues without rendering them
+            let progress = player.controlBar.progressControl;
+            let preview = document.createElement("div");
+            preview.className = "seek-preview";
+            progress.el().appendChild(preview);
+            progress.on("mousemove", function (event) {
+                let bar = progress.el().getBoundingClientRect();
+                let offset = Math.min(Math.max(event.clientX - bar.left, 0), bar.width);
+                let time = (offset / bar.width) * player.duration();
+                let cue = Array.from(track.cues || []).find(cue => cue.startTime <= time && time < cue.endTime);
+                if (!cue) {
+                    preview.style.display = "none";
+                    return;
+                }
+                let [url, fragment] = cue.text.trim().split("#xywh=");
+                let [x, y, w, h] = fragment.split(",").map(Number);
+                preview.style.backgroundImage = `url("${url}")`;
+                preview.style.backgroundPosition = `-${x}px -${y}px`;
+                preview.style.width = `${w}px`;
+                preview.style.height = `${h}px`;
+                preview.style.left = `${Math.min(Math.max(offset - w / 2, 0), bar.width - w)}px`;
+                preview.style.display = "block";
+            });
+            progress.on("mouseout", function () {
+                preview.style.display = "none";
+            });
+        });
+    </script>
+    {% endif %}
     <script>
         function goHome() {
             window.location.href = "/home";
@@ -299,6 +540,32 @@ pub fn get_content() -> String {
         function goBack() {
             window.history.back();
         }
+        function share() {
+            let hours = prompt("Number of hours the link should be valid for", "24");
+            if (hours === null) {
+                return;
+            }
+            let views = prompt("Maximum number of views (leave blank for unlimited)", "");
+            if (views === null) {
+                return;
+            }
+            let file = decodeURIComponent(window.location.pathname.replace(/^\/stream\//, ""));
+            let payload = {file: file, expiry: Math.round(parseFloat(hours) * 3600)};
+            if (views.trim() !== "") {
+                payload.max_views = parseInt(views);
+            }
+            fetch("/share", {
+                method: "POST",
+                headers: {"Content-Type": "application/json"},
+                body: JSON.stringify(payload)
+            }).then(response => response.json().then(data => {
+                if (response.ok) {
+                    prompt("Share link (copy to clipboard)", window.location.origin + data.url);
+                } else {
+                    alert(data.detail);
+                }
+            }));
+        }
     </script>
     <script>
         function fullScreen() {
@@ -338,7 +605,7 @@ pub fn get_content() -> String {
             function navigateLeft(event) {
                 if (event.key === 'ArrowLeft') {
                     // Navigate to the previous image
-                    window.location='{{ previous }}';
+                    window.location='{{ previous }}{{ sort_query }}';
                 }
             }
         {% endif %}
@@ -348,7 +615,7 @@ pub fn get_content() -> String {
             function navigateRight(event) {
                 if (event.key === 'ArrowRight') {
                     // Navigate to the next image
-                    window.location='{{ next }}';
+                    window.location='{{ next }}{{ sort_query }}';
                 }
             }
         {% endif %}
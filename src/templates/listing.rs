@@ -69,6 +69,72 @@ pub fn get_content() -> String {
             font-size: 16px;
             cursor: pointer;
         }
+        .media-info {
+            margin-left: 10px;
+            font-size: 12px;
+            opacity: 0.6;
+        }
+        .search input {
+            width: 300px;
+            max-width: 70%;
+            padding: 6px 10px;
+            font-size: 16px;
+        }
+        .search button {
+            border: none;
+            padding: 7px 12px;
+            font-size: 16px;
+            cursor: pointer;
+        }
+        .sorting {
+            display: inline;
+            margin-left: 10px;
+        }
+        .sorting select {
+            padding: 2px 6px;
+            font-size: 14px;
+        }
+        .pagination {
+            margin: 10px 0;
+        }
+        .pagination a, .pagination span {
+            margin-right: 15px;
+        }
+        .view-toggle {
+            margin-left: 10px;
+            border: none;
+            padding: 4px 10px;
+            font-size: 14px;
+            cursor: pointer;
+        }
+        .thumbnail {
+            display: none;
+        }
+        #files.grid {
+            display: grid;
+            grid-template-columns: repeat(auto-fill, minmax(220px, 1fr));
+            gap: 1rem;
+        }
+        #files.grid li {
+            margin: 0;
+            overflow: hidden;
+            text-overflow: ellipsis;
+        }
+        #files.grid li > i {
+            display: none;
+        }
+        #files.grid .thumbnail {
+            display: block;
+            width: 100%;
+            aspect-ratio: 16 / 9;
+            object-fit: cover;
+            margin-bottom: 0.3rem;
+            background: #80808040;
+        }
+        #files.grid .media-info {
+            display: block;
+            margin-left: 0;
+        }
     </style>
     <style>
         .dropbtn {
@@ -179,7 +245,9 @@ pub fn get_content() -> String {
 </noscript>
 <body translate="no">
     <div class="toggler fa fa-moon-o"></div>
-    <button class="upload" onclick="upload()"><i class="fa-solid fa-cloud-arrow-up"></i> Upload</button>
+    {% if role != 'viewer' %}
+        <button class="upload" onclick="upload()"><i class="fa-solid fa-cloud-arrow-up"></i> Upload</button>
+    {% endif %}
     <button class="home" onclick="goHome()"><i class="fa fa-home"></i> Home</button>
     <button class="back" onclick="goBack()"><i class="fa fa-backward"></i> Back</button>
     <div class="dropdown">
@@ -200,7 +268,29 @@ pub fn get_content() -> String {
     {% else %}
         <h1>Welcome to RuStream <small>v{{ version }}</small></h1>
     {% endif %}
+    <form class="search" action="/search" method="get">
+        <input type="search" name="q" placeholder="Search the library" value="{{ search|e }}" required>
+        <button type="submit"><i class="fa-solid fa-magnifying-glass"></i></button>
+    </form>
     <hr>
+    {% if search is defined %}
+        <h3>{{ files|length }} result(s) for '{{ search|e }}'</h3>
+    {% endif %}
+    {% if history %}
+        <h3>Recently Watched</h3>
+        {% for file in history %}
+            <li><i class="{{ file.font }}"></i>&nbsp;&nbsp;<a href="{{ file.path }}">{{ file.name }}</a><span class="media-info">{{ file.completion }}% watched · {{ file.watched }}</span></li>
+        {% endfor %}
+    {% endif %}
+    {% if recently_added %}
+        <h3>Recently Added</h3>
+        {% for file in recently_added %}
+            <li><i class="{{ file.font }}"></i>&nbsp;&nbsp;<a href="{{ file.path }}">{{ file.name }}</a><span class="media-info">{{ file.modified }}</span></li>
+        {% endfor %}
+    {% endif %}
+    {% if history or recently_added %}
+        <hr>
+    {% endif %}
     {% if dir_name or files or directories or secured_directories %}
         <!-- Display directory name if within subdir -->
         {% if dir_name %}
@@ -208,26 +298,58 @@ pub fn get_content() -> String {
         {% endif %}
         <!-- Display number of files and list the files -->
         {% if files %}
-            <h3>Files {{ files|length }}</h3>
+            <h3>Files {% if pagination %}{{ pagination.total }}{% else %}{{ files|length }}{% endif %}{% if thumbnails %}<button class="view-toggle" onclick="toggleView()" title="Toggle grid view"><i id="viewIcon" class="fa-solid fa-table-cells"></i></button>{% endif %}
+            {% if sorting %}
+                <form class="sorting" method="get">
+                    <select name="sort" onchange="this.form.submit()" title="Sort by">
+                        {% for value, label in [['name', 'Name'], ['mtime', 'Modified'], ['size', 'Size'], ['duration', 'Duration']] %}
+                            <option value="{{ value }}"{% if sorting.sort == value %} selected{% endif %}>{{ label }}</option>
+                        {% endfor %}
+                    </select>
+                    <select name="order" onchange="this.form.submit()" title="Order">
+                        <option value="asc"{% if sorting.order == 'asc' %} selected{% endif %}>Ascending</option>
+                        <option value="desc"{% if sorting.order == 'desc' %} selected{% endif %}>Descending</option>
+                    </select>
+                    {% if sorting.limit %}<input type="hidden" name="limit" value="{{ pagination.limit }}">{% endif %}
+                </form>
+            {% endif %}
+            </h3>
+            <div id="files">
             {% for file in files %}
-                {% if secure_path == 'true' %}
-                    <li><i class="{{ file.font }}"></i>&nbsp;&nbsp;<a oncontextmenu="showContextMenu(event, '{{ file.path }}')" href="{{ file.path }}">{{ file.name }}</a></li>
+                {% if secure_path == 'true' and role == 'admin' %}
+                    <li><i class="{{ file.font }}"></i>&nbsp;&nbsp;<a oncontextmenu="showContextMenu(event, '{{ file.path }}')" href="{{ file.path }}{{ sort_query }}">{% if thumbnails %}<img class="thumbnail" data-src="{{ file.thumbnail }}" alt="" loading="lazy">{% endif %}{{ file.name }}</a>{% if file.duration %}<span class="media-info">{{ file.resolution }} {{ file.duration }}</span>{% endif %}</li>
                 {% else %}
-                    <li><i class="{{ file.font }}"></i>&nbsp;&nbsp;<a href="{{ file.path }}">{{ file.name }}</a></li>
+                    <li><i class="{{ file.font }}"></i>&nbsp;&nbsp;<a href="{{ file.path }}{{ sort_query }}">{% if thumbnails %}<img class="thumbnail" data-src="{{ file.thumbnail }}" alt="" loading="lazy">{% endif %}{{ file.name }}</a>{% if file.duration %}<span class="media-info">{{ file.resolution }} {{ file.duration }}</span>{% endif %}</li>
                 {% endif %}
             {% endfor %}
+            </div>
+            {% if pagination and pagination.pages > 1 %}
+                <div class="pagination">
+                    {% if pagination.page > 1 %}
+                        <a href="?sort={{ sorting.sort }}&order={{ sorting.order }}&limit={{ pagination.limit }}&page={{ pagination.page - 1 }}"><i class="fa-solid fa-angle-left"></i> Previous</a>
+                    {% endif %}
+                    <span>Page {{ pagination.page }} of {{ pagination.pages }}</span>
+                    {% if pagination.page < pagination.pages %}
+                        <a href="?sort={{ sorting.sort }}&order={{ sorting.order }}&limit={{ pagination.limit }}&page={{ pagination.page + 1 }}">Next <i class="fa-solid fa-angle-right"></i></a>
+                    {% endif %}
+                </div>
+            {% endif %}
         {% endif %}
         <!-- Display number of directories and list the directories -->
         {% if directories %}
             <h3>Directories {{ directories|length }}</h3>
             {% for directory in directories %}
-                <li><i class="{{ directory.font }}"></i>&nbsp;&nbsp;<a href="{{ directory.path }}">{{ directory.name }}</a></li>
+                <li><i class="{{ directory.font }}"></i>&nbsp;&nbsp;<a href="{{ directory.path }}{{ sort_query }}">{{ directory.name }}</a></li>
             {% endfor %}
         {% endif %}
         {% if secured_directories %}
             <h3>Secured Directory</h3>
             {% for directory in secured_directories %}
-                <li><i class="{{ directory.font }}"></i>&nbsp;&nbsp;<a oncontextmenu="showContextMenu(event, '{{ directory.path }}', true)" href="{{ directory.path }}">{{ directory.name }}</a></li>
+                {% if role == 'admin' %}
+                    <li><i class="{{ directory.font }}"></i>&nbsp;&nbsp;<a oncontextmenu="showContextMenu(event, '{{ directory.path }}', true)" href="{{ directory.path }}{{ sort_query }}">{{ directory.name }}</a></li>
+                {% else %}
+                    <li><i class="{{ directory.font }}"></i>&nbsp;&nbsp;<a href="{{ directory.path }}{{ sort_query }}">{{ directory.name }}</a></li>
+                {% endif %}
             {% endfor %}
         {% endif %}
     {% else %}
@@ -251,6 +373,31 @@ pub fn get_content() -> String {
             window.history.back();
         }
     </script>
+    {% if thumbnails %}
+    <script>
+        // Thumbnails are only requested once the grid view is enabled, and loaded lazily by the browser
+        function setView(grid) {
+            let files = document.getElementById('files');
+            if (!files) {
+                return;
+            }
+            files.classList.toggle('grid', grid);
+            document.getElementById('viewIcon').className = grid ? 'fa-solid fa-list' : 'fa-solid fa-table-cells';
+            if (grid) {
+                files.querySelectorAll('img.thumbnail[data-src]').forEach(function (img) {
+                    img.src = img.dataset.src;
+                    img.removeAttribute('data-src');
+                });
+            }
+        }
+        function toggleView() {
+            let grid = !document.getElementById('files').classList.contains('grid');
+            localStorage.setItem('listingView', grid ? 'grid' : 'list');
+            setView(grid);
+        }
+        setView(localStorage.getItem('listingView') === 'grid');
+    </script>
+    {% endif %}
     <script>
         var contextMenu = document.getElementById('contextMenu');
 
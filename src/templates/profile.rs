@@ -132,7 +132,9 @@ pub fn get_content() -> String {
 </noscript>
 <body translate="no" onload="displayTimer(); displayExpiryUTC(); displayExpiryLocal()">
 <div class="toggler fa fa-moon-o"></div>
-<button class="upload" onclick="upload()"><i class="fa-solid fa-cloud-arrow-up"></i> Upload</button>
+{% if role != 'viewer' %}
+    <button class="upload" onclick="upload()"><i class="fa-solid fa-cloud-arrow-up"></i> Upload</button>
+{% endif %}
 <button class="home" onclick="goHome()"><i class="fa fa-home"></i> Home</button>
 <button class="back" onclick="goBack()"><i class="fa fa-backward"></i> Back</button>
 <div class="dropdown">
@@ -146,6 +148,8 @@ pub fn get_content() -> String {
 <hr>
 <br><br>
 <h4 style="text-align: center">Welcome {{ user }}</h3>
+<h4>Role</h4>
+<p>{{ role }}</p>
 <h4>Session Validity</h4>
 <p id="secondsCountDown"><p>
 <p id="validityUTC"></h5>
@@ -154,6 +158,46 @@ pub fn get_content() -> String {
     <h4>Last Accessed</h4>
     <i class="{{ file.font }}"></i>&nbsp;&nbsp;<a href="{{ file.path }}">{{ file.name }}</a>
 {% endif %}
+{% if watching %}
+    <h4>Continue Watching</h4>
+    {% for item in watching %}
+        <p>
+            <i class="fa-solid fa-circle-play"></i>&nbsp;&nbsp;<a href="{{ item.path }}">{{ item.name }}</a>
+            <br><small>Resume from {{ item.position }} ({{ item.completion }}% watched)</small>
+        </p>
+    {% endfor %}
+{% endif %}
+<h4>Subtitle Preferences</h4>
+<p>
+    <label for="subtitle-offset">Delay (milliseconds)</label>
+    <input type="number" id="subtitle-offset" step="250" value="{{ subtitles.offset }}">
+    <br><small>Positive values show the subtitles later, negative values show them earlier</small>
+</p>
+<p>
+    <label for="subtitle-font-size">Font size</label>
+    <select id="subtitle-font-size">
+        {% for size in font_sizes %}
+            <option value="{{ size }}"{% if size == subtitles.font_size %} selected{% endif %}>{{ size }}%</option>
+        {% endfor %}
+    </select>
+</p>
+<p>
+    <label for="subtitle-language">Language</label>
+    <input type="text" id="subtitle-language" placeholder="en or English" value="{{ subtitles.language or '' }}">
+    <br><small>Forced subtitles in this language are enabled by default, ahead of the browser's languages</small>
+</p>
+<button onclick="savePreferences()">Save</button>
+<span id="preferences-status"></span>
+{% if sessions %}
+    <h4>Active Sessions</h4>
+    {% for device in sessions %}
+        <p>
+            <i class="fa-solid fa-desktop"></i>&nbsp;&nbsp;{{ device.ip }} - {{ device.created }}
+            {% if device.current == 'true' %}<b>(current)</b>{% endif %}
+            <br><small>{{ device.user_agent }}</small>
+        </p>
+    {% endfor %}
+{% endif %}
 <script>
     function goHome() { window.location.href = "/home"; }
     function goProfile() { window.location.href = '/profile'; }
@@ -161,6 +205,29 @@ pub fn get_content() -> String {
     function upload() { window.location.href = "/upload"; }
     function goBack() { window.history.back(); }
 </script>
+<script>
+    function savePreferences() {
+        let status = document.getElementById("preferences-status");
+        fetch("/preferences", {
+            method: "POST",
+            headers: {"Content-Type": "application/json"},
+            body: JSON.stringify({
+                offset: parseInt(document.getElementById("subtitle-offset").value) || 0,
+                font_size: parseInt(document.getElementById("subtitle-font-size").value),
+                language: document.getElementById("subtitle-language").value || null
+            })
+        }).then(function (response) {
+            return response.json().then(function (data) {
+                if (response.ok) {
+                    document.getElementById("subtitle-language").value = data.language || "";
+                    status.innerText = "Saved";
+                } else {
+                    status.innerText = data.detail;
+                }
+            });
+        });
+    }
+</script>
 <script>
     function secondsToStr(seconds) {
         let levels = [